@@ -1,11 +1,22 @@
 use cosmwasm_std::{
-    attr, Addr, Binary, BlockInfo, Deps, DepsMut, Env, MessageInfo, Response, StdError, StdResult,
-    Storage, Uint128,
+    attr, Addr, Binary, BlockInfo, Deps, DepsMut, Env, MessageInfo, Order, Response, StdError,
+    StdResult, Storage, Timestamp, Uint128,
 };
 use cw20::{AllowanceResponse, Cw20ReceiveMsg, Expiration};
+use cw_storage_plus::Bound;
 
+use crate::contract::validate_memo;
 use crate::error::ContractError;
-use crate::state::{ALLOWANCES, ALLOWANCES_SPENDER, BALANCES, TOKEN_INFO};
+use crate::hooks::prepare_hooks;
+use crate::msg::{
+    AllowanceReceiveMsg, AllowanceRefillResponse, BalanceChangeKind, RecurringAllowanceResponse,
+};
+use crate::state::{AllowanceRefill, ALLOWANCES, ALLOWANCE_REFILLS, BALANCES, TOKEN_INFO};
+use crate::vesting::assert_unlocked;
+
+// bounds the number of allowances removed in one prune
+const MAX_PRUNE_LIMIT: u32 = 30;
+const DEFAULT_PRUNE_LIMIT: u32 = 10;
 
 pub fn execute_increase_allowance(
     deps: DepsMut,
@@ -32,7 +43,6 @@ pub fn execute_increase_allowance(
         Ok(val)
     };
     ALLOWANCES.update(deps.storage, (&info.sender, &spender_addr), update_fn)?;
-    ALLOWANCES_SPENDER.update(deps.storage, (&spender_addr, &info.sender), update_fn)?;
 
     let res = Response::new().add_attributes(vec![
         attr("action", "increase_allowance"),
@@ -43,6 +53,21 @@ pub fn execute_increase_allowance(
     Ok(res)
 }
 
+pub fn execute_increase_allowance_and_call(
+    deps: DepsMut,
+    env: Env,
+    info: MessageInfo,
+    spender: String,
+    amount: Uint128,
+    expires: Option<Expiration>,
+    msg: Binary,
+) -> Result<Response, ContractError> {
+    let owner = info.sender.to_string();
+    let res = execute_increase_allowance(deps, env, info, spender.clone(), amount, expires)?;
+    let msg = AllowanceReceiveMsg { owner, amount, msg }.into_cosmos_msg(spender)?;
+    Ok(res.add_message(msg))
+}
+
 pub fn execute_decrease_allowance(
     deps: DepsMut,
     env: Env,
@@ -58,12 +83,9 @@ pub fn execute_decrease_allowance(
 
     let key = (&info.sender, &spender_addr);
 
-    fn reverse<'a>(t: (&'a Addr, &'a Addr)) -> (&'a Addr, &'a Addr) {
-        (t.1, t.0)
-    }
-
     // load value and delete if it hits 0, or update otherwise
-    let mut allowance = ALLOWANCES.load(deps.storage, key)?;
+    let old = ALLOWANCES.load(deps.storage, key)?;
+    let mut allowance = old.clone();
     if amount < allowance.allowance {
         // update the new amount
         allowance.allowance = allowance
@@ -76,11 +98,10 @@ pub fn execute_decrease_allowance(
             }
             allowance.expires = exp;
         }
-        ALLOWANCES.save(deps.storage, key, &allowance)?;
-        ALLOWANCES_SPENDER.save(deps.storage, reverse(key), &allowance)?;
+        ALLOWANCES.replace(deps.storage, key, Some(&allowance), Some(&old))?;
     } else {
-        ALLOWANCES.remove(deps.storage, key);
-        ALLOWANCES_SPENDER.remove(deps.storage, reverse(key));
+        ALLOWANCES.replace(deps.storage, key, None, Some(&old))?;
+        ALLOWANCE_REFILLS.remove(deps.storage, key);
     }
 
     let res = Response::new().add_attributes(vec![
@@ -92,6 +113,91 @@ pub fn execute_decrease_allowance(
     Ok(res)
 }
 
+pub fn execute_set_recurring_allowance(
+    deps: DepsMut,
+    env: Env,
+    info: MessageInfo,
+    spender: String,
+    cap: Uint128,
+    period: u64,
+    expires: Option<Expiration>,
+) -> Result<Response, ContractError> {
+    let spender_addr = deps.api.addr_validate(&spender)?;
+    if spender_addr == info.sender {
+        return Err(ContractError::CannotSetOwnAccount {});
+    }
+    if period == 0 {
+        return Err(ContractError::InvalidRefillPeriod {});
+    }
+    let expires = expires.unwrap_or_default();
+    if expires.is_expired(&env.block) {
+        return Err(ContractError::InvalidExpiration {});
+    }
+
+    let allowance = AllowanceResponse {
+        allowance: cap,
+        expires,
+    };
+    ALLOWANCES.save(deps.storage, (&info.sender, &spender_addr), &allowance)?;
+    let refill = AllowanceRefill {
+        cap,
+        period,
+        period_start: env.block.time,
+    };
+    ALLOWANCE_REFILLS.save(deps.storage, (&info.sender, &spender_addr), &refill)?;
+
+    let res = Response::new().add_attributes(vec![
+        attr("action", "set_recurring_allowance"),
+        attr("owner", info.sender),
+        attr("spender", spender),
+        attr("cap", cap),
+        attr("period", period.to_string()),
+    ]);
+    Ok(res)
+}
+
+pub fn execute_prune_expired_allowances(
+    deps: DepsMut,
+    env: Env,
+    owner: String,
+    start_after: Option<String>,
+    limit: Option<u32>,
+) -> Result<Response, ContractError> {
+    let owner_addr = deps.api.addr_validate(&owner)?;
+    let limit = limit.unwrap_or(DEFAULT_PRUNE_LIMIT).min(MAX_PRUNE_LIMIT) as usize;
+    let start = start_after.map(|s| Bound::ExclusiveRaw(s.into_bytes()));
+
+    // only limit allowances are checked, so live ones cannot make pruning run out of gas
+    let page = ALLOWANCES
+        .prefix(&owner_addr)
+        .range(deps.storage, start, None, Order::Ascending)
+        .take(limit)
+        .collect::<StdResult<Vec<_>>>()?;
+    let last = match page.last() {
+        Some((spender, _)) if page.len() == limit => Some(spender.clone()),
+        _ => None,
+    };
+    let expired: Vec<_> = page
+        .into_iter()
+        .filter(|(_, allow)| allow.expires.is_expired(&env.block))
+        .map(|(spender, _)| spender)
+        .collect();
+    for spender in &expired {
+        ALLOWANCES.remove(deps.storage, (&owner_addr, spender))?;
+        ALLOWANCE_REFILLS.remove(deps.storage, (&owner_addr, spender));
+    }
+
+    let mut res = Response::new().add_attributes(vec![
+        attr("action", "prune_expired_allowances"),
+        attr("owner", owner),
+        attr("pruned", expired.len().to_string()),
+    ]);
+    if let Some(last) = last {
+        res = res.add_attribute("start_after", last);
+    }
+    Ok(res)
+}
+
 // this can be used to update a lower allowance - call bucket.update with proper keys
 pub fn deduct_allowance(
     storage: &mut dyn Storage,
@@ -100,12 +206,25 @@ pub fn deduct_allowance(
     block: &BlockInfo,
     amount: Uint128,
 ) -> Result<AllowanceResponse, ContractError> {
+    // recurring allowances start over with the full cap in every new period
+    let mut refilled = None;
+    if let Some(mut refill) = ALLOWANCE_REFILLS.may_load(storage, (owner, spender))? {
+        if refill.is_due(&block.time) {
+            refill.period_start = refill.current_period_start(&block.time);
+            ALLOWANCE_REFILLS.save(storage, (owner, spender), &refill)?;
+            refilled = Some(refill.cap);
+        }
+    }
+
     let update_fn = |current: Option<AllowanceResponse>| -> _ {
         match current {
             Some(mut a) => {
                 if a.expires.is_expired(block) {
                     Err(ContractError::Expired {})
                 } else {
+                    if let Some(cap) = refilled {
+                        a.allowance = cap;
+                    }
                     // deduct the allowance if enough
                     a.allowance = a
                         .allowance
@@ -117,8 +236,7 @@ pub fn deduct_allowance(
             None => Err(ContractError::NoAllowance {}),
         }
     };
-    ALLOWANCES.update(storage, (owner, spender), update_fn)?;
-    ALLOWANCES_SPENDER.update(storage, (spender, owner), update_fn)
+    ALLOWANCES.update(storage, (owner, spender), update_fn)
 }
 
 pub fn execute_transfer_from(
@@ -128,13 +246,23 @@ pub fn execute_transfer_from(
     owner: String,
     recipient: String,
     amount: Uint128,
+    memo: Option<String>,
 ) -> Result<Response, ContractError> {
+    validate_memo(&memo)?;
     let rcpt_addr = deps.api.addr_validate(&recipient)?;
     let owner_addr = deps.api.addr_validate(&owner)?;
 
     // deduct allowance before doing anything else have enough allowance
     deduct_allowance(deps.storage, &owner_addr, &info.sender, &env.block, amount)?;
 
+    assert_unlocked(
+        deps.storage,
+        &owner_addr,
+        &info.sender,
+        Some(&rcpt_addr),
+        amount,
+    )?;
+
     BALANCES.update(
         deps.storage,
         &owner_addr,
@@ -148,14 +276,22 @@ pub fn execute_transfer_from(
         |balance: Option<Uint128>| -> StdResult<_> { Ok(balance.unwrap_or_default() + amount) },
     )?;
 
-    let res = Response::new().add_attributes(vec![
+    let hooks = prepare_hooks(
+        deps.storage,
+        BalanceChangeKind::Transfer,
+        Some(&owner_addr),
+        Some(&rcpt_addr),
+        amount,
+    )?;
+
+    let res = Response::new().add_submessages(hooks).add_attributes(vec![
         attr("action", "transfer_from"),
         attr("from", owner),
         attr("to", recipient),
         attr("by", info.sender),
         attr("amount", amount),
     ]);
-    Ok(res)
+    Ok(res.add_attributes(memo.map(|memo| ("memo", memo))))
 }
 
 pub fn execute_burn_from(
@@ -171,6 +307,8 @@ pub fn execute_burn_from(
     // deduct allowance before doing anything else have enough allowance
     deduct_allowance(deps.storage, &owner_addr, &info.sender, &env.block, amount)?;
 
+    assert_unlocked(deps.storage, &owner_addr, &info.sender, None, amount)?;
+
     // lower balance
     BALANCES.update(
         deps.storage,
@@ -185,7 +323,15 @@ pub fn execute_burn_from(
         Ok(meta)
     })?;
 
-    let res = Response::new().add_attributes(vec![
+    let hooks = prepare_hooks(
+        deps.storage,
+        BalanceChangeKind::Burn,
+        Some(&owner_addr),
+        None,
+        amount,
+    )?;
+
+    let res = Response::new().add_submessages(hooks).add_attributes(vec![
         attr("action", "burn_from"),
         attr("from", owner),
         attr("by", info.sender),
@@ -194,6 +340,7 @@ pub fn execute_burn_from(
     Ok(res)
 }
 
+#[allow(clippy::too_many_arguments)]
 pub fn execute_send_from(
     deps: DepsMut,
     env: Env,
@@ -202,13 +349,23 @@ pub fn execute_send_from(
     contract: String,
     amount: Uint128,
     msg: Binary,
+    memo: Option<String>,
 ) -> Result<Response, ContractError> {
+    validate_memo(&memo)?;
     let rcpt_addr = deps.api.addr_validate(&contract)?;
     let owner_addr = deps.api.addr_validate(&owner)?;
 
     // deduct allowance before doing anything else have enough allowance
     deduct_allowance(deps.storage, &owner_addr, &info.sender, &env.block, amount)?;
 
+    assert_unlocked(
+        deps.storage,
+        &owner_addr,
+        &info.sender,
+        Some(&rcpt_addr),
+        amount,
+    )?;
+
     // move the tokens to the contract
     BALANCES.update(
         deps.storage,
@@ -223,13 +380,24 @@ pub fn execute_send_from(
         |balance: Option<Uint128>| -> StdResult<_> { Ok(balance.unwrap_or_default() + amount) },
     )?;
 
-    let attrs = vec![
+    let mut attrs = vec![
         attr("action", "send_from"),
         attr("from", &owner),
         attr("to", &contract),
         attr("by", &info.sender),
         attr("amount", amount),
     ];
+    if let Some(memo) = memo {
+        attrs.push(attr("memo", memo));
+    }
+
+    let hooks = prepare_hooks(
+        deps.storage,
+        BalanceChangeKind::Transfer,
+        Some(&owner_addr),
+        Some(&rcpt_addr),
+        amount,
+    )?;
 
     // create a send message
     let msg = Cw20ReceiveMsg {
@@ -239,17 +407,77 @@ pub fn execute_send_from(
     }
     .into_cosmos_msg(contract)?;
 
-    let res = Response::new().add_message(msg).add_attributes(attrs);
+    // hooks go first, like in `Send`, so they see the transfer before the receiver acts on it
+    let res = Response::new()
+        .add_submessages(hooks)
+        .add_message(msg)
+        .add_attributes(attrs);
     Ok(res)
 }
 
-pub fn query_allowance(deps: Deps, owner: String, spender: String) -> StdResult<AllowanceResponse> {
+/// Returns the stored allowance, refilled to its cap if a recurring allowance started a new
+/// period since it was last spent from
+pub fn refilled_allowance(
+    storage: &dyn Storage,
+    owner: &Addr,
+    spender: &Addr,
+    mut allowance: AllowanceResponse,
+    time: &Timestamp,
+) -> StdResult<AllowanceResponse> {
+    if let Some(refill) = ALLOWANCE_REFILLS.may_load(storage, (owner, spender))? {
+        if refill.is_due(time) {
+            allowance.allowance = refill.cap;
+        }
+    }
+    Ok(allowance)
+}
+
+pub fn query_allowance(
+    deps: Deps,
+    env: Env,
+    owner: String,
+    spender: String,
+) -> StdResult<AllowanceResponse> {
     let owner_addr = deps.api.addr_validate(&owner)?;
     let spender_addr = deps.api.addr_validate(&spender)?;
     let allowance = ALLOWANCES
         .may_load(deps.storage, (&owner_addr, &spender_addr))?
         .unwrap_or_default();
-    Ok(allowance)
+    refilled_allowance(
+        deps.storage,
+        &owner_addr,
+        &spender_addr,
+        allowance,
+        &env.block.time,
+    )
+}
+
+pub fn query_recurring_allowance(
+    deps: Deps,
+    env: Env,
+    owner: String,
+    spender: String,
+) -> StdResult<RecurringAllowanceResponse> {
+    let owner_addr = deps.api.addr_validate(&owner)?;
+    let spender_addr = deps.api.addr_validate(&spender)?;
+    let allowance = ALLOWANCES
+        .may_load(deps.storage, (&owner_addr, &spender_addr))?
+        .unwrap_or_default();
+    let refill = ALLOWANCE_REFILLS.may_load(deps.storage, (&owner_addr, &spender_addr))?;
+
+    let remaining = match &refill {
+        Some(refill) if refill.is_due(&env.block.time) => refill.cap,
+        _ => allowance.allowance,
+    };
+    Ok(RecurringAllowanceResponse {
+        remaining,
+        expires: allowance.expires,
+        refill: refill.map(|refill| AllowanceRefillResponse {
+            next_reset: refill.next_reset(&env.block.time),
+            cap: refill.cap,
+            period: refill.period,
+        }),
+    })
 }
 
 #[cfg(test)]
@@ -257,11 +485,12 @@ mod tests {
     use super::*;
 
     use cosmwasm_std::testing::{mock_dependencies_with_balance, mock_env, mock_info};
-    use cosmwasm_std::{coins, Timestamp};
-    use cw20::{Cw20Coin, TokenInfoResponse};
+    use cosmwasm_std::{coins, SubMsg, Timestamp};
+    use cw20::TokenInfoResponse;
 
     use crate::contract::{execute, instantiate, query_balance, query_token_info};
-    use crate::msg::{ExecuteMsg, InstantiateMsg};
+    use crate::enumerable::{query_owner_allowances, query_spender_allowances};
+    use crate::msg::{ExecuteMsg, InitialBalance, InstantiateMsg};
 
     fn get_balance<T: Into<String>>(deps: Deps, address: T) -> Uint128 {
         query_balance(deps, address.into()).unwrap().balance
@@ -277,11 +506,14 @@ mod tests {
             name: "Auto Gen".to_string(),
             symbol: "AUTO".to_string(),
             decimals: 3,
-            initial_balances: vec![Cw20Coin {
+            initial_balances: vec![InitialBalance {
                 address: addr.into(),
                 amount,
+                vesting: None,
             }],
             mint: "test_minter".to_string(),
+            admin: None,
+            marketing: None,
         };
         let info = mock_info("creator", &[]);
         let env = mock_env();
@@ -300,7 +532,8 @@ mod tests {
         do_instantiate(deps.as_mut(), owner.clone(), Uint128::new(12340000));
 
         // no allowance to start
-        let allowance = query_allowance(deps.as_ref(), owner.clone(), spender.clone()).unwrap();
+        let allowance =
+            query_allowance(deps.as_ref(), mock_env(), owner.clone(), spender.clone()).unwrap();
         assert_eq!(allowance, AllowanceResponse::default());
 
         // set allowance with height expiration
@@ -314,7 +547,8 @@ mod tests {
         execute(deps.as_mut(), env.clone(), info.clone(), msg).unwrap();
 
         // ensure it looks good
-        let allowance = query_allowance(deps.as_ref(), owner.clone(), spender.clone()).unwrap();
+        let allowance =
+            query_allowance(deps.as_ref(), mock_env(), owner.clone(), spender.clone()).unwrap();
         assert_eq!(
             allowance,
             AllowanceResponse {
@@ -332,7 +566,8 @@ mod tests {
             expires: None,
         };
         execute(deps.as_mut(), env.clone(), info.clone(), msg).unwrap();
-        let allowance = query_allowance(deps.as_ref(), owner.clone(), spender.clone()).unwrap();
+        let allowance =
+            query_allowance(deps.as_ref(), mock_env(), owner.clone(), spender.clone()).unwrap();
         assert_eq!(
             allowance,
             AllowanceResponse {
@@ -351,7 +586,8 @@ mod tests {
             expires: Some(new_expire),
         };
         execute(deps.as_mut(), env.clone(), info.clone(), msg).unwrap();
-        let allowance = query_allowance(deps.as_ref(), owner.clone(), spender.clone()).unwrap();
+        let allowance =
+            query_allowance(deps.as_ref(), mock_env(), owner.clone(), spender.clone()).unwrap();
         assert_eq!(
             allowance,
             AllowanceResponse {
@@ -367,7 +603,7 @@ mod tests {
             expires: None,
         };
         execute(deps.as_mut(), env, info, msg).unwrap();
-        let allowance = query_allowance(deps.as_ref(), owner, spender).unwrap();
+        let allowance = query_allowance(deps.as_ref(), mock_env(), owner, spender).unwrap();
         assert_eq!(allowance, AllowanceResponse::default());
     }
 
@@ -384,15 +620,15 @@ mod tests {
 
         // no allowance to start
         assert_eq!(
-            query_allowance(deps.as_ref(), owner.clone(), spender.clone()).unwrap(),
+            query_allowance(deps.as_ref(), mock_env(), owner.clone(), spender.clone()).unwrap(),
             AllowanceResponse::default()
         );
         assert_eq!(
-            query_allowance(deps.as_ref(), owner.clone(), spender2.clone()).unwrap(),
+            query_allowance(deps.as_ref(), mock_env(), owner.clone(), spender2.clone()).unwrap(),
             AllowanceResponse::default()
         );
         assert_eq!(
-            query_allowance(deps.as_ref(), spender.clone(), spender2.clone()).unwrap(),
+            query_allowance(deps.as_ref(), mock_env(), spender.clone(), spender2.clone()).unwrap(),
             AllowanceResponse::default()
         );
 
@@ -425,15 +661,15 @@ mod tests {
             expires: Expiration::Never {},
         };
         assert_eq!(
-            query_allowance(deps.as_ref(), owner.clone(), spender.clone()).unwrap(),
+            query_allowance(deps.as_ref(), mock_env(), owner.clone(), spender.clone()).unwrap(),
             expect_one
         );
         assert_eq!(
-            query_allowance(deps.as_ref(), owner.clone(), spender2.clone()).unwrap(),
+            query_allowance(deps.as_ref(), mock_env(), owner.clone(), spender2.clone()).unwrap(),
             expect_two
         );
         assert_eq!(
-            query_allowance(deps.as_ref(), spender.clone(), spender2.clone()).unwrap(),
+            query_allowance(deps.as_ref(), mock_env(), spender.clone(), spender2.clone()).unwrap(),
             AllowanceResponse::default()
         );
 
@@ -453,15 +689,15 @@ mod tests {
             expires: expires3,
         };
         assert_eq!(
-            query_allowance(deps.as_ref(), owner.clone(), spender.clone()).unwrap(),
+            query_allowance(deps.as_ref(), mock_env(), owner.clone(), spender.clone()).unwrap(),
             expect_one
         );
         assert_eq!(
-            query_allowance(deps.as_ref(), owner, spender2.clone()).unwrap(),
+            query_allowance(deps.as_ref(), mock_env(), owner, spender2.clone()).unwrap(),
             expect_two
         );
         assert_eq!(
-            query_allowance(deps.as_ref(), spender, spender2).unwrap(),
+            query_allowance(deps.as_ref(), mock_env(), spender, spender2).unwrap(),
             expect_three
         );
     }
@@ -521,6 +757,7 @@ mod tests {
             owner: owner.clone(),
             recipient: rcpt.clone(),
             amount: transfer,
+            memo: None,
         };
         let info = mock_info(spender.as_ref(), &[]);
         let env = mock_env();
@@ -535,7 +772,8 @@ mod tests {
         assert_eq!(get_balance(deps.as_ref(), rcpt.clone()), transfer);
 
         // ensure it looks good
-        let allowance = query_allowance(deps.as_ref(), owner.clone(), spender.clone()).unwrap();
+        let allowance =
+            query_allowance(deps.as_ref(), mock_env(), owner.clone(), spender.clone()).unwrap();
         let expect = AllowanceResponse {
             allowance: allow1.checked_sub(transfer).unwrap(),
             expires: Expiration::Never {},
@@ -547,6 +785,7 @@ mod tests {
             owner: owner.clone(),
             recipient: rcpt.clone(),
             amount: Uint128::new(33443),
+            memo: None,
         };
         let info = mock_info(spender.as_ref(), &[]);
         let env = mock_env();
@@ -570,9 +809,326 @@ mod tests {
             owner,
             recipient: rcpt,
             amount: Uint128::new(33443),
+            memo: None,
         };
         let info = mock_info(spender.as_ref(), &[]);
         let err = execute(deps.as_mut(), env, info, msg).unwrap_err();
         assert_eq!(err, ContractError::Expired {});
     }
+
+    #[test]
+    fn recurring_allowance_refills() {
+        let mut deps = mock_dependencies_with_balance(&coins(2, "token"));
+        let owner = String::from("addr0001");
+        let spender = String::from("addr0002");
+        let rcpt = String::from("addr0003");
+        do_instantiate(deps.as_mut(), &owner, Uint128::new(10000));
+
+        let week = 7 * 24 * 60 * 60;
+        let mut env = mock_env();
+        let msg = ExecuteMsg::SetRecurringAllowance {
+            spender: spender.clone(),
+            cap: Uint128::new(100),
+            period: 0,
+            expires: None,
+        };
+        let err = execute(
+            deps.as_mut(),
+            env.clone(),
+            mock_info(owner.as_ref(), &[]),
+            msg,
+        )
+        .unwrap_err();
+        assert_eq!(err, ContractError::InvalidRefillPeriod {});
+        let msg = ExecuteMsg::SetRecurringAllowance {
+            spender: spender.clone(),
+            cap: Uint128::new(100),
+            period: week,
+            expires: None,
+        };
+        execute(
+            deps.as_mut(),
+            env.clone(),
+            mock_info(owner.as_ref(), &[]),
+            msg,
+        )
+        .unwrap();
+
+        let transfer = |amount: u128| ExecuteMsg::TransferFrom {
+            owner: owner.clone(),
+            recipient: rcpt.clone(),
+            amount: Uint128::new(amount),
+            memo: None,
+        };
+        let spender_info = mock_info(spender.as_ref(), &[]);
+        execute(
+            deps.as_mut(),
+            env.clone(),
+            spender_info.clone(),
+            transfer(60),
+        )
+        .unwrap();
+        let err = execute(
+            deps.as_mut(),
+            env.clone(),
+            spender_info.clone(),
+            transfer(41),
+        )
+        .unwrap_err();
+        assert!(matches!(err, ContractError::Std(StdError::Overflow { .. })));
+
+        let start = env.block.time;
+        let allowance =
+            query_recurring_allowance(deps.as_ref(), env.clone(), owner.clone(), spender.clone())
+                .unwrap();
+        assert_eq!(
+            allowance,
+            RecurringAllowanceResponse {
+                remaining: Uint128::new(40),
+                expires: Expiration::Never {},
+                refill: Some(AllowanceRefillResponse {
+                    cap: Uint128::new(100),
+                    period: week,
+                    next_reset: start.plus_seconds(week),
+                }),
+            }
+        );
+
+        // unused allowance does not carry over into later periods
+        env.block.time = start.plus_seconds(2 * week + 5);
+        let allowance =
+            query_recurring_allowance(deps.as_ref(), env.clone(), owner.clone(), spender.clone())
+                .unwrap();
+        assert_eq!(allowance.remaining, Uint128::new(100));
+        assert_eq!(
+            allowance.refill.unwrap().next_reset,
+            start.plus_seconds(3 * week)
+        );
+        // the standard cw20 queries show the refill too, before anything is spent
+        let allowance =
+            query_allowance(deps.as_ref(), env.clone(), owner.clone(), spender.clone()).unwrap();
+        assert_eq!(allowance.allowance, Uint128::new(100));
+        let allowances =
+            query_owner_allowances(deps.as_ref(), env.clone(), owner.clone(), None, None, None)
+                .unwrap();
+        assert_eq!(allowances.allowances[0].allowance, Uint128::new(100));
+        let allowances = query_spender_allowances(
+            deps.as_ref(),
+            env.clone(),
+            spender.clone(),
+            None,
+            None,
+            None,
+        )
+        .unwrap();
+        assert_eq!(allowances.allowances[0].allowance, Uint128::new(100));
+        execute(
+            deps.as_mut(),
+            env.clone(),
+            spender_info.clone(),
+            transfer(100),
+        )
+        .unwrap();
+        let err = execute(deps.as_mut(), env.clone(), spender_info, transfer(1)).unwrap_err();
+        assert!(matches!(err, ContractError::Std(StdError::Overflow { .. })));
+        assert_eq!(get_balance(deps.as_ref(), rcpt.clone()), Uint128::new(160));
+
+        // removing the allowance stops the refills
+        let msg = ExecuteMsg::DecreaseAllowance {
+            spender: spender.clone(),
+            amount: Uint128::new(100),
+            expires: None,
+        };
+        execute(
+            deps.as_mut(),
+            env.clone(),
+            mock_info(owner.as_ref(), &[]),
+            msg,
+        )
+        .unwrap();
+        env.block.time = start.plus_seconds(3 * week);
+        let allowance = query_recurring_allowance(deps.as_ref(), env, owner, spender).unwrap();
+        assert_eq!(allowance.remaining, Uint128::zero());
+        assert_eq!(allowance.refill, None);
+    }
+
+    #[test]
+    fn prune_expired_allowances() {
+        let mut deps = mock_dependencies_with_balance(&coins(2, "token"));
+        let owner = String::from("addr0001");
+        do_instantiate(deps.as_mut(), &owner, Uint128::new(10000));
+
+        let mut env = mock_env();
+        let info = mock_info(owner.as_ref(), &[]);
+        for (spender, expires) in [
+            ("spender1", Expiration::AtHeight(env.block.height + 1)),
+            ("spender2", Expiration::Never {}),
+            ("spender3", Expiration::AtHeight(env.block.height + 1)),
+        ] {
+            let msg = ExecuteMsg::IncreaseAllowance {
+                spender: spender.into(),
+                amount: Uint128::new(100),
+                expires: Some(expires),
+            };
+            execute(deps.as_mut(), env.clone(), info.clone(), msg).unwrap();
+        }
+
+        env.block.height += 1;
+        let visible = |deps: Deps, env: &Env| {
+            query_owner_allowances(deps, env.clone(), owner.clone(), None, None, Some(true))
+                .unwrap()
+                .allowances
+                .into_iter()
+                .map(|a| a.spender)
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(visible(deps.as_ref(), &env), vec!["spender2".to_string()]);
+        let all =
+            query_owner_allowances(deps.as_ref(), env.clone(), owner.clone(), None, None, None)
+                .unwrap();
+        assert_eq!(all.allowances.len(), 3);
+        let spender = query_spender_allowances(
+            deps.as_ref(),
+            env.clone(),
+            "spender1".into(),
+            None,
+            None,
+            Some(true),
+        )
+        .unwrap();
+        assert_eq!(spender.allowances, vec![]);
+
+        // a page of hidden allowances comes back empty, with where to continue from
+        let page = query_owner_allowances(
+            deps.as_ref(),
+            env.clone(),
+            owner.clone(),
+            None,
+            Some(1),
+            Some(true),
+        )
+        .unwrap();
+        assert_eq!(page.allowances, vec![]);
+        assert_eq!(page.start_after, Some("spender1".to_string()));
+        let page = query_owner_allowances(
+            deps.as_ref(),
+            env.clone(),
+            owner.clone(),
+            page.start_after,
+            Some(1),
+            Some(true),
+        )
+        .unwrap();
+        assert_eq!(page.allowances[0].spender, "spender2");
+        assert_eq!(page.start_after, Some("spender2".to_string()));
+
+        // anyone can prune, checking limit allowances at a time
+        let prune =
+            |start_after: Option<&str>, limit: Option<u32>| ExecuteMsg::PruneExpiredAllowances {
+                owner: owner.clone(),
+                start_after: start_after.map(Into::into),
+                limit,
+            };
+        let res = execute(
+            deps.as_mut(),
+            env.clone(),
+            mock_info("anyone", &[]),
+            prune(None, Some(1)),
+        )
+        .unwrap();
+        assert_eq!(res.attributes[2], attr("pruned", "1"));
+        assert_eq!(res.attributes[3], attr("start_after", "spender1"));
+        let res = execute(
+            deps.as_mut(),
+            env.clone(),
+            mock_info("anyone", &[]),
+            prune(Some("spender1"), Some(1)),
+        )
+        .unwrap();
+        assert_eq!(res.attributes[2], attr("pruned", "0"));
+        assert_eq!(res.attributes[3], attr("start_after", "spender2"));
+        let res = execute(
+            deps.as_mut(),
+            env.clone(),
+            mock_info("anyone", &[]),
+            prune(Some("spender2"), None),
+        )
+        .unwrap();
+        assert_eq!(res.attributes[2], attr("pruned", "1"));
+        // the end was reached
+        assert_eq!(res.attributes.len(), 3);
+
+        // both maps are cleaned up
+        let all =
+            query_owner_allowances(deps.as_ref(), env.clone(), owner.clone(), None, None, None)
+                .unwrap();
+        assert_eq!(all.allowances.len(), 1);
+        for spender in ["spender1", "spender3"] {
+            let allowances = query_spender_allowances(
+                deps.as_ref(),
+                env.clone(),
+                spender.into(),
+                None,
+                None,
+                None,
+            )
+            .unwrap();
+            assert_eq!(allowances.allowances, vec![]);
+        }
+    }
+
+    #[test]
+    fn increase_allowance_and_call() {
+        let mut deps = mock_dependencies_with_balance(&coins(2, "token"));
+        let owner = String::from("addr0001");
+        let staking = String::from("staking");
+        do_instantiate(deps.as_mut(), &owner, Uint128::new(10000));
+
+        let inner = Binary::from(br#"{"bond":{}}"#.as_slice());
+        let msg = ExecuteMsg::IncreaseAllowanceAndCall {
+            spender: staking.clone(),
+            amount: Uint128::new(700),
+            expires: None,
+            msg: inner.clone(),
+        };
+        let res = execute(
+            deps.as_mut(),
+            mock_env(),
+            mock_info(owner.as_ref(), &[]),
+            msg,
+        )
+        .unwrap();
+        assert_eq!(res.attributes[0], attr("action", "increase_allowance"));
+        assert_eq!(
+            res.messages,
+            vec![SubMsg::new(
+                AllowanceReceiveMsg {
+                    owner: owner.clone(),
+                    amount: Uint128::new(700),
+                    msg: inner,
+                }
+                .into_cosmos_msg(staking.clone())
+                .unwrap()
+            )]
+        );
+
+        // the called contract can pull the tokens right away
+        let allowance =
+            query_allowance(deps.as_ref(), mock_env(), owner.clone(), staking.clone()).unwrap();
+        assert_eq!(allowance.allowance, Uint128::new(700));
+        let msg = ExecuteMsg::TransferFrom {
+            owner: owner.clone(),
+            recipient: staking.clone(),
+            amount: Uint128::new(700),
+            memo: None,
+        };
+        execute(
+            deps.as_mut(),
+            mock_env(),
+            mock_info(staking.as_ref(), &[]),
+            msg,
+        )
+        .unwrap();
+        assert_eq!(get_balance(deps.as_ref(), staking), Uint128::new(700));
+    }
 }
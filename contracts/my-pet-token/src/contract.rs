@@ -2,26 +2,117 @@
 use cosmwasm_std::entry_point;
 use cosmwasm_std::Order::Ascending;
 use cosmwasm_std::{
-    to_json_binary, Addr, Binary, Deps, DepsMut, Env, MessageInfo, Response, StdResult, Uint128,
+    to_json_binary, Addr, Binary, Deps, DepsMut, Env, MessageInfo, Response, StdError, StdResult,
+    Uint128,
 };
 
 use cw2::{ensure_from_older_version, set_contract_version};
-use cw20::{BalanceResponse, Cw20Coin, Cw20ReceiveMsg, TokenInfoResponse};
+use cw20::{
+    AllowanceResponse, BalanceResponse, Cw20ReceiveMsg, DownloadLogoResponse, EmbeddedLogo, Logo,
+    LogoInfo, MarketingInfoResponse, TokenInfoResponse,
+};
+use cw_storage_plus::{Bound, Map};
+use cw_utils::maybe_addr;
 
 use crate::allowances::{
-    execute_decrease_allowance, execute_increase_allowance, execute_transfer_from, query_allowance,
+    execute_decrease_allowance, execute_increase_allowance, execute_increase_allowance_and_call,
+    execute_prune_expired_allowances, execute_send_from, execute_set_recurring_allowance,
+    execute_transfer_from, query_allowance, query_recurring_allowance,
+};
+use crate::enumerable::{
+    query_all_accounts, query_all_balances, query_holder_count, query_owner_allowances,
+    query_spender_allowances, query_top_holders, query_verify_supply,
 };
-use crate::enumerable::{query_all_accounts, query_owner_allowances};
 use crate::error::ContractError;
-use crate::msg::{ExecuteMsg, InstantiateMsg, MigrateMsg, QueryMsg};
+use crate::escrow::{
+    execute_cancel_escrow, execute_claim_escrow, execute_create_escrow, query_escrow,
+    query_escrows_by_recipient, query_escrows_by_sender,
+};
+use crate::hooks::{execute_add_hook, execute_remove_hook, prepare_hooks, query_hooks};
+use crate::mint_history::{query_mint_history, query_mint_windows, record_mint};
+use crate::msg::{
+    BalanceChangeKind, ExecuteMsg, InitialBalance, InstantiateMsg, MigrateMsg, QueryMsg,
+};
+use crate::recovery::execute_recover_tokens;
 use crate::state::{
-    PetStakingData, TokenInfo, ALLOWANCES, ALLOWANCES_SPENDER, BALANCES, PET_STAKING_DATA,
-    TOKEN_INFO,
+    PetStakingData, TokenInfo, Vesting, ADMIN, ALLOWANCES, BALANCES, LOGO, MARKETING_INFO,
+    PET_STAKING_DATA, TOKEN_INFO, VESTING,
+};
+use crate::stream::{
+    execute_cancel_stream, execute_create_stream, execute_withdraw_stream, query_stream,
+    query_streams_by,
 };
+use crate::vesting::{assert_unlocked, execute_release_vested, query_vesting};
 
 // version info for migration info
 const CONTRACT_NAME: &str = "mypet";
-const CONTRACT_VERSION: &str = "1.0.0";
+const CONTRACT_VERSION: &str = "1.2.0";
+
+// entries loaded into memory at once while migrations rebuild indexes
+const MIGRATION_PAGE: usize = 100;
+
+// maximum length of a transfer memo in bytes
+const MAX_MEMO_LENGTH: usize = 256;
+
+const LOGO_SIZE_CAP: usize = 5 * 1024;
+
+/// Checks if data starts with XML preamble
+fn verify_xml_preamble(data: &[u8]) -> Result<(), ContractError> {
+    // The easiest way to perform this check would be just match on regex, however regex
+    // compilation is heavy and probably not worth it.
+
+    let preamble = data
+        .split_inclusive(|c| *c == b'>')
+        .next()
+        .ok_or(ContractError::InvalidXmlPreamble {})?;
+
+    const PREFIX: &[u8] = b"<?xml ";
+    const POSTFIX: &[u8] = b"?>";
+
+    if !(preamble.starts_with(PREFIX) && preamble.ends_with(POSTFIX)) {
+        Err(ContractError::InvalidXmlPreamble {})
+    } else {
+        Ok(())
+    }
+}
+
+/// Validates XML logo
+fn verify_xml_logo(logo: &[u8]) -> Result<(), ContractError> {
+    verify_xml_preamble(logo)?;
+
+    if logo.len() > LOGO_SIZE_CAP {
+        Err(ContractError::LogoTooBig {})
+    } else {
+        Ok(())
+    }
+}
+
+/// Validates png logo
+fn verify_png_logo(logo: &[u8]) -> Result<(), ContractError> {
+    // PNG header format:
+    // 0x89 - magic byte, out of ASCII table to fail on 7-bit systems
+    // "PNG" ascii representation
+    // [0x0d, 0x0a] - dos style line ending
+    // 0x1a - dos control character, stop displaying rest of the file
+    // 0x0a - unix style line ending
+    const HEADER: [u8; 8] = [0x89, b'P', b'N', b'G', 0x0d, 0x0a, 0x1a, 0x0a];
+    if logo.len() > LOGO_SIZE_CAP {
+        Err(ContractError::LogoTooBig {})
+    } else if !logo.starts_with(&HEADER) {
+        Err(ContractError::InvalidPngHeader {})
+    } else {
+        Ok(())
+    }
+}
+
+/// Checks if passed logo is correct, and if not, returns an error
+fn verify_logo(logo: &Logo) -> Result<(), ContractError> {
+    match logo {
+        Logo::Embedded(EmbeddedLogo::Svg(logo)) => verify_xml_logo(logo),
+        Logo::Embedded(EmbeddedLogo::Png(logo)) => verify_png_logo(logo),
+        Logo::Url(_) => Ok(()), // Any reasonable url validation would be regex based, probably not worth it
+    }
+}
 
 #[cfg_attr(not(feature = "library"), entry_point)]
 pub fn instantiate(
@@ -36,6 +127,34 @@ pub fn instantiate(
     // create initial accounts
     let total_supply = create_accounts(&mut deps, &msg.initial_balances)?;
     let minter = deps.api.addr_validate(&msg.mint)?;
+    let api = deps.api;
+    ADMIN.set(deps.branch(), maybe_addr(api, msg.admin)?)?;
+
+    if let Some(marketing) = msg.marketing {
+        let logo = if let Some(logo) = marketing.logo {
+            verify_logo(&logo)?;
+            LOGO.save(deps.storage, &logo)?;
+
+            match logo {
+                Logo::Url(url) => Some(LogoInfo::Url(url)),
+                Logo::Embedded(_) => Some(LogoInfo::Embedded),
+            }
+        } else {
+            None
+        };
+
+        let data = MarketingInfoResponse {
+            project: marketing.project,
+            description: marketing.description,
+            marketing: marketing
+                .marketing
+                .map(|addr| deps.api.addr_validate(&addr))
+                .transpose()?,
+            logo,
+        };
+        MARKETING_INFO.save(deps.storage, &data)?;
+    }
+
     // store token info
     let data = TokenInfo {
         name: msg.name,
@@ -50,20 +169,28 @@ pub fn instantiate(
 
 pub fn create_accounts(
     deps: &mut DepsMut,
-    accounts: &[Cw20Coin],
+    accounts: &[InitialBalance],
 ) -> Result<Uint128, ContractError> {
     validate_accounts(accounts)?;
     let mut total_supply = Uint128::zero();
     for row in accounts {
         let address = deps.api.addr_validate(&row.address)?;
         BALANCES.save(deps.storage, &address, &row.amount)?;
+        if let Some(schedule) = &row.vesting {
+            let vesting = Vesting {
+                schedule: schedule.clone(),
+                total: row.amount,
+                released: Uint128::zero(),
+            };
+            VESTING.save(deps.storage, &address, &vesting)?;
+        }
         total_supply += row.amount;
     }
 
     Ok(total_supply)
 }
 
-pub fn validate_accounts(accounts: &[Cw20Coin]) -> Result<(), ContractError> {
+pub fn validate_accounts(accounts: &[InitialBalance]) -> Result<(), ContractError> {
     let mut addresses = accounts.iter().map(|c| &c.address).collect::<Vec<_>>();
     addresses.sort();
     addresses.dedup();
@@ -75,6 +202,15 @@ pub fn validate_accounts(accounts: &[Cw20Coin]) -> Result<(), ContractError> {
     }
 }
 
+pub fn validate_memo(memo: &Option<String>) -> Result<(), ContractError> {
+    match memo {
+        Some(memo) if memo.len() > MAX_MEMO_LENGTH => Err(ContractError::InvalidMemo {
+            max: MAX_MEMO_LENGTH,
+        }),
+        _ => Ok(()),
+    }
+}
+
 #[cfg_attr(not(feature = "library"), entry_point)]
 pub fn execute(
     deps: DepsMut,
@@ -83,28 +219,95 @@ pub fn execute(
     msg: ExecuteMsg,
 ) -> Result<Response, ContractError> {
     match msg {
-        ExecuteMsg::Transfer { recipient, amount } => {
-            execute_transfer(deps, env, info, recipient, amount)
-        }
+        ExecuteMsg::Transfer {
+            recipient,
+            amount,
+            memo,
+        } => execute_transfer(deps, env, info, recipient, amount, memo),
+        ExecuteMsg::Burn { amount } => execute_burn(deps, env, info, amount),
         ExecuteMsg::Mint { recipient, amount } => execute_mint(deps, env, info, recipient, amount),
         ExecuteMsg::IncreaseAllowance {
             spender,
             amount,
             expires,
         } => execute_increase_allowance(deps, env, info, spender, amount, expires),
+        ExecuteMsg::IncreaseAllowanceAndCall {
+            spender,
+            amount,
+            expires,
+            msg,
+        } => execute_increase_allowance_and_call(deps, env, info, spender, amount, expires, msg),
         ExecuteMsg::DecreaseAllowance {
             spender,
             amount,
             expires,
         } => execute_decrease_allowance(deps, env, info, spender, amount, expires),
+        ExecuteMsg::PruneExpiredAllowances {
+            owner,
+            start_after,
+            limit,
+        } => execute_prune_expired_allowances(deps, env, owner, start_after, limit),
+        ExecuteMsg::SetRecurringAllowance {
+            spender,
+            cap,
+            period,
+            expires,
+        } => execute_set_recurring_allowance(deps, env, info, spender, cap, period, expires),
         ExecuteMsg::TransferFrom {
             owner,
             recipient,
             amount,
-        } => execute_transfer_from(deps, env, info, owner, recipient, amount),
+            memo,
+        } => execute_transfer_from(deps, env, info, owner, recipient, amount, memo),
+        ExecuteMsg::Send {
+            contract,
+            amount,
+            msg,
+            memo,
+        } => execute_send(deps, env, info, contract, amount, msg, memo),
+        ExecuteMsg::SendFrom {
+            owner,
+            contract,
+            amount,
+            msg,
+            memo,
+        } => execute_send_from(deps, env, info, owner, contract, amount, msg, memo),
         ExecuteMsg::UpdateMinter { new_minter } => {
             execute_update_minter(deps, env, info, new_minter)
         }
+        ExecuteMsg::ReleaseVested {} => execute_release_vested(deps, env, info),
+        ExecuteMsg::CreateEscrow {
+            recipient,
+            amount,
+            release_at,
+        } => execute_create_escrow(deps, env, info, recipient, amount, release_at),
+        ExecuteMsg::ClaimEscrow { id } => execute_claim_escrow(deps, env, info, id),
+        ExecuteMsg::CancelEscrow { id } => execute_cancel_escrow(deps, env, info, id),
+        ExecuteMsg::CreateStream {
+            recipient,
+            amount,
+            start_time,
+            end_time,
+        } => execute_create_stream(deps, env, info, recipient, amount, start_time, end_time),
+        ExecuteMsg::WithdrawStream { id } => execute_withdraw_stream(deps, env, info, id),
+        ExecuteMsg::CancelStream { id } => execute_cancel_stream(deps, env, info, id),
+        ExecuteMsg::UpdateAdmin { admin } => {
+            let api = deps.api;
+            Ok(ADMIN.execute_update_admin(deps, info, maybe_addr(api, admin)?)?)
+        }
+        ExecuteMsg::AddHook { addr, kinds } => execute_add_hook(deps, info, addr, kinds),
+        ExecuteMsg::RemoveHook { addr } => execute_remove_hook(deps, info, addr),
+        ExecuteMsg::UpdateMarketing {
+            project,
+            description,
+            marketing,
+        } => execute_update_marketing(deps, env, info, project, description, marketing),
+        ExecuteMsg::UploadLogo(logo) => execute_upload_logo(deps, env, info, logo),
+        ExecuteMsg::RecoverTokens {
+            from_contract_balance,
+            recipient,
+            amount,
+        } => execute_recover_tokens(deps, env, info, from_contract_balance, recipient, amount),
     }
 }
 
@@ -114,9 +317,19 @@ pub fn execute_transfer(
     info: MessageInfo,
     recipient: String,
     amount: Uint128,
+    memo: Option<String>,
 ) -> Result<Response, ContractError> {
+    validate_memo(&memo)?;
     let rcpt_addr = deps.api.addr_validate(&recipient)?;
 
+    assert_unlocked(
+        deps.storage,
+        &info.sender,
+        &info.sender,
+        Some(&rcpt_addr),
+        amount,
+    )?;
+
     BALANCES.update(
         deps.storage,
         &info.sender,
@@ -130,11 +343,21 @@ pub fn execute_transfer(
         |balance: Option<Uint128>| -> StdResult<_> { Ok(balance.unwrap_or_default() + amount) },
     )?;
 
+    let hooks = prepare_hooks(
+        deps.storage,
+        BalanceChangeKind::Transfer,
+        Some(&info.sender),
+        Some(&rcpt_addr),
+        amount,
+    )?;
+
     let res = Response::new()
+        .add_submessages(hooks)
         .add_attribute("action", "transfer")
         .add_attribute("from", info.sender)
         .add_attribute("to", recipient)
-        .add_attribute("amount", amount);
+        .add_attribute("amount", amount)
+        .add_attributes(memo.map(|memo| ("memo", memo)));
     Ok(res)
 }
 
@@ -144,6 +367,8 @@ pub fn execute_burn(
     info: MessageInfo,
     amount: Uint128,
 ) -> Result<Response, ContractError> {
+    assert_unlocked(deps.storage, &info.sender, &info.sender, None, amount)?;
+
     // lower balance
     BALANCES.update(
         deps.storage,
@@ -158,7 +383,16 @@ pub fn execute_burn(
         Ok(info)
     })?;
 
+    let hooks = prepare_hooks(
+        deps.storage,
+        BalanceChangeKind::Burn,
+        Some(&info.sender),
+        None,
+        amount,
+    )?;
+
     let res = Response::new()
+        .add_submessages(hooks)
         .add_attribute("action", "burn")
         .add_attribute("from", info.sender)
         .add_attribute("amount", amount);
@@ -205,9 +439,27 @@ pub fn execute_mint(
         |balance: Option<Uint128>| -> StdResult<_> { Ok(balance.unwrap_or_default() + amount) },
     )?;
     PET_STAKING_DATA.save(deps.storage, &pet_staking_data)?;
+    let id = record_mint(
+        deps.storage,
+        &info.sender,
+        &rcpt_addr,
+        amount,
+        env.block.time,
+        pet_staking_data.start_time.seconds(),
+    )?;
+
+    let hooks = prepare_hooks(
+        deps.storage,
+        BalanceChangeKind::Mint,
+        None,
+        Some(&rcpt_addr),
+        amount,
+    )?;
 
     let res = Response::new()
+        .add_submessages(hooks)
         .add_attribute("action", "mint")
+        .add_attribute("id", id.to_string())
         .add_attribute("to", recipient)
         .add_attribute("amount", amount);
     Ok(res)
@@ -220,9 +472,19 @@ pub fn execute_send(
     contract: String,
     amount: Uint128,
     msg: Binary,
+    memo: Option<String>,
 ) -> Result<Response, ContractError> {
+    validate_memo(&memo)?;
     let rcpt_addr = deps.api.addr_validate(&contract)?;
 
+    assert_unlocked(
+        deps.storage,
+        &info.sender,
+        &info.sender,
+        Some(&rcpt_addr),
+        amount,
+    )?;
+
     // move the tokens to the contract
     BALANCES.update(
         deps.storage,
@@ -237,11 +499,21 @@ pub fn execute_send(
         |balance: Option<Uint128>| -> StdResult<_> { Ok(balance.unwrap_or_default() + amount) },
     )?;
 
+    let hooks = prepare_hooks(
+        deps.storage,
+        BalanceChangeKind::Transfer,
+        Some(&info.sender),
+        Some(&rcpt_addr),
+        amount,
+    )?;
+
     let res = Response::new()
+        .add_submessages(hooks)
         .add_attribute("action", "send")
         .add_attribute("from", &info.sender)
         .add_attribute("to", &contract)
         .add_attribute("amount", amount)
+        .add_attributes(memo.map(|memo| ("memo", memo)))
         .add_message(
             Cw20ReceiveMsg {
                 sender: info.sender.into(),
@@ -279,24 +551,185 @@ pub fn execute_update_minter(
         .add_attribute("new_minter", config.mint))
 }
 
+pub fn execute_update_marketing(
+    deps: DepsMut,
+    _env: Env,
+    info: MessageInfo,
+    project: Option<String>,
+    description: Option<String>,
+    marketing: Option<String>,
+) -> Result<Response, ContractError> {
+    let mut marketing_info = MARKETING_INFO
+        .may_load(deps.storage)?
+        .ok_or(ContractError::Unauthorized {})?;
+
+    if marketing_info
+        .marketing
+        .as_ref()
+        .ok_or(ContractError::Unauthorized {})?
+        != info.sender
+    {
+        return Err(ContractError::Unauthorized {});
+    }
+
+    match project {
+        Some(empty) if empty.trim().is_empty() => marketing_info.project = None,
+        Some(project) => marketing_info.project = Some(project),
+        None => (),
+    }
+
+    match description {
+        Some(empty) if empty.trim().is_empty() => marketing_info.description = None,
+        Some(description) => marketing_info.description = Some(description),
+        None => (),
+    }
+
+    match marketing {
+        Some(empty) if empty.trim().is_empty() => marketing_info.marketing = None,
+        Some(marketing) => marketing_info.marketing = Some(deps.api.addr_validate(&marketing)?),
+        None => (),
+    }
+
+    if marketing_info.project.is_none()
+        && marketing_info.description.is_none()
+        && marketing_info.marketing.is_none()
+        && marketing_info.logo.is_none()
+    {
+        MARKETING_INFO.remove(deps.storage);
+    } else {
+        MARKETING_INFO.save(deps.storage, &marketing_info)?;
+    }
+
+    let res = Response::new().add_attribute("action", "update_marketing");
+    Ok(res)
+}
+
+pub fn execute_upload_logo(
+    deps: DepsMut,
+    _env: Env,
+    info: MessageInfo,
+    logo: Logo,
+) -> Result<Response, ContractError> {
+    let mut marketing_info = MARKETING_INFO
+        .may_load(deps.storage)?
+        .ok_or(ContractError::Unauthorized {})?;
+
+    verify_logo(&logo)?;
+
+    if marketing_info
+        .marketing
+        .as_ref()
+        .ok_or(ContractError::Unauthorized {})?
+        != info.sender
+    {
+        return Err(ContractError::Unauthorized {});
+    }
+
+    LOGO.save(deps.storage, &logo)?;
+
+    let logo_info = match logo {
+        Logo::Url(url) => LogoInfo::Url(url),
+        Logo::Embedded(_) => LogoInfo::Embedded,
+    };
+
+    marketing_info.logo = Some(logo_info);
+    MARKETING_INFO.save(deps.storage, &marketing_info)?;
+
+    let res = Response::new().add_attribute("action", "upload_logo");
+    Ok(res)
+}
+
 #[cfg_attr(not(feature = "library"), entry_point)]
-pub fn query(deps: Deps, _env: Env, msg: QueryMsg) -> StdResult<Binary> {
+pub fn query(deps: Deps, env: Env, msg: QueryMsg) -> StdResult<Binary> {
     match msg {
         QueryMsg::Balance { address } => to_json_binary(&query_balance(deps, address)?),
         QueryMsg::TokenInfo {} => to_json_binary(&query_token_info(deps)?),
         QueryMsg::Minter {} => to_json_binary(&query_minter(deps)?),
         QueryMsg::Allowance { owner, spender } => {
-            to_json_binary(&query_allowance(deps, owner, spender)?)
+            to_json_binary(&query_allowance(deps, env, owner, spender)?)
+        }
+        QueryMsg::RecurringAllowance { owner, spender } => {
+            to_json_binary(&query_recurring_allowance(deps, env, owner, spender)?)
         }
         QueryMsg::AllAllowances {
             owner,
             start_after,
             limit,
-        } => to_json_binary(&query_owner_allowances(deps, owner, start_after, limit)?),
+            hide_expired,
+        } => to_json_binary(&query_owner_allowances(
+            deps,
+            env,
+            owner,
+            start_after,
+            limit,
+            hide_expired,
+        )?),
+        QueryMsg::AllSpenderAllowances {
+            spender,
+            start_after,
+            limit,
+            hide_expired,
+        } => to_json_binary(&query_spender_allowances(
+            deps,
+            env,
+            spender,
+            start_after,
+            limit,
+            hide_expired,
+        )?),
         QueryMsg::AllAccounts { start_after, limit } => {
             to_json_binary(&query_all_accounts(deps, start_after, limit)?)
         }
+        QueryMsg::AllBalances {
+            start_after,
+            limit,
+            skip_zero,
+        } => to_json_binary(&query_all_balances(deps, start_after, limit, skip_zero)?),
+        QueryMsg::VerifySupply {
+            start_after,
+            limit,
+            running_sum,
+        } => to_json_binary(&query_verify_supply(deps, start_after, limit, running_sum)?),
+        QueryMsg::HolderCount {} => to_json_binary(&query_holder_count(deps)?),
+        QueryMsg::TopHolders { limit, start_after } => {
+            to_json_binary(&query_top_holders(deps, limit, start_after)?)
+        }
         QueryMsg::PetStaking {} => to_json_binary(&query_pet_staking_data(deps)?),
+        QueryMsg::Vesting { address } => to_json_binary(&query_vesting(deps, env, address)?),
+        QueryMsg::Escrow { id } => to_json_binary(&query_escrow(deps, id)?),
+        QueryMsg::EscrowsBySender {
+            sender,
+            start_after,
+            limit,
+        } => to_json_binary(&query_escrows_by_sender(deps, sender, start_after, limit)?),
+        QueryMsg::EscrowsByRecipient {
+            recipient,
+            start_after,
+            limit,
+        } => to_json_binary(&query_escrows_by_recipient(
+            deps,
+            recipient,
+            start_after,
+            limit,
+        )?),
+        QueryMsg::Stream { id } => to_json_binary(&query_stream(deps, env, id)?),
+        QueryMsg::MintHistory {
+            start_after,
+            limit,
+            recipient,
+        } => to_json_binary(&query_mint_history(deps, start_after, limit, recipient)?),
+        QueryMsg::MintWindows { start_after, limit } => {
+            to_json_binary(&query_mint_windows(deps, start_after, limit)?)
+        }
+        QueryMsg::Admin {} => to_json_binary(&ADMIN.query_admin(deps)?),
+        QueryMsg::Hooks {} => to_json_binary(&query_hooks(deps)?),
+        QueryMsg::MarketingInfo {} => to_json_binary(&query_marketing_info(deps)?),
+        QueryMsg::DownloadLogo {} => to_json_binary(&query_download_logo(deps)?),
+        QueryMsg::StreamsBy {
+            address,
+            start_after,
+            limit,
+        } => to_json_binary(&query_streams_by(deps, env, address, start_after, limit)?),
     }
 }
 
@@ -329,18 +762,88 @@ pub fn query_pet_staking_data(deps: Deps) -> StdResult<PetStakingData> {
     Ok(pet_staking)
 }
 
+pub fn query_marketing_info(deps: Deps) -> StdResult<MarketingInfoResponse> {
+    Ok(MARKETING_INFO.may_load(deps.storage)?.unwrap_or_default())
+}
+
+pub fn query_download_logo(deps: Deps) -> StdResult<DownloadLogoResponse> {
+    let logo = LOGO.load(deps.storage)?;
+    match logo {
+        Logo::Embedded(EmbeddedLogo::Svg(logo)) => Ok(DownloadLogoResponse {
+            mime_type: "image/svg+xml".to_owned(),
+            data: logo,
+        }),
+        Logo::Embedded(EmbeddedLogo::Png(logo)) => Ok(DownloadLogoResponse {
+            mime_type: "image/png".to_owned(),
+            data: logo,
+        }),
+        Logo::Url(_) => Err(StdError::not_found("logo")),
+    }
+}
+
 #[cfg_attr(not(feature = "library"), entry_point)]
 pub fn migrate(deps: DepsMut, _env: Env, _msg: MigrateMsg) -> Result<Response, ContractError> {
     let original_version =
         ensure_from_older_version(deps.storage, CONTRACT_NAME, CONTRACT_VERSION)?;
 
-    if original_version < "0.14.0".parse::<semver::Version>().unwrap() {
-        // Build reverse map of allowances per spender
-        let data = ALLOWANCES
-            .range(deps.storage, None, None, Ascending)
-            .collect::<StdResult<Vec<_>>>()?;
-        for ((owner, spender), allowance) in data {
-            ALLOWANCES_SPENDER.save(deps.storage, (&spender, &owner), &allowance)?;
+    if original_version < "1.1.0".parse::<semver::Version>().unwrap() {
+        // Index existing balances by amount and count their holders, a page at a time.
+        // All pages still run in this one migration, so its gas must cover every holder.
+        let mut last: Option<Addr> = None;
+        loop {
+            let page = BALANCES
+                .range(
+                    deps.storage,
+                    last.as_ref().map(Bound::exclusive),
+                    None,
+                    Ascending,
+                )
+                .take(MIGRATION_PAGE)
+                .collect::<StdResult<Vec<_>>>()?;
+            let done = page.len() < MIGRATION_PAGE;
+            for (addr, balance) in page {
+                BALANCES.replace(deps.storage, &addr, Some(&balance), None)?;
+                last = Some(addr);
+            }
+            if done {
+                break;
+            }
+        }
+    }
+    if original_version < "1.2.0".parse::<semver::Version>().unwrap() {
+        // Index allowances by spender, replacing the hand-maintained reverse map. Like the
+        // balances above, this pages through memory but runs within this migration's gas.
+        let mut last: Option<(Addr, Addr)> = None;
+        loop {
+            let start = last
+                .as_ref()
+                .map(|(owner, spender)| Bound::exclusive((owner, spender)));
+            let page = ALLOWANCES
+                .range(deps.storage, start, None, Ascending)
+                .take(MIGRATION_PAGE)
+                .collect::<StdResult<Vec<_>>>()?;
+            let done = page.len() < MIGRATION_PAGE;
+            for ((owner, spender), allowance) in page {
+                ALLOWANCES.replace(deps.storage, (&owner, &spender), Some(&allowance), None)?;
+                last = Some((owner, spender));
+            }
+            if done {
+                break;
+            }
+        }
+        let reverse: Map<(&Addr, &Addr), AllowanceResponse> = Map::new("allowance_spender");
+        loop {
+            // removed entries drop out, so every page starts from the beginning
+            let keys = reverse
+                .keys(deps.storage, None, None, Ascending)
+                .take(MIGRATION_PAGE)
+                .collect::<StdResult<Vec<_>>>()?;
+            if keys.is_empty() {
+                break;
+            }
+            for (spender, owner) in keys {
+                reverse.remove(deps.storage, (&spender, &owner));
+            }
         }
     }
     Ok(Response::default())
@@ -385,11 +888,14 @@ mod tests {
             name: "Auto Gen".to_string(),
             symbol: "AUTO".to_string(),
             decimals: 3,
-            initial_balances: vec![Cw20Coin {
+            initial_balances: vec![InitialBalance {
                 address: addr.to_string(),
                 amount,
+                vesting: None,
             }],
             mint: mint.clone(),
+            admin: None,
+            marketing: None,
         };
         let info = mock_info("creator", &[]);
         let env = mock_env();
@@ -422,11 +928,14 @@ mod tests {
                 name: "Cash Token".to_string(),
                 symbol: "CASH".to_string(),
                 decimals: 9,
-                initial_balances: vec![Cw20Coin {
+                initial_balances: vec![InitialBalance {
                     address: String::from("addr0000"),
                     amount,
+                    vesting: None,
                 }],
                 mint: "test_minter".to_string(),
+                admin: None,
+                marketing: None,
             };
             let info = mock_info("creator", &[]);
             let env = mock_env();
@@ -457,11 +966,14 @@ mod tests {
                 name: "Cash Token".to_string(),
                 symbol: "CASH".to_string(),
                 decimals: 9,
-                initial_balances: vec![Cw20Coin {
+                initial_balances: vec![InitialBalance {
                     address: "addr0000".into(),
                     amount,
+                    vesting: None,
                 }],
                 mint: minter.clone(),
+                admin: None,
+                marketing: None,
             };
             let info = mock_info("creator", &[]);
             let env = mock_env();
@@ -561,7 +1073,13 @@ mod tests {
             };
             let mut new_env = mock_env();
             new_env.block.time = new_env.block.time.plus_days(1u64).plus_seconds(1u64);
-            execute(deps.as_mut(), new_env.clone(), mock_info("minter", &[]), msg).unwrap();
+            execute(
+                deps.as_mut(),
+                new_env.clone(),
+                mock_info("minter", &[]),
+                msg,
+            )
+            .unwrap();
             let pet_staking_data = query_pet_staking_data(deps.as_ref()).unwrap();
             assert_eq!(pet_staking_data.amount, Uint128::from(2u128));
             assert_eq!(pet_staking_data.start_time, new_env.block.time);
@@ -651,16 +1169,20 @@ mod tests {
                 symbol: "BASH".to_string(),
                 decimals: 6,
                 initial_balances: vec![
-                    Cw20Coin {
+                    InitialBalance {
                         address: addr1.clone(),
                         amount: amount1,
+                        vesting: None,
                     },
-                    Cw20Coin {
+                    InitialBalance {
                         address: addr1.clone(),
                         amount: amount2,
+                        vesting: None,
                     },
                 ],
                 mint: "test_minter".to_string(),
+                admin: None,
+                marketing: None,
             };
             let err =
                 instantiate(deps.as_mut(), env.clone(), info.clone(), instantiate_msg).unwrap_err();
@@ -672,16 +1194,20 @@ mod tests {
                 symbol: "BASH".to_string(),
                 decimals: 6,
                 initial_balances: vec![
-                    Cw20Coin {
+                    InitialBalance {
                         address: addr1.clone(),
                         amount: amount1,
+                        vesting: None,
                     },
-                    Cw20Coin {
+                    InitialBalance {
                         address: addr2.clone(),
                         amount: amount2,
+                        vesting: None,
                     },
                 ],
                 mint: "test_minter".to_string(),
+                admin: None,
+                marketing: None,
             };
             let res = instantiate(deps.as_mut(), env, info, instantiate_msg).unwrap();
             assert_eq!(0, res.messages.len());
@@ -752,6 +1278,7 @@ mod tests {
             let msg = ExecuteMsg::Transfer {
                 recipient: addr2.clone(),
                 amount: Uint128::zero(),
+                memo: None,
             };
             execute(deps.as_mut(), env, info, msg).unwrap();
 
@@ -761,6 +1288,7 @@ mod tests {
             let msg = ExecuteMsg::Transfer {
                 recipient: addr2.clone(),
                 amount: too_much,
+                memo: None,
             };
             let err = execute(deps.as_mut(), env, info, msg).unwrap_err();
             assert!(matches!(err, ContractError::Std(StdError::Overflow { .. })));
@@ -771,6 +1299,7 @@ mod tests {
             let msg = ExecuteMsg::Transfer {
                 recipient: addr1.clone(),
                 amount: transfer,
+                memo: None,
             };
             let err = execute(deps.as_mut(), env, info, msg).unwrap_err();
             assert!(matches!(err, ContractError::Std(StdError::Overflow { .. })));
@@ -781,6 +1310,7 @@ mod tests {
             let msg = ExecuteMsg::Transfer {
                 recipient: addr2.clone(),
                 amount: transfer,
+                memo: None,
             };
             let res = execute(deps.as_mut(), env, info, msg).unwrap();
             assert_eq!(res.messages.len(), 0);
@@ -793,6 +1323,171 @@ mod tests {
                 amount1
             );
         }
+
+        #[test]
+        fn transfer_with_memo() {
+            let mut deps = mock_dependencies();
+            let addr1 = String::from("addr0001");
+            let addr2 = String::from("addr0002");
+            do_instantiate(deps.as_mut(), &addr1, Uint128::new(1000));
+
+            // clients omitting the memo keep working
+            let msg: ExecuteMsg =
+                from_json(br#"{"transfer":{"recipient":"addr0002","amount":"10"}}"#.as_slice())
+                    .unwrap();
+            let res = execute(deps.as_mut(), mock_env(), mock_info(&addr1, &[]), msg).unwrap();
+            assert!(!res.attributes.iter().any(|a| a.key == "memo"));
+
+            let msg = ExecuteMsg::Transfer {
+                recipient: addr2.clone(),
+                amount: Uint128::new(10),
+                memo: Some("deposit-1234".to_string()),
+            };
+            let res = execute(deps.as_mut(), mock_env(), mock_info(&addr1, &[]), msg).unwrap();
+            assert!(res
+                .attributes
+                .iter()
+                .any(|a| a.key == "memo" && a.value == "deposit-1234"));
+
+            let msg = ExecuteMsg::Transfer {
+                recipient: addr2.clone(),
+                amount: Uint128::new(10),
+                memo: Some(str::repeat("a", MAX_MEMO_LENGTH + 1)),
+            };
+            let err = execute(deps.as_mut(), mock_env(), mock_info(&addr1, &[]), msg).unwrap_err();
+            assert_eq!(
+                err,
+                ContractError::InvalidMemo {
+                    max: MAX_MEMO_LENGTH
+                }
+            );
+            assert_eq!(get_balance(deps.as_ref(), addr2), Uint128::new(20));
+        }
+
+        #[test]
+        fn memo_on_other_paths() {
+            let mut deps = mock_dependencies();
+            let owner = String::from("addr0001");
+            let spender = String::from("addr0002");
+            let contract = String::from("addr0003");
+            do_instantiate(deps.as_mut(), &owner, Uint128::new(1000));
+            let msg = ExecuteMsg::IncreaseAllowance {
+                spender: spender.clone(),
+                amount: Uint128::new(500),
+                expires: None,
+            };
+            execute(deps.as_mut(), mock_env(), mock_info(&owner, &[]), msg).unwrap();
+
+            let too_long = Some(str::repeat("a", MAX_MEMO_LENGTH + 1));
+            let invalid = ContractError::InvalidMemo {
+                max: MAX_MEMO_LENGTH,
+            };
+            let rejected = [
+                (
+                    &spender,
+                    ExecuteMsg::TransferFrom {
+                        owner: owner.clone(),
+                        recipient: contract.clone(),
+                        amount: Uint128::new(10),
+                        memo: too_long.clone(),
+                    },
+                ),
+                (
+                    &owner,
+                    ExecuteMsg::Send {
+                        contract: contract.clone(),
+                        amount: Uint128::new(10),
+                        msg: Binary::default(),
+                        memo: too_long.clone(),
+                    },
+                ),
+                (
+                    &spender,
+                    ExecuteMsg::SendFrom {
+                        owner: owner.clone(),
+                        contract: contract.clone(),
+                        amount: Uint128::new(10),
+                        msg: Binary::default(),
+                        memo: too_long,
+                    },
+                ),
+            ];
+            for (sender, msg) in rejected {
+                let err =
+                    execute(deps.as_mut(), mock_env(), mock_info(sender, &[]), msg).unwrap_err();
+                assert_eq!(err, invalid);
+            }
+            assert_eq!(get_balance(deps.as_ref(), &contract), Uint128::zero());
+
+            let msg = ExecuteMsg::TransferFrom {
+                owner: owner.clone(),
+                recipient: contract.clone(),
+                amount: Uint128::new(10),
+                memo: Some("invoice-7".to_string()),
+            };
+            let res = execute(deps.as_mut(), mock_env(), mock_info(&spender, &[]), msg).unwrap();
+            assert!(res
+                .attributes
+                .iter()
+                .any(|a| a.key == "memo" && a.value == "invoice-7"));
+
+            let msg = ExecuteMsg::SendFrom {
+                owner,
+                contract: contract.clone(),
+                amount: Uint128::new(10),
+                msg: Binary::default(),
+                memo: Some("invoice-8".to_string()),
+            };
+            let res = execute(deps.as_mut(), mock_env(), mock_info(&spender, &[]), msg).unwrap();
+            assert!(res
+                .attributes
+                .iter()
+                .any(|a| a.key == "memo" && a.value == "invoice-8"));
+            assert_eq!(get_balance(deps.as_ref(), contract), Uint128::new(20));
+        }
+
+        #[test]
+        fn send() {
+            let mut deps = mock_dependencies();
+            let addr1 = String::from("addr0001");
+            let contract = String::from("addr0002");
+            let amount1 = Uint128::from(12340000u128);
+            let transfer = Uint128::from(76543u128);
+            let send_msg = Binary::from(r#"{"some":123}"#.as_bytes());
+
+            do_instantiate(deps.as_mut(), &addr1, amount1);
+
+            let msg = ExecuteMsg::Send {
+                contract: contract.clone(),
+                amount: transfer,
+                msg: send_msg.clone(),
+                memo: Some("stake".to_string()),
+            };
+            let res = execute(deps.as_mut(), mock_env(), mock_info(&addr1, &[]), msg).unwrap();
+            assert_eq!(res.messages.len(), 1);
+
+            // ensure proper send message sent
+            let binary_msg = Cw20ReceiveMsg {
+                sender: addr1.clone(),
+                amount: transfer,
+                msg: send_msg,
+            }
+            .into_binary()
+            .unwrap();
+            assert_eq!(
+                res.messages[0].msg,
+                CosmosMsg::Wasm(WasmMsg::Execute {
+                    contract_addr: contract.clone(),
+                    msg: binary_msg,
+                    funds: vec![],
+                })
+            );
+
+            let remainder = amount1.checked_sub(transfer).unwrap();
+            assert_eq!(get_balance(deps.as_ref(), addr1), remainder);
+            assert_eq!(get_balance(deps.as_ref(), contract), transfer);
+        }
+
         mod migration {
             use super::*;
 
@@ -824,11 +1519,14 @@ mod tests {
                             name: "Token".to_string(),
                             symbol: "TOKEN".to_string(),
                             decimals: 6,
-                            initial_balances: vec![Cw20Coin {
+                            initial_balances: vec![InitialBalance {
                                 address: "sender".to_string(),
                                 amount: Uint128::new(100),
+                                vesting: None,
                             }],
                             mint: "test_minter".to_string(),
+                            admin: None,
+                            marketing: None,
                         },
                         &[],
                         "TOKEN",
@@ -845,6 +1543,7 @@ mod tests {
                             owner: "sender".to_string(),
                             start_after: None,
                             limit: None,
+                            hide_expired: None,
                         },
                     )
                     .unwrap();
@@ -889,6 +1588,257 @@ mod tests {
 
                 assert_eq!(balance.balance, Uint128::new(100));
             }
+
+            #[test]
+            fn migrate_indexes_balances() {
+                let mut deps = mock_dependencies();
+                set_contract_version(&mut deps.storage, CONTRACT_NAME, "1.0.0").unwrap();
+
+                // simulate balances written before they were indexed
+                let legacy: cw_storage_plus::Map<&Addr, Uint128> =
+                    cw_storage_plus::Map::new("balance");
+                for (addr, amount) in [("minnow", 100u128), ("whale", 500), ("empty", 0)] {
+                    legacy
+                        .save(&mut deps.storage, &Addr::unchecked(addr), &amount.into())
+                        .unwrap();
+                }
+                // enough dust holders to span several migration pages
+                let dust = 2 * MIGRATION_PAGE + 1;
+                for i in 0..dust {
+                    let addr = Addr::unchecked(format!("dust{:03}", i));
+                    legacy
+                        .save(&mut deps.storage, &addr, &1u128.into())
+                        .unwrap();
+                }
+
+                migrate(deps.as_mut(), mock_env(), MigrateMsg {}).unwrap();
+                assert_eq!(
+                    query_holder_count(deps.as_ref()).unwrap().count,
+                    2 + dust as u64
+                );
+                let holders = query_top_holders(deps.as_ref(), None, None)
+                    .unwrap()
+                    .holders;
+                assert_eq!(
+                    holders
+                        .into_iter()
+                        .take(2)
+                        .map(|h| (h.address, h.balance.u128()))
+                        .collect::<Vec<_>>(),
+                    vec![("whale".into(), 500), ("minnow".into(), 100)]
+                );
+            }
+
+            #[test]
+            fn migrate_indexes_allowances_by_spender() {
+                let mut deps = mock_dependencies();
+                set_contract_version(&mut deps.storage, CONTRACT_NAME, "1.1.0").unwrap();
+
+                // simulate allowances kept in two hand-maintained maps
+                let owners: Map<(&Addr, &Addr), AllowanceResponse> = Map::new("allowance");
+                let spenders: Map<(&Addr, &Addr), AllowanceResponse> =
+                    Map::new("allowance_spender");
+                let allowance = AllowanceResponse {
+                    allowance: Uint128::new(7777),
+                    expires: Expiration::Never {},
+                };
+                // enough owners to span several migration pages
+                let owners_count = 2 * MIGRATION_PAGE + 1;
+                for i in 0..owners_count {
+                    let owner = format!("owner{:03}", i);
+                    let (owner, spender) = (Addr::unchecked(owner), Addr::unchecked("spender"));
+                    owners
+                        .save(&mut deps.storage, (&owner, &spender), &allowance)
+                        .unwrap();
+                    spenders
+                        .save(&mut deps.storage, (&spender, &owner), &allowance)
+                        .unwrap();
+                }
+
+                migrate(deps.as_mut(), mock_env(), MigrateMsg {}).unwrap();
+                let res = query_spender_allowances(
+                    deps.as_ref(),
+                    mock_env(),
+                    "spender".into(),
+                    None,
+                    Some(2),
+                    None,
+                )
+                .unwrap();
+                let owners: Vec<_> = res.allowances.into_iter().map(|a| a.owner).collect();
+                assert_eq!(owners, vec!["owner000", "owner001"]);
+                // the last page was indexed too
+                let res = query_spender_allowances(
+                    deps.as_ref(),
+                    mock_env(),
+                    "spender".into(),
+                    Some(format!("owner{:03}", owners_count - 2)),
+                    None,
+                    None,
+                )
+                .unwrap();
+                let owners: Vec<_> = res.allowances.into_iter().map(|a| a.owner).collect();
+                assert_eq!(owners, vec![format!("owner{:03}", owners_count - 1)]);
+
+                // the old reverse map is gone
+                let left = spenders.range(&deps.storage, None, None, Ascending).count();
+                assert_eq!(left, 0);
+            }
+        }
+    }
+
+    mod marketing {
+        use super::*;
+
+        use crate::msg::InstantiateMarketingInfo;
+
+        fn instantiate_with_marketing(deps: DepsMut, logo: Option<Logo>) {
+            let instantiate_msg = InstantiateMsg {
+                name: "Cash Token".to_string(),
+                symbol: "CASH".to_string(),
+                decimals: 9,
+                initial_balances: vec![],
+                mint: "test_minter".to_string(),
+                admin: None,
+                marketing: Some(InstantiateMarketingInfo {
+                    project: Some("Project".to_owned()),
+                    description: Some("Description".to_owned()),
+                    marketing: Some("marketing".to_owned()),
+                    logo,
+                }),
+            };
+            let info = mock_info("creator", &[]);
+            instantiate(deps, mock_env(), info, instantiate_msg).unwrap();
+        }
+
+        #[test]
+        fn basic() {
+            let mut deps = mock_dependencies();
+            instantiate_with_marketing(deps.as_mut(), Some(Logo::Url("url".to_owned())));
+
+            assert_eq!(
+                query_marketing_info(deps.as_ref()).unwrap(),
+                MarketingInfoResponse {
+                    project: Some("Project".to_owned()),
+                    description: Some("Description".to_owned()),
+                    marketing: Some(Addr::unchecked("marketing")),
+                    logo: Some(LogoInfo::Url("url".to_owned())),
+                }
+            );
+
+            let err = query_download_logo(deps.as_ref()).unwrap_err();
+            assert!(
+                matches!(err, StdError::NotFound { .. }),
+                "Expected StdError::NotFound, received {}",
+                err
+            );
+        }
+
+        #[test]
+        fn update_marketing() {
+            let mut deps = mock_dependencies();
+            instantiate_with_marketing(deps.as_mut(), None);
+
+            // only the marketing address can update
+            let msg = ExecuteMsg::UpdateMarketing {
+                project: Some("New project".to_owned()),
+                description: None,
+                marketing: None,
+            };
+            let err = execute(
+                deps.as_mut(),
+                mock_env(),
+                mock_info("creator", &[]),
+                msg.clone(),
+            )
+            .unwrap_err();
+            assert_eq!(err, ContractError::Unauthorized {});
+
+            execute(deps.as_mut(), mock_env(), mock_info("marketing", &[]), msg).unwrap();
+
+            // clearing a field with an empty string
+            let msg = ExecuteMsg::UpdateMarketing {
+                project: None,
+                description: Some("".to_owned()),
+                marketing: None,
+            };
+            execute(deps.as_mut(), mock_env(), mock_info("marketing", &[]), msg).unwrap();
+
+            assert_eq!(
+                query_marketing_info(deps.as_ref()).unwrap(),
+                MarketingInfoResponse {
+                    project: Some("New project".to_owned()),
+                    description: None,
+                    marketing: Some(Addr::unchecked("marketing")),
+                    logo: None,
+                }
+            );
+        }
+
+        #[test]
+        fn upload_logo() {
+            let mut deps = mock_dependencies();
+            instantiate_with_marketing(deps.as_mut(), None);
+
+            let png = Binary::from([0x89, b'P', b'N', b'G', 0x0d, 0x0a, 0x1a, 0x0a, 0x00]);
+            let msg = ExecuteMsg::UploadLogo(Logo::Embedded(EmbeddedLogo::Png(png.clone())));
+            execute(deps.as_mut(), mock_env(), mock_info("marketing", &[]), msg).unwrap();
+
+            assert_eq!(
+                query_marketing_info(deps.as_ref()).unwrap().logo,
+                Some(LogoInfo::Embedded)
+            );
+            assert_eq!(
+                query_download_logo(deps.as_ref()).unwrap(),
+                DownloadLogoResponse {
+                    mime_type: "image/png".to_owned(),
+                    data: png,
+                }
+            );
+
+            let svg = Binary::from(
+                br#"<?xml version="1.0"?><svg xmlns="http://www.w3.org/2000/svg"></svg>"#
+                    .as_slice(),
+            );
+            let msg = ExecuteMsg::UploadLogo(Logo::Embedded(EmbeddedLogo::Svg(svg.clone())));
+            execute(deps.as_mut(), mock_env(), mock_info("marketing", &[]), msg).unwrap();
+            assert_eq!(
+                query_download_logo(deps.as_ref()).unwrap(),
+                DownloadLogoResponse {
+                    mime_type: "image/svg+xml".to_owned(),
+                    data: svg,
+                }
+            );
+        }
+
+        #[test]
+        fn invalid_logos_rejected() {
+            let mut deps = mock_dependencies();
+            instantiate_with_marketing(deps.as_mut(), None);
+
+            let msg = ExecuteMsg::UploadLogo(Logo::Embedded(EmbeddedLogo::Png(Binary::from(
+                b"not a png".as_slice(),
+            ))));
+            let err =
+                execute(deps.as_mut(), mock_env(), mock_info("marketing", &[]), msg).unwrap_err();
+            assert_eq!(err, ContractError::InvalidPngHeader {});
+
+            let msg = ExecuteMsg::UploadLogo(Logo::Embedded(EmbeddedLogo::Svg(Binary::from(
+                b"<svg></svg>".as_slice(),
+            ))));
+            let err =
+                execute(deps.as_mut(), mock_env(), mock_info("marketing", &[]), msg).unwrap_err();
+            assert_eq!(err, ContractError::InvalidXmlPreamble {});
+
+            let mut svg = br#"<?xml version="1.0"?><svg>"#.to_vec();
+            svg.resize(LOGO_SIZE_CAP + 1, b' ');
+            let msg = ExecuteMsg::UploadLogo(Logo::Embedded(EmbeddedLogo::Svg(svg.into())));
+            let err =
+                execute(deps.as_mut(), mock_env(), mock_info("marketing", &[]), msg).unwrap_err();
+            assert_eq!(err, ContractError::LogoTooBig {});
+
+            // nothing was stored
+            assert_eq!(query_marketing_info(deps.as_ref()).unwrap().logo, None);
         }
     }
 }
@@ -1,10 +1,12 @@
-use cosmwasm_std::{Deps, Order, StdResult};
-use cw20::{
-    AllAccountsResponse, AllAllowancesResponse, AllSpenderAllowancesResponse, AllowanceInfo,
-    SpenderAllowanceInfo,
-};
+use cosmwasm_std::{Addr, Deps, Env, Order, StdResult, Uint128};
+use cw20::{AllAccountsResponse, AllowanceInfo, SpenderAllowanceInfo};
 
-use crate::state::{ALLOWANCES, ALLOWANCES_SPENDER, BALANCES};
+use crate::allowances::refilled_allowance;
+use crate::msg::{
+    AllBalancesResponse, HolderCountResponse, HolderInfo, OwnerAllowancesResponse,
+    SpenderAllowancesResponse, TopHoldersResponse, VerifySupplyResponse,
+};
+use crate::state::{ALLOWANCES, BALANCES, TOKEN_INFO};
 use cw_storage_plus::Bound;
 
 // settings for pagination
@@ -13,52 +15,90 @@ const DEFAULT_LIMIT: u32 = 10;
 
 pub fn query_owner_allowances(
     deps: Deps,
+    env: Env,
     owner: String,
     start_after: Option<String>,
     limit: Option<u32>,
-) -> StdResult<AllAllowancesResponse> {
+    hide_expired: Option<bool>,
+) -> StdResult<OwnerAllowancesResponse> {
     let owner_addr = deps.api.addr_validate(&owner)?;
     let limit = limit.unwrap_or(DEFAULT_LIMIT).min(MAX_LIMIT) as usize;
     let start = start_after.map(|s| Bound::ExclusiveRaw(s.into_bytes()));
+    let hide_expired = hide_expired.unwrap_or(false);
 
-    let allowances = ALLOWANCES
+    // expired allowances are hidden after the page is read, so a page costs the same
+    // however many of them there are
+    let page = ALLOWANCES
         .prefix(&owner_addr)
         .range(deps.storage, start, None, Order::Ascending)
         .take(limit)
-        .map(|item| {
-            item.map(|(addr, allow)| AllowanceInfo {
-                spender: addr.into(),
+        .collect::<StdResult<Vec<_>>>()?;
+    let start_after = match page.last() {
+        Some((spender, _)) if hide_expired && page.len() == limit => Some(spender.to_string()),
+        _ => None,
+    };
+
+    let allowances = page
+        .into_iter()
+        .filter(|(_, allow)| !(hide_expired && allow.expires.is_expired(&env.block)))
+        .map(|(spender, allow)| {
+            let allow =
+                refilled_allowance(deps.storage, &owner_addr, &spender, allow, &env.block.time)?;
+            Ok(AllowanceInfo {
+                spender: spender.into(),
                 allowance: allow.allowance,
                 expires: allow.expires,
             })
         })
         .collect::<StdResult<_>>()?;
-    Ok(AllAllowancesResponse { allowances })
+    Ok(OwnerAllowancesResponse {
+        allowances,
+        start_after,
+    })
 }
 
 pub fn query_spender_allowances(
     deps: Deps,
+    env: Env,
     spender: String,
     start_after: Option<String>,
     limit: Option<u32>,
-) -> StdResult<AllSpenderAllowancesResponse> {
+    hide_expired: Option<bool>,
+) -> StdResult<SpenderAllowancesResponse> {
     let spender_addr = deps.api.addr_validate(&spender)?;
     let limit = limit.unwrap_or(DEFAULT_LIMIT).min(MAX_LIMIT) as usize;
-    let start = start_after.map(|s| Bound::ExclusiveRaw(s.into_bytes()));
+    let start = start_after.map(|s| Bound::exclusive((Addr::unchecked(s), spender_addr.clone())));
+    let hide_expired = hide_expired.unwrap_or(false);
 
-    let allowances = ALLOWANCES_SPENDER
-        .prefix(&spender_addr)
+    let page = ALLOWANCES
+        .idx
+        .spender
+        .prefix(spender_addr.clone())
         .range(deps.storage, start, None, Order::Ascending)
         .take(limit)
-        .map(|item| {
-            item.map(|(addr, allow)| SpenderAllowanceInfo {
-                owner: addr.into(),
+        .collect::<StdResult<Vec<_>>>()?;
+    let start_after = match page.last() {
+        Some(((owner, _), _)) if hide_expired && page.len() == limit => Some(owner.to_string()),
+        _ => None,
+    };
+
+    let allowances = page
+        .into_iter()
+        .filter(|(_, allow)| !(hide_expired && allow.expires.is_expired(&env.block)))
+        .map(|((owner, _), allow)| {
+            let allow =
+                refilled_allowance(deps.storage, &owner, &spender_addr, allow, &env.block.time)?;
+            Ok(SpenderAllowanceInfo {
+                owner: owner.into(),
                 allowance: allow.allowance,
                 expires: allow.expires,
             })
         })
         .collect::<StdResult<_>>()?;
-    Ok(AllSpenderAllowancesResponse { allowances })
+    Ok(SpenderAllowancesResponse {
+        allowances,
+        start_after,
+    })
 }
 
 pub fn query_all_accounts(
@@ -78,16 +118,110 @@ pub fn query_all_accounts(
     Ok(AllAccountsResponse { accounts })
 }
 
+pub fn query_all_balances(
+    deps: Deps,
+    start_after: Option<String>,
+    limit: Option<u32>,
+    skip_zero: Option<bool>,
+) -> StdResult<AllBalancesResponse> {
+    let limit = limit.unwrap_or(DEFAULT_LIMIT).min(MAX_LIMIT) as usize;
+    let start = start_after.map(|s| Bound::ExclusiveRaw(s.into()));
+    let skip_zero = skip_zero.unwrap_or(false);
+
+    let balances = BALANCES
+        .range(deps.storage, start, None, Order::Ascending)
+        .filter(|item| !(skip_zero && matches!(item, Ok((_, balance)) if balance.is_zero())))
+        .take(limit)
+        .map(|item| {
+            item.map(|(addr, balance)| HolderInfo {
+                address: addr.into(),
+                balance,
+            })
+        })
+        .collect::<StdResult<_>>()?;
+    Ok(AllBalancesResponse { balances })
+}
+
+pub fn query_verify_supply(
+    deps: Deps,
+    start_after: Option<String>,
+    limit: Option<u32>,
+    running_sum: Option<Uint128>,
+) -> StdResult<VerifySupplyResponse> {
+    let limit = limit.unwrap_or(DEFAULT_LIMIT).min(MAX_LIMIT) as usize;
+    let start = start_after.clone().map(|s| Bound::ExclusiveRaw(s.into()));
+    let total_supply = TOKEN_INFO.load(deps.storage)?.total_supply;
+
+    // fetch one more to know whether this is the last page
+    let mut balances = BALANCES
+        .range(deps.storage, start, None, Order::Ascending)
+        .take(limit + 1)
+        .collect::<StdResult<Vec<_>>>()?;
+    let complete = balances.len() <= limit;
+    balances.truncate(limit);
+
+    let mut running_sum = running_sum.unwrap_or_default();
+    for (_, balance) in &balances {
+        running_sum = running_sum.checked_add(*balance)?;
+    }
+    let last_account = balances.pop().map(|(addr, _)| addr.into()).or(start_after);
+    let mismatch = running_sum > total_supply || (complete && running_sum != total_supply);
+
+    Ok(VerifySupplyResponse {
+        total_supply,
+        running_sum,
+        last_account,
+        complete,
+        mismatch,
+    })
+}
+
+pub fn query_holder_count(deps: Deps) -> StdResult<HolderCountResponse> {
+    let count = BALANCES.idx.holders.load(deps.storage)?;
+    Ok(HolderCountResponse { count })
+}
+
+pub fn query_top_holders(
+    deps: Deps,
+    limit: Option<u32>,
+    start_after: Option<HolderInfo>,
+) -> StdResult<TopHoldersResponse> {
+    let limit = limit.unwrap_or(DEFAULT_LIMIT).min(MAX_LIMIT) as usize;
+    let end = match start_after {
+        Some(cursor) => {
+            let addr = deps.api.addr_validate(&cursor.address)?;
+            Some(Bound::exclusive((cursor.balance.u128(), addr)))
+        }
+        None => None,
+    };
+
+    let holders = BALANCES
+        .idx
+        .amount
+        .range(deps.storage, None, end, Order::Descending)
+        // accounts which emptied their balance are kept, but sort last
+        .take_while(|item| !matches!(item, Ok((_, balance)) if balance.is_zero()))
+        .take(limit)
+        .map(|item| {
+            item.map(|(addr, balance): (_, Uint128)| HolderInfo {
+                address: addr.into(),
+                balance,
+            })
+        })
+        .collect::<StdResult<_>>()?;
+    Ok(TopHoldersResponse { holders })
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
 
     use cosmwasm_std::testing::{mock_dependencies_with_balance, mock_env, mock_info};
-    use cosmwasm_std::{coins, DepsMut, Uint128};
-    use cw20::{Cw20Coin, Expiration, TokenInfoResponse};
+    use cosmwasm_std::{coins, Addr, DepsMut, Uint128};
+    use cw20::{Expiration, TokenInfoResponse};
 
     use crate::contract::{execute, instantiate, query_token_info};
-    use crate::msg::{ExecuteMsg, InstantiateMsg};
+    use crate::msg::{ExecuteMsg, InitialBalance, InstantiateMsg};
 
     // this will set up the instantiation for other tests
     fn do_instantiate(mut deps: DepsMut, addr: &str, amount: Uint128) -> TokenInfoResponse {
@@ -95,11 +229,14 @@ mod tests {
             name: "Auto Gen".to_string(),
             symbol: "AUTO".to_string(),
             decimals: 3,
-            initial_balances: vec![Cw20Coin {
+            initial_balances: vec![InitialBalance {
                 address: addr.into(),
                 amount,
+                vesting: None,
             }],
             mint: "test_mint".to_string(),
+            admin: None,
+            marketing: None,
         };
         let info = mock_info("creator", &[]);
         let env = mock_env();
@@ -121,7 +258,9 @@ mod tests {
         do_instantiate(deps.as_mut(), &owner, Uint128::new(12340000));
 
         // no allowance to start
-        let allowances = query_owner_allowances(deps.as_ref(), owner.clone(), None, None).unwrap();
+        let allowances =
+            query_owner_allowances(deps.as_ref(), mock_env(), owner.clone(), None, None, None)
+                .unwrap();
         assert_eq!(allowances.allowances, vec![]);
 
         // set allowance with height expiration
@@ -144,12 +283,21 @@ mod tests {
         execute(deps.as_mut(), env, info, msg).unwrap();
 
         // query list gets 2
-        let allowances = query_owner_allowances(deps.as_ref(), owner.clone(), None, None).unwrap();
+        let allowances =
+            query_owner_allowances(deps.as_ref(), mock_env(), owner.clone(), None, None, None)
+                .unwrap();
         assert_eq!(allowances.allowances.len(), 2);
 
         // first one is spender1 (order of CanonicalAddr uncorrelated with String)
-        let allowances =
-            query_owner_allowances(deps.as_ref(), owner.clone(), None, Some(1)).unwrap();
+        let allowances = query_owner_allowances(
+            deps.as_ref(),
+            mock_env(),
+            owner.clone(),
+            None,
+            Some(1),
+            None,
+        )
+        .unwrap();
         assert_eq!(allowances.allowances.len(), 1);
         let allow = &allowances.allowances[0];
         assert_eq!(&allow.spender, &spender1);
@@ -159,9 +307,11 @@ mod tests {
         // next one is spender2
         let allowances = query_owner_allowances(
             deps.as_ref(),
+            mock_env(),
             owner,
             Some(allow.spender.clone()),
             Some(10000),
+            None,
         )
         .unwrap();
         assert_eq!(allowances.allowances.len(), 1);
@@ -194,6 +344,7 @@ mod tests {
             ExecuteMsg::Transfer {
                 recipient: acct2,
                 amount: Uint128::new(222222),
+                memo: None,
             },
         )
         .unwrap();
@@ -204,6 +355,7 @@ mod tests {
             ExecuteMsg::Transfer {
                 recipient: acct3,
                 amount: Uint128::new(333333),
+                memo: None,
             },
         )
         .unwrap();
@@ -214,6 +366,7 @@ mod tests {
             ExecuteMsg::Transfer {
                 recipient: acct4,
                 amount: Uint128::new(444444),
+                memo: None,
             },
         )
         .unwrap();
@@ -235,4 +388,190 @@ mod tests {
                 .unwrap();
         assert_eq!(accounts.accounts, expected_order[3..].to_vec());
     }
+
+    #[test]
+    fn query_all_balances_works() {
+        let mut deps = mock_dependencies_with_balance(&coins(2, "token"));
+        do_instantiate(deps.as_mut(), "owner", Uint128::new(1000));
+
+        let transfer = |deps: DepsMut, from: &str, to: &str, amount: u128| {
+            let msg = ExecuteMsg::Transfer {
+                recipient: to.into(),
+                amount: Uint128::new(amount),
+                memo: None,
+            };
+            execute(deps, mock_env(), mock_info(from, &[]), msg).unwrap();
+        };
+        transfer(deps.as_mut(), "owner", "bob", 300);
+        transfer(deps.as_mut(), "owner", "carl", 500);
+        transfer(deps.as_mut(), "bob", "carl", 300);
+
+        let balance = |address: &str, amount: u128| HolderInfo {
+            address: address.into(),
+            balance: Uint128::new(amount),
+        };
+        let balances = query_all_balances(deps.as_ref(), None, None, None)
+            .unwrap()
+            .balances;
+        assert_eq!(
+            balances,
+            vec![
+                balance("bob", 0),
+                balance("carl", 800),
+                balance("owner", 200)
+            ]
+        );
+
+        // let's do pagination
+        let page = query_all_balances(deps.as_ref(), Some("bob".into()), Some(1), None)
+            .unwrap()
+            .balances;
+        assert_eq!(page, vec![balance("carl", 800)]);
+
+        // empty accounts can be skipped without shrinking the page
+        let page = query_all_balances(deps.as_ref(), None, Some(2), Some(true))
+            .unwrap()
+            .balances;
+        assert_eq!(page, vec![balance("carl", 800), balance("owner", 200)]);
+    }
+
+    #[test]
+    fn query_verify_supply_works() {
+        let mut deps = mock_dependencies_with_balance(&coins(2, "token"));
+        do_instantiate(deps.as_mut(), "owner", Uint128::new(1000));
+
+        for (recipient, amount) in [("bob", 300u128), ("carl", 200)] {
+            let msg = ExecuteMsg::Transfer {
+                recipient: recipient.into(),
+                amount: Uint128::new(amount),
+                memo: None,
+            };
+            execute(deps.as_mut(), mock_env(), mock_info("owner", &[]), msg).unwrap();
+        }
+
+        // walk all pages, carrying the running sum
+        let page = query_verify_supply(deps.as_ref(), None, Some(2), None).unwrap();
+        assert_eq!(
+            page,
+            VerifySupplyResponse {
+                total_supply: Uint128::new(1000),
+                running_sum: Uint128::new(500),
+                last_account: Some("carl".into()),
+                complete: false,
+                mismatch: false,
+            }
+        );
+        let page = query_verify_supply(
+            deps.as_ref(),
+            page.last_account,
+            Some(2),
+            Some(page.running_sum),
+        )
+        .unwrap();
+        assert_eq!(page.running_sum, Uint128::new(1000));
+        assert!(page.complete);
+        assert!(!page.mismatch);
+
+        // a balance which was not accounted for in the supply is reported
+        BALANCES
+            .save(
+                &mut deps.storage,
+                &Addr::unchecked("ghost"),
+                &Uint128::new(1),
+            )
+            .unwrap();
+        let page = query_verify_supply(deps.as_ref(), None, None, None).unwrap();
+        assert_eq!(page.running_sum, Uint128::new(1001));
+        assert!(page.complete);
+        assert!(page.mismatch);
+    }
+
+    #[test]
+    fn query_top_holders_works() {
+        let mut deps = mock_dependencies_with_balance(&coins(2, "token"));
+        do_instantiate(deps.as_mut(), "owner", Uint128::new(1000));
+
+        let transfer = |deps: DepsMut, from: &str, to: &str, amount: u128| {
+            let msg = ExecuteMsg::Transfer {
+                recipient: to.into(),
+                amount: Uint128::new(amount),
+                memo: None,
+            };
+            execute(deps, mock_env(), mock_info(from, &[]), msg).unwrap();
+        };
+        transfer(deps.as_mut(), "owner", "bob", 300);
+        transfer(deps.as_mut(), "owner", "carl", 500);
+
+        assert_eq!(query_holder_count(deps.as_ref()).unwrap().count, 3);
+        let holders = query_top_holders(deps.as_ref(), None, None)
+            .unwrap()
+            .holders;
+        assert_eq!(
+            holders,
+            vec![
+                HolderInfo {
+                    address: "carl".into(),
+                    balance: Uint128::new(500),
+                },
+                HolderInfo {
+                    address: "bob".into(),
+                    balance: Uint128::new(300),
+                },
+                HolderInfo {
+                    address: "owner".into(),
+                    balance: Uint128::new(200),
+                },
+            ]
+        );
+
+        // let's do pagination
+        let page = query_top_holders(deps.as_ref(), Some(1), None)
+            .unwrap()
+            .holders;
+        assert_eq!(page, holders[0..1].to_vec());
+        let page = query_top_holders(deps.as_ref(), Some(1), Some(holders[0].clone()))
+            .unwrap()
+            .holders;
+        assert_eq!(page, holders[1..2].to_vec());
+
+        // the cursor holds even if its holder's balance changed since the last page
+        transfer(deps.as_mut(), "carl", "owner", 250);
+        let page = query_top_holders(deps.as_ref(), Some(2), Some(holders[0].clone()))
+            .unwrap()
+            .holders;
+        assert_eq!(
+            page,
+            vec![
+                HolderInfo {
+                    address: "owner".into(),
+                    balance: Uint128::new(450),
+                },
+                HolderInfo {
+                    address: "bob".into(),
+                    balance: Uint128::new(300),
+                },
+            ]
+        );
+        transfer(deps.as_mut(), "owner", "carl", 250);
+
+        // emptied accounts are no longer holders
+        transfer(deps.as_mut(), "bob", "carl", 300);
+        assert_eq!(query_holder_count(deps.as_ref()).unwrap().count, 2);
+        let holders = query_top_holders(deps.as_ref(), None, None)
+            .unwrap()
+            .holders;
+        assert_eq!(
+            holders,
+            vec![
+                HolderInfo {
+                    address: "carl".into(),
+                    balance: Uint128::new(800),
+                },
+                HolderInfo {
+                    address: "owner".into(),
+                    balance: Uint128::new(200),
+                },
+            ]
+        );
+    }
 }
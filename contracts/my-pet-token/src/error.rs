@@ -1,11 +1,19 @@
-use cosmwasm_std::StdError;
+use cosmwasm_std::{StdError, Uint128};
 use thiserror::Error;
 
+use cw_controllers::{AdminError, HookError};
+
 #[derive(Error, Debug, PartialEq)]
 pub enum ContractError {
     #[error("{0}")]
     Std(#[from] StdError),
 
+    #[error("{0}")]
+    Admin(#[from] AdminError),
+
+    #[error("{0}")]
+    Hook(#[from] HookError),
+
     #[error("Unauthorized")]
     Unauthorized {},
 
@@ -29,9 +37,68 @@ pub enum ContractError {
     #[error("Invalid expiration value")]
     InvalidExpiration {},
 
+    #[error("Logo binary data exceeds 5KB limit")]
+    LogoTooBig {},
+
+    #[error("Invalid xml preamble for SVG")]
+    InvalidXmlPreamble {},
+
+    #[error("Invalid png header")]
+    InvalidPngHeader {},
+
     #[error("Duplicate initial balance addresses")]
     DuplicateInitialBalanceAddresses {},
 
     #[error("Token limit of 1M per day for pet staking has been reached")]
     TokenLimitReached {},
+
+    #[error("Cannot spend tokens which are still locked by vesting")]
+    TokensLocked {},
+
+    #[error("No vesting schedule for this account")]
+    NoVesting {},
+
+    #[error("No vested tokens that can be released currently")]
+    NothingToRelease {},
+
+    #[error("Escrow amount must be greater than zero")]
+    EmptyEscrow {},
+
+    #[error("Escrow not found")]
+    EscrowNotFound {},
+
+    #[error("Escrow cannot be claimed before its release time")]
+    EscrowNotReleased {},
+
+    #[error("Escrow cannot be cancelled after its release time")]
+    EscrowReleased {},
+
+    #[error("Stream amount must be greater than zero")]
+    EmptyStream {},
+
+    #[error("Stream not found")]
+    StreamNotFound {},
+
+    #[error("Stream must end after it starts and after the current block time")]
+    InvalidStreamTimes {},
+
+    #[error("No streamed tokens that can be withdrawn currently")]
+    NothingToWithdraw {},
+
+    #[error("Memo must not exceed {max} bytes")]
+    InvalidMemo { max: usize },
+
+    #[error("Cannot register more than {max} hooks")]
+    TooManyHooks { max: u32 },
+
+    #[error("Hook must subscribe to at least one kind of balance change")]
+    NoHookKinds {},
+
+    #[error("Refill period must be longer than zero")]
+    InvalidRefillPeriod {},
+
+    #[error(
+        "Cannot recover more than the {available} tokens not backing escrows, streams or stakes"
+    )]
+    RecoverExceedsSurplus { available: Uint128 },
 }
@@ -0,0 +1,407 @@
+use cosmwasm_std::{
+    attr, Addr, Deps, DepsMut, Env, MessageInfo, Order, Response, StdResult, SubMsg, Uint128,
+};
+use cw20::Expiration;
+use cw_storage_plus::Bound;
+
+use crate::error::ContractError;
+use crate::hooks::prepare_hooks;
+use crate::msg::{BalanceChangeKind, EscrowResponse, EscrowsResponse};
+use crate::recovery::{hold, unhold};
+use crate::state::{escrows, Escrow, BALANCES, ESCROW_COUNT};
+use crate::vesting::assert_unlocked;
+
+// settings for pagination
+const MAX_LIMIT: u32 = 30;
+const DEFAULT_LIMIT: u32 = 10;
+
+pub fn execute_create_escrow(
+    deps: DepsMut,
+    env: Env,
+    info: MessageInfo,
+    recipient: String,
+    amount: Uint128,
+    release_at: Expiration,
+) -> Result<Response, ContractError> {
+    let rcpt_addr = deps.api.addr_validate(&recipient)?;
+    if amount.is_zero() {
+        return Err(ContractError::EmptyEscrow {});
+    }
+    if release_at.is_expired(&env.block) || matches!(release_at, Expiration::Never {}) {
+        return Err(ContractError::InvalidExpiration {});
+    }
+
+    assert_unlocked(deps.storage, &info.sender, &info.sender, None, amount)?;
+
+    // escrowed tokens are held by the contract itself until claimed or cancelled
+    BALANCES.update(
+        deps.storage,
+        &info.sender,
+        |balance: Option<Uint128>| -> StdResult<_> {
+            Ok(balance.unwrap_or_default().checked_sub(amount)?)
+        },
+    )?;
+    BALANCES.update(
+        deps.storage,
+        &env.contract.address,
+        |balance: Option<Uint128>| -> StdResult<_> { Ok(balance.unwrap_or_default() + amount) },
+    )?;
+    hold(deps.storage, amount)?;
+
+    let id = ESCROW_COUNT.may_load(deps.storage)?.unwrap_or_default() + 1;
+    ESCROW_COUNT.save(deps.storage, &id)?;
+    let escrow = Escrow {
+        sender: info.sender.clone(),
+        recipient: rcpt_addr,
+        amount,
+        release_at,
+    };
+    escrows().save(deps.storage, id, &escrow)?;
+
+    let hooks = prepare_hooks(
+        deps.storage,
+        BalanceChangeKind::Transfer,
+        Some(&info.sender),
+        Some(&env.contract.address),
+        amount,
+    )?;
+
+    let res = Response::new().add_submessages(hooks).add_attributes(vec![
+        attr("action", "create_escrow"),
+        attr("id", id.to_string()),
+        attr("from", info.sender),
+        attr("to", recipient),
+        attr("amount", amount),
+        attr("release_at", release_at.to_string()),
+    ]);
+    Ok(res)
+}
+
+pub fn execute_claim_escrow(
+    deps: DepsMut,
+    env: Env,
+    info: MessageInfo,
+    id: u64,
+) -> Result<Response, ContractError> {
+    let escrow = escrows()
+        .may_load(deps.storage, id)?
+        .ok_or(ContractError::EscrowNotFound {})?;
+    if escrow.recipient != info.sender {
+        return Err(ContractError::Unauthorized {});
+    }
+    if !escrow.release_at.is_expired(&env.block) {
+        return Err(ContractError::EscrowNotReleased {});
+    }
+
+    let hooks = release_escrow(deps, &env, id, &escrow, &escrow.recipient)?;
+
+    let res = Response::new().add_submessages(hooks).add_attributes(vec![
+        attr("action", "claim_escrow"),
+        attr("id", id.to_string()),
+        attr("from", escrow.sender),
+        attr("to", escrow.recipient),
+        attr("amount", escrow.amount),
+    ]);
+    Ok(res)
+}
+
+pub fn execute_cancel_escrow(
+    deps: DepsMut,
+    env: Env,
+    info: MessageInfo,
+    id: u64,
+) -> Result<Response, ContractError> {
+    let escrow = escrows()
+        .may_load(deps.storage, id)?
+        .ok_or(ContractError::EscrowNotFound {})?;
+    if escrow.sender != info.sender {
+        return Err(ContractError::Unauthorized {});
+    }
+    if escrow.release_at.is_expired(&env.block) {
+        return Err(ContractError::EscrowReleased {});
+    }
+
+    let hooks = release_escrow(deps, &env, id, &escrow, &escrow.sender)?;
+
+    let res = Response::new().add_submessages(hooks).add_attributes(vec![
+        attr("action", "cancel_escrow"),
+        attr("id", id.to_string()),
+        attr("from", escrow.sender),
+        attr("to", escrow.recipient),
+        attr("amount", escrow.amount),
+    ]);
+    Ok(res)
+}
+
+// moves the escrowed tokens from the contract to `to`, deletes the escrow and returns the hooks
+fn release_escrow(
+    deps: DepsMut,
+    env: &Env,
+    id: u64,
+    escrow: &Escrow,
+    to: &Addr,
+) -> Result<Vec<SubMsg>, ContractError> {
+    escrows().remove(deps.storage, id)?;
+    unhold(deps.storage, escrow.amount)?;
+    BALANCES.update(
+        deps.storage,
+        &env.contract.address,
+        |balance: Option<Uint128>| -> StdResult<_> {
+            Ok(balance.unwrap_or_default().checked_sub(escrow.amount)?)
+        },
+    )?;
+    BALANCES.update(
+        deps.storage,
+        to,
+        |balance: Option<Uint128>| -> StdResult<_> {
+            Ok(balance.unwrap_or_default() + escrow.amount)
+        },
+    )?;
+    let hooks = prepare_hooks(
+        deps.storage,
+        BalanceChangeKind::Transfer,
+        Some(&env.contract.address),
+        Some(to),
+        escrow.amount,
+    )?;
+    Ok(hooks)
+}
+
+fn to_escrow_response(id: u64, escrow: Escrow) -> EscrowResponse {
+    EscrowResponse {
+        id,
+        sender: escrow.sender.into(),
+        recipient: escrow.recipient.into(),
+        amount: escrow.amount,
+        release_at: escrow.release_at,
+    }
+}
+
+pub fn query_escrow(deps: Deps, id: u64) -> StdResult<EscrowResponse> {
+    let escrow = escrows().load(deps.storage, id)?;
+    Ok(to_escrow_response(id, escrow))
+}
+
+pub fn query_escrows_by_sender(
+    deps: Deps,
+    sender: String,
+    start_after: Option<u64>,
+    limit: Option<u32>,
+) -> StdResult<EscrowsResponse> {
+    let sender_addr = deps.api.addr_validate(&sender)?;
+    let limit = limit.unwrap_or(DEFAULT_LIMIT).min(MAX_LIMIT) as usize;
+    let start = start_after.map(Bound::exclusive);
+
+    let escrows = escrows()
+        .idx
+        .sender
+        .prefix(sender_addr)
+        .range(deps.storage, start, None, Order::Ascending)
+        .take(limit)
+        .map(|item| item.map(|(id, escrow)| to_escrow_response(id, escrow)))
+        .collect::<StdResult<_>>()?;
+    Ok(EscrowsResponse { escrows })
+}
+
+pub fn query_escrows_by_recipient(
+    deps: Deps,
+    recipient: String,
+    start_after: Option<u64>,
+    limit: Option<u32>,
+) -> StdResult<EscrowsResponse> {
+    let rcpt_addr = deps.api.addr_validate(&recipient)?;
+    let limit = limit.unwrap_or(DEFAULT_LIMIT).min(MAX_LIMIT) as usize;
+    let start = start_after.map(Bound::exclusive);
+
+    let escrows = escrows()
+        .idx
+        .recipient
+        .prefix(rcpt_addr)
+        .range(deps.storage, start, None, Order::Ascending)
+        .take(limit)
+        .map(|item| item.map(|(id, escrow)| to_escrow_response(id, escrow)))
+        .collect::<StdResult<_>>()?;
+    Ok(EscrowsResponse { escrows })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use cosmwasm_std::testing::{mock_dependencies, mock_env, mock_info};
+
+    use crate::contract::{execute, instantiate, query_balance};
+    use crate::msg::{ExecuteMsg, InitialBalance, InstantiateMsg};
+
+    fn get_balance<T: Into<String>>(deps: Deps, address: T) -> Uint128 {
+        query_balance(deps, address.into()).unwrap().balance
+    }
+
+    // this will set up the instantiation for other tests
+    fn do_instantiate(deps: DepsMut, addr: &str, amount: Uint128) {
+        let instantiate_msg = InstantiateMsg {
+            name: "Auto Gen".to_string(),
+            symbol: "AUTO".to_string(),
+            decimals: 3,
+            initial_balances: vec![InitialBalance {
+                address: addr.into(),
+                amount,
+                vesting: None,
+            }],
+            mint: "test_minter".to_string(),
+            admin: None,
+            marketing: None,
+        };
+        let info = mock_info("creator", &[]);
+        instantiate(deps, mock_env(), info, instantiate_msg).unwrap();
+    }
+
+    fn create_escrow(deps: DepsMut, sender: &str, recipient: &str, amount: u128) -> u64 {
+        let env = mock_env();
+        let msg = ExecuteMsg::CreateEscrow {
+            recipient: recipient.into(),
+            amount: Uint128::new(amount),
+            release_at: Expiration::AtTime(env.block.time.plus_days(1)),
+        };
+        let res = execute(deps, env, mock_info(sender, &[]), msg).unwrap();
+        res.attributes[1].value.parse().unwrap()
+    }
+
+    #[test]
+    fn create_and_claim_escrow() {
+        let mut deps = mock_dependencies();
+        let contract = mock_env().contract.address;
+        do_instantiate(deps.as_mut(), "sender", Uint128::new(1000));
+
+        let id = create_escrow(deps.as_mut(), "sender", "rcpt", 400);
+        assert_eq!(get_balance(deps.as_ref(), "sender"), Uint128::new(600));
+        assert_eq!(get_balance(deps.as_ref(), &contract), Uint128::new(400));
+
+        // only the recipient can claim, and only after the release time
+        let err = execute(
+            deps.as_mut(),
+            mock_env(),
+            mock_info("rcpt", &[]),
+            ExecuteMsg::ClaimEscrow { id },
+        )
+        .unwrap_err();
+        assert_eq!(err, ContractError::EscrowNotReleased {});
+
+        let mut env = mock_env();
+        env.block.time = env.block.time.plus_days(1);
+        let err = execute(
+            deps.as_mut(),
+            env.clone(),
+            mock_info("sender", &[]),
+            ExecuteMsg::ClaimEscrow { id },
+        )
+        .unwrap_err();
+        assert_eq!(err, ContractError::Unauthorized {});
+
+        // cannot be cancelled anymore either
+        let err = execute(
+            deps.as_mut(),
+            env.clone(),
+            mock_info("sender", &[]),
+            ExecuteMsg::CancelEscrow { id },
+        )
+        .unwrap_err();
+        assert_eq!(err, ContractError::EscrowReleased {});
+
+        execute(
+            deps.as_mut(),
+            env.clone(),
+            mock_info("rcpt", &[]),
+            ExecuteMsg::ClaimEscrow { id },
+        )
+        .unwrap();
+        assert_eq!(get_balance(deps.as_ref(), "rcpt"), Uint128::new(400));
+        assert_eq!(get_balance(deps.as_ref(), &contract), Uint128::zero());
+
+        // claimed escrows are gone
+        let err = execute(
+            deps.as_mut(),
+            env,
+            mock_info("rcpt", &[]),
+            ExecuteMsg::ClaimEscrow { id },
+        )
+        .unwrap_err();
+        assert_eq!(err, ContractError::EscrowNotFound {});
+    }
+
+    #[test]
+    fn cancel_escrow() {
+        let mut deps = mock_dependencies();
+        do_instantiate(deps.as_mut(), "sender", Uint128::new(1000));
+
+        // release time must be in the future
+        let msg = ExecuteMsg::CreateEscrow {
+            recipient: "rcpt".into(),
+            amount: Uint128::new(1),
+            release_at: Expiration::AtHeight(mock_env().block.height),
+        };
+        let err = execute(deps.as_mut(), mock_env(), mock_info("sender", &[]), msg).unwrap_err();
+        assert_eq!(err, ContractError::InvalidExpiration {});
+
+        let msg = ExecuteMsg::CreateEscrow {
+            recipient: "rcpt".into(),
+            amount: Uint128::zero(),
+            release_at: Expiration::AtTime(mock_env().block.time.plus_days(1)),
+        };
+        let err = execute(deps.as_mut(), mock_env(), mock_info("sender", &[]), msg).unwrap_err();
+        assert_eq!(err, ContractError::EmptyEscrow {});
+
+        let id = create_escrow(deps.as_mut(), "sender", "rcpt", 400);
+        let err = execute(
+            deps.as_mut(),
+            mock_env(),
+            mock_info("rcpt", &[]),
+            ExecuteMsg::CancelEscrow { id },
+        )
+        .unwrap_err();
+        assert_eq!(err, ContractError::Unauthorized {});
+
+        execute(
+            deps.as_mut(),
+            mock_env(),
+            mock_info("sender", &[]),
+            ExecuteMsg::CancelEscrow { id },
+        )
+        .unwrap();
+        assert_eq!(get_balance(deps.as_ref(), "sender"), Uint128::new(1000));
+        assert_eq!(get_balance(deps.as_ref(), "rcpt"), Uint128::zero());
+    }
+
+    #[test]
+    fn query_escrows_works() {
+        let mut deps = mock_dependencies();
+        do_instantiate(deps.as_mut(), "sender", Uint128::new(1000));
+
+        let id1 = create_escrow(deps.as_mut(), "sender", "alice", 100);
+        let id2 = create_escrow(deps.as_mut(), "sender", "bob", 200);
+        let id3 = create_escrow(deps.as_mut(), "sender", "alice", 300);
+
+        let escrows = query_escrows_by_sender(deps.as_ref(), "sender".into(), None, None).unwrap();
+        let ids: Vec<_> = escrows.escrows.iter().map(|e| e.id).collect();
+        assert_eq!(ids, vec![id1, id2, id3]);
+
+        // pagination
+        let escrows =
+            query_escrows_by_sender(deps.as_ref(), "sender".into(), Some(id1), Some(1)).unwrap();
+        assert_eq!(escrows.escrows.len(), 1);
+        assert_eq!(escrows.escrows[0].id, id2);
+
+        let escrows =
+            query_escrows_by_recipient(deps.as_ref(), "alice".into(), None, None).unwrap();
+        let ids: Vec<_> = escrows.escrows.iter().map(|e| e.id).collect();
+        assert_eq!(ids, vec![id1, id3]);
+
+        let escrow = query_escrow(deps.as_ref(), id2).unwrap();
+        assert_eq!(escrow.recipient, "bob");
+        assert_eq!(escrow.amount, Uint128::new(200));
+
+        let escrows =
+            query_escrows_by_recipient(deps.as_ref(), "sender".into(), None, None).unwrap();
+        assert_eq!(escrows, EscrowsResponse::default());
+    }
+}
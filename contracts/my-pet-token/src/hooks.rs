@@ -0,0 +1,376 @@
+use cosmwasm_std::{
+    attr, Addr, Deps, DepsMut, MessageInfo, Order, Response, StdResult, Storage, SubMsg, Uint128,
+};
+use cw_controllers::HookError;
+
+use crate::error::ContractError;
+use crate::msg::{BalanceChangeKind, BalanceChangedHookMsg, HookResponse, HooksResponse};
+use crate::state::{ADMIN, HOOKS};
+
+// bounds the gas spent dispatching hooks on every balance change
+pub const MAX_HOOKS: u32 = 10;
+
+pub fn execute_add_hook(
+    deps: DepsMut,
+    info: MessageInfo,
+    addr: String,
+    kinds: Vec<BalanceChangeKind>,
+) -> Result<Response, ContractError> {
+    ADMIN.assert_admin(deps.as_ref(), &info.sender)?;
+    let hook = deps.api.addr_validate(&addr)?;
+
+    if HOOKS.has(deps.storage, &hook) {
+        return Err(HookError::HookAlreadyRegistered {}.into());
+    }
+    if kinds.is_empty() {
+        return Err(ContractError::NoHookKinds {});
+    }
+    let count = HOOKS
+        .keys(deps.storage, None, None, Order::Ascending)
+        .count();
+    if count >= MAX_HOOKS as usize {
+        return Err(ContractError::TooManyHooks { max: MAX_HOOKS });
+    }
+    HOOKS.save(deps.storage, &hook, &kinds)?;
+
+    let res = Response::new().add_attributes(vec![
+        attr("action", "add_hook"),
+        attr("hook", addr),
+        attr("sender", info.sender),
+    ]);
+    Ok(res)
+}
+
+pub fn execute_remove_hook(
+    deps: DepsMut,
+    info: MessageInfo,
+    addr: String,
+) -> Result<Response, ContractError> {
+    ADMIN.assert_admin(deps.as_ref(), &info.sender)?;
+    let hook = deps.api.addr_validate(&addr)?;
+
+    if !HOOKS.has(deps.storage, &hook) {
+        return Err(HookError::HookNotRegistered {}.into());
+    }
+    HOOKS.remove(deps.storage, &hook);
+
+    let res = Response::new().add_attributes(vec![
+        attr("action", "remove_hook"),
+        attr("hook", addr),
+        attr("sender", info.sender),
+    ]);
+    Ok(res)
+}
+
+/// Builds a `BalanceChangedHookMsg` for every hook subscribed to this kind of change
+pub fn prepare_hooks(
+    storage: &dyn Storage,
+    kind: BalanceChangeKind,
+    from: Option<&Addr>,
+    to: Option<&Addr>,
+    amount: Uint128,
+) -> StdResult<Vec<SubMsg>> {
+    let msg = BalanceChangedHookMsg {
+        from: from.map(Into::into),
+        to: to.map(Into::into),
+        amount,
+    };
+    HOOKS
+        .range(storage, None, None, Order::Ascending)
+        .filter(|item| match item {
+            Ok((_, kinds)) => kinds.contains(&kind),
+            Err(_) => true,
+        })
+        .map(|item| {
+            let (hook, _) = item?;
+            Ok(SubMsg::new(msg.clone().into_cosmos_msg(hook)?))
+        })
+        .collect()
+}
+
+pub fn query_hooks(deps: Deps) -> StdResult<HooksResponse> {
+    let hooks = HOOKS
+        .range(deps.storage, None, None, Order::Ascending)
+        .map(|item| {
+            item.map(|(addr, kinds)| HookResponse {
+                addr: addr.into(),
+                kinds,
+            })
+        })
+        .collect::<StdResult<_>>()?;
+    Ok(HooksResponse { hooks })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use cosmwasm_std::testing::{mock_dependencies, mock_env, mock_info};
+    use cosmwasm_std::Binary;
+    use cw20::{Cw20ReceiveMsg, Expiration};
+
+    use crate::contract::{execute, instantiate};
+    use crate::msg::{ExecuteMsg, InitialBalance, InstantiateMsg};
+
+    const ADMIN_ADDR: &str = "admin";
+    const MINTER: &str = "minter";
+
+    fn do_instantiate(deps: DepsMut) {
+        let instantiate_msg = InstantiateMsg {
+            name: "Auto Gen".to_string(),
+            symbol: "AUTO".to_string(),
+            decimals: 3,
+            initial_balances: vec![InitialBalance {
+                address: "owner".into(),
+                amount: Uint128::new(1000),
+                vesting: None,
+            }],
+            mint: MINTER.to_string(),
+            admin: Some(ADMIN_ADDR.to_string()),
+            marketing: None,
+        };
+        let info = mock_info("creator", &[]);
+        instantiate(deps, mock_env(), info, instantiate_msg).unwrap();
+    }
+
+    fn add_hook(deps: DepsMut, addr: &str, kinds: Vec<BalanceChangeKind>) {
+        let msg = ExecuteMsg::AddHook {
+            addr: addr.into(),
+            kinds,
+        };
+        execute(deps, mock_env(), mock_info(ADMIN_ADDR, &[]), msg).unwrap();
+    }
+
+    #[test]
+    fn only_admin_manages_hooks() {
+        let mut deps = mock_dependencies();
+        do_instantiate(deps.as_mut());
+
+        let msg = ExecuteMsg::AddHook {
+            addr: "quests".into(),
+            kinds: vec![BalanceChangeKind::Transfer],
+        };
+        let err = execute(deps.as_mut(), mock_env(), mock_info("owner", &[]), msg).unwrap_err();
+        assert!(matches!(err, ContractError::Admin(_)));
+
+        add_hook(deps.as_mut(), "quests", vec![BalanceChangeKind::Transfer]);
+        assert_eq!(
+            query_hooks(deps.as_ref()).unwrap(),
+            HooksResponse {
+                hooks: vec![HookResponse {
+                    addr: "quests".into(),
+                    kinds: vec![BalanceChangeKind::Transfer],
+                }]
+            }
+        );
+
+        // cannot register twice or without any kinds
+        let msg = ExecuteMsg::AddHook {
+            addr: "quests".into(),
+            kinds: vec![BalanceChangeKind::Mint],
+        };
+        let err = execute(deps.as_mut(), mock_env(), mock_info(ADMIN_ADDR, &[]), msg).unwrap_err();
+        assert_eq!(err, HookError::HookAlreadyRegistered {}.into());
+        let msg = ExecuteMsg::AddHook {
+            addr: "leaderboard".into(),
+            kinds: vec![],
+        };
+        let err = execute(deps.as_mut(), mock_env(), mock_info(ADMIN_ADDR, &[]), msg).unwrap_err();
+        assert_eq!(err, ContractError::NoHookKinds {});
+
+        let msg = ExecuteMsg::RemoveHook {
+            addr: "quests".into(),
+        };
+        execute(
+            deps.as_mut(),
+            mock_env(),
+            mock_info(ADMIN_ADDR, &[]),
+            msg.clone(),
+        )
+        .unwrap();
+        assert_eq!(
+            query_hooks(deps.as_ref()).unwrap(),
+            HooksResponse::default()
+        );
+        let err = execute(deps.as_mut(), mock_env(), mock_info(ADMIN_ADDR, &[]), msg).unwrap_err();
+        assert_eq!(err, HookError::HookNotRegistered {}.into());
+    }
+
+    #[test]
+    fn hook_count_is_bounded() {
+        let mut deps = mock_dependencies();
+        do_instantiate(deps.as_mut());
+
+        for i in 0..MAX_HOOKS {
+            add_hook(
+                deps.as_mut(),
+                &format!("hook{}", i),
+                vec![BalanceChangeKind::Mint],
+            );
+        }
+        let msg = ExecuteMsg::AddHook {
+            addr: "one_too_many".into(),
+            kinds: vec![BalanceChangeKind::Mint],
+        };
+        let err = execute(deps.as_mut(), mock_env(), mock_info(ADMIN_ADDR, &[]), msg).unwrap_err();
+        assert_eq!(err, ContractError::TooManyHooks { max: MAX_HOOKS });
+    }
+
+    #[test]
+    fn hooks_receive_subscribed_changes() {
+        let mut deps = mock_dependencies();
+        do_instantiate(deps.as_mut());
+        add_hook(deps.as_mut(), "quests", vec![BalanceChangeKind::Transfer]);
+        add_hook(
+            deps.as_mut(),
+            "leaderboard",
+            vec![BalanceChangeKind::Mint, BalanceChangeKind::Transfer],
+        );
+
+        let msg = ExecuteMsg::Transfer {
+            recipient: "friend".into(),
+            amount: Uint128::new(100),
+            memo: None,
+        };
+        let res = execute(deps.as_mut(), mock_env(), mock_info("owner", &[]), msg).unwrap();
+        let hook_msg = BalanceChangedHookMsg {
+            from: Some("owner".into()),
+            to: Some("friend".into()),
+            amount: Uint128::new(100),
+        };
+        assert_eq!(
+            res.messages,
+            vec![
+                SubMsg::new(hook_msg.clone().into_cosmos_msg("leaderboard").unwrap()),
+                SubMsg::new(hook_msg.into_cosmos_msg("quests").unwrap()),
+            ]
+        );
+
+        // only the leaderboard is interested in mints
+        let msg = ExecuteMsg::Mint {
+            recipient: "friend".into(),
+            amount: Uint128::new(5),
+        };
+        let res = execute(deps.as_mut(), mock_env(), mock_info(MINTER, &[]), msg).unwrap();
+        let hook_msg = BalanceChangedHookMsg {
+            from: None,
+            to: Some("friend".into()),
+            amount: Uint128::new(5),
+        };
+        assert_eq!(
+            res.messages,
+            vec![SubMsg::new(
+                hook_msg.into_cosmos_msg("leaderboard").unwrap()
+            )]
+        );
+    }
+
+    #[test]
+    fn escrows_and_streams_dispatch_hooks() {
+        let mut deps = mock_dependencies();
+        do_instantiate(deps.as_mut());
+        add_hook(deps.as_mut(), "quests", vec![BalanceChangeKind::Transfer]);
+        let contract = mock_env().contract.address;
+
+        let msg = ExecuteMsg::CreateEscrow {
+            recipient: "friend".into(),
+            amount: Uint128::new(100),
+            release_at: Expiration::AtTime(mock_env().block.time.plus_days(1)),
+        };
+        let res = execute(deps.as_mut(), mock_env(), mock_info("owner", &[]), msg).unwrap();
+        let hook_msg = BalanceChangedHookMsg {
+            from: Some("owner".into()),
+            to: Some(contract.to_string()),
+            amount: Uint128::new(100),
+        };
+        assert_eq!(
+            res.messages,
+            vec![SubMsg::new(hook_msg.into_cosmos_msg("quests").unwrap())]
+        );
+
+        let mut env = mock_env();
+        env.block.time = env.block.time.plus_days(1);
+        let msg = ExecuteMsg::ClaimEscrow { id: 1 };
+        let res = execute(deps.as_mut(), env, mock_info("friend", &[]), msg).unwrap();
+        let hook_msg = BalanceChangedHookMsg {
+            from: Some(contract.to_string()),
+            to: Some("friend".into()),
+            amount: Uint128::new(100),
+        };
+        assert_eq!(
+            res.messages,
+            vec![SubMsg::new(hook_msg.into_cosmos_msg("quests").unwrap())]
+        );
+
+        // cancelling a stream halfway pays both sides
+        let env = mock_env();
+        let msg = ExecuteMsg::CreateStream {
+            recipient: "friend".into(),
+            amount: Uint128::new(100),
+            start_time: env.block.time,
+            end_time: env.block.time.plus_seconds(100),
+        };
+        execute(deps.as_mut(), env.clone(), mock_info("owner", &[]), msg).unwrap();
+        let mut env = mock_env();
+        env.block.time = env.block.time.plus_seconds(40);
+        let msg = ExecuteMsg::CancelStream { id: 1 };
+        let res = execute(deps.as_mut(), env, mock_info("owner", &[]), msg).unwrap();
+        let paid = |to: &str, amount: u128| {
+            let hook_msg = BalanceChangedHookMsg {
+                from: Some(contract.to_string()),
+                to: Some(to.into()),
+                amount: Uint128::new(amount),
+            };
+            SubMsg::new(hook_msg.into_cosmos_msg("quests").unwrap())
+        };
+        assert_eq!(res.messages, vec![paid("friend", 40), paid("owner", 60)]);
+    }
+
+    #[test]
+    fn hooks_precede_receive_on_both_send_paths() {
+        let mut deps = mock_dependencies();
+        do_instantiate(deps.as_mut());
+        add_hook(deps.as_mut(), "quests", vec![BalanceChangeKind::Transfer]);
+        let hook = |amount: u128| {
+            let hook_msg = BalanceChangedHookMsg {
+                from: Some("owner".into()),
+                to: Some("shop".into()),
+                amount: Uint128::new(amount),
+            };
+            SubMsg::new(hook_msg.into_cosmos_msg("quests").unwrap())
+        };
+        let receive = |sender: &str, amount: u128| {
+            let msg = Cw20ReceiveMsg {
+                sender: sender.into(),
+                amount: Uint128::new(amount),
+                msg: Binary::default(),
+            };
+            SubMsg::new(msg.into_cosmos_msg("shop").unwrap())
+        };
+
+        let msg = ExecuteMsg::Send {
+            contract: "shop".into(),
+            amount: Uint128::new(10),
+            msg: Binary::default(),
+            memo: None,
+        };
+        let res = execute(deps.as_mut(), mock_env(), mock_info("owner", &[]), msg).unwrap();
+        assert_eq!(res.messages, vec![hook(10), receive("owner", 10)]);
+
+        let msg = ExecuteMsg::IncreaseAllowance {
+            spender: "cashier".into(),
+            amount: Uint128::new(20),
+            expires: None,
+        };
+        execute(deps.as_mut(), mock_env(), mock_info("owner", &[]), msg).unwrap();
+        let msg = ExecuteMsg::SendFrom {
+            owner: "owner".into(),
+            contract: "shop".into(),
+            amount: Uint128::new(20),
+            msg: Binary::default(),
+            memo: None,
+        };
+        let res = execute(deps.as_mut(), mock_env(), mock_info("cashier", &[]), msg).unwrap();
+        assert_eq!(res.messages, vec![hook(20), receive("cashier", 20)]);
+    }
+}
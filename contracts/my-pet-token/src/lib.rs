@@ -2,7 +2,13 @@ pub mod allowances;
 pub mod contract;
 pub mod enumerable;
 mod error;
+pub mod escrow;
+pub mod hooks;
+pub mod mint_history;
 pub mod msg;
+pub mod recovery;
 pub mod state;
+pub mod stream;
+pub mod vesting;
 
 pub use crate::error::ContractError;
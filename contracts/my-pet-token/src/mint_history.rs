@@ -0,0 +1,189 @@
+use cosmwasm_std::{Addr, Deps, Order, StdResult, Storage, Timestamp, Uint128};
+use cw_storage_plus::Bound;
+
+use crate::msg::{
+    MintHistoryResponse, MintRecordResponse, MintWindowResponse, MintWindowsResponse,
+};
+use crate::state::{mint_history, MintRecord, MINT_COUNT, MINT_WINDOW_TOTALS};
+
+// settings for pagination
+const MAX_LIMIT: u32 = 30;
+const DEFAULT_LIMIT: u32 = 10;
+
+/// Appends a mint to the history and adds it to the total of its window
+pub fn record_mint(
+    storage: &mut dyn Storage,
+    minter: &Addr,
+    recipient: &Addr,
+    amount: Uint128,
+    time: Timestamp,
+    window: u64,
+) -> StdResult<u64> {
+    let id = MINT_COUNT.may_load(storage)?.unwrap_or_default() + 1;
+    MINT_COUNT.save(storage, &id)?;
+    let record = MintRecord {
+        minter: minter.clone(),
+        recipient: recipient.clone(),
+        amount,
+        time,
+        window,
+    };
+    mint_history().save(storage, id, &record)?;
+    MINT_WINDOW_TOTALS.update(storage, window, |total| -> StdResult<_> {
+        Ok(total.unwrap_or_default().checked_add(amount)?)
+    })?;
+    Ok(id)
+}
+
+fn to_mint_record_response(id: u64, record: MintRecord) -> MintRecordResponse {
+    MintRecordResponse {
+        id,
+        minter: record.minter.into(),
+        recipient: record.recipient.into(),
+        amount: record.amount,
+        time: record.time,
+        window: record.window,
+    }
+}
+
+pub fn query_mint_history(
+    deps: Deps,
+    start_after: Option<u64>,
+    limit: Option<u32>,
+    recipient: Option<String>,
+) -> StdResult<MintHistoryResponse> {
+    let limit = limit.unwrap_or(DEFAULT_LIMIT).min(MAX_LIMIT) as usize;
+    let start = start_after.map(Bound::exclusive);
+
+    let records = match recipient {
+        Some(recipient) => {
+            let rcpt_addr = deps.api.addr_validate(&recipient)?;
+            mint_history().idx.recipient.prefix(rcpt_addr).range(
+                deps.storage,
+                start,
+                None,
+                Order::Ascending,
+            )
+        }
+        None => mint_history().range(deps.storage, start, None, Order::Ascending),
+    };
+    let mints = records
+        .take(limit)
+        .map(|item| item.map(|(id, record)| to_mint_record_response(id, record)))
+        .collect::<StdResult<_>>()?;
+    Ok(MintHistoryResponse { mints })
+}
+
+pub fn query_mint_windows(
+    deps: Deps,
+    start_after: Option<u64>,
+    limit: Option<u32>,
+) -> StdResult<MintWindowsResponse> {
+    let limit = limit.unwrap_or(DEFAULT_LIMIT).min(MAX_LIMIT) as usize;
+    let start = start_after.map(Bound::exclusive);
+
+    let windows = MINT_WINDOW_TOTALS
+        .range(deps.storage, start, None, Order::Ascending)
+        .take(limit)
+        .map(|item| item.map(|(window, total)| MintWindowResponse { window, total }))
+        .collect::<StdResult<_>>()?;
+    Ok(MintWindowsResponse { windows })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use cosmwasm_std::testing::{mock_dependencies, mock_env, mock_info};
+    use cosmwasm_std::{DepsMut, Env};
+
+    use crate::contract::{execute, instantiate};
+    use crate::msg::{ExecuteMsg, InitialBalance, InstantiateMsg};
+
+    const MINTER: &str = "staking";
+
+    fn do_instantiate(deps: DepsMut) {
+        let instantiate_msg = InstantiateMsg {
+            name: "Auto Gen".to_string(),
+            symbol: "AUTO".to_string(),
+            decimals: 3,
+            initial_balances: vec![InitialBalance {
+                address: "owner".into(),
+                amount: Uint128::new(1000),
+                vesting: None,
+            }],
+            mint: MINTER.to_string(),
+            admin: None,
+            marketing: None,
+        };
+        let info = mock_info("creator", &[]);
+        instantiate(deps, mock_env(), info, instantiate_msg).unwrap();
+    }
+
+    fn mint(deps: DepsMut, env: &Env, recipient: &str, amount: u128) {
+        let msg = ExecuteMsg::Mint {
+            recipient: recipient.into(),
+            amount: Uint128::new(amount),
+        };
+        execute(deps, env.clone(), mock_info(MINTER, &[]), msg).unwrap();
+    }
+
+    #[test]
+    fn mints_are_recorded() {
+        let mut deps = mock_dependencies();
+        do_instantiate(deps.as_mut());
+
+        let mut env = mock_env();
+        let first_window = env.block.time;
+        mint(deps.as_mut(), &env, "alice", 100);
+        env.block.time = env.block.time.plus_hours(1);
+        mint(deps.as_mut(), &env, "bob", 200);
+        // a new budget window starts after a day
+        env.block.time = env.block.time.plus_days(1);
+        let second_window = env.block.time;
+        mint(deps.as_mut(), &env, "alice", 300);
+
+        let history = query_mint_history(deps.as_ref(), None, None, None).unwrap();
+        assert_eq!(history.mints.len(), 3);
+        assert_eq!(
+            history.mints[1],
+            MintRecordResponse {
+                id: 2,
+                minter: MINTER.into(),
+                recipient: "bob".into(),
+                amount: Uint128::new(200),
+                time: first_window.plus_hours(1),
+                window: first_window.seconds(),
+            }
+        );
+
+        // filter by recipient and paginate
+        let history =
+            query_mint_history(deps.as_ref(), None, Some(1), Some("alice".into())).unwrap();
+        let ids: Vec<_> = history.mints.iter().map(|m| m.id).collect();
+        assert_eq!(ids, vec![1]);
+        let history =
+            query_mint_history(deps.as_ref(), Some(1), None, Some("alice".into())).unwrap();
+        let ids: Vec<_> = history.mints.iter().map(|m| m.id).collect();
+        assert_eq!(ids, vec![3]);
+        assert_eq!(history.mints[0].window, second_window.seconds());
+
+        let windows = query_mint_windows(deps.as_ref(), None, None).unwrap();
+        assert_eq!(
+            windows.windows,
+            vec![
+                MintWindowResponse {
+                    window: first_window.seconds(),
+                    total: Uint128::new(300),
+                },
+                MintWindowResponse {
+                    window: second_window.seconds(),
+                    total: Uint128::new(300),
+                },
+            ]
+        );
+        let windows =
+            query_mint_windows(deps.as_ref(), Some(first_window.seconds()), None).unwrap();
+        assert_eq!(windows.windows.len(), 1);
+    }
+}
@@ -1,16 +1,33 @@
 use cosmwasm_schema::{cw_serde, QueryResponses};
-use cosmwasm_std::{StdError, StdResult, Uint128};
-use cw20::Cw20Coin;
+use cosmwasm_std::{
+    to_json_binary, Binary, CosmosMsg, StdError, StdResult, Timestamp, Uint128, WasmMsg,
+};
+use cw20::{AllowanceInfo, Logo, SpenderAllowanceInfo};
 use cw_utils::Expiration;
 use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
 
-use crate::state::PetStakingData;
+use crate::state::{PetStakingData, VestingSchedule};
 
 #[cw_serde]
 pub enum ExecuteMsg {
     /// Transfer is a base message to move tokens to another account without triggering actions
-    Transfer { recipient: String, amount: Uint128 },
+    Transfer {
+        recipient: String,
+        amount: Uint128,
+        /// Optional note attached to the transfer, e.g. to tag exchange deposits
+        memo: Option<String>,
+    },
+    /// Burn is a base message to destroy tokens forever
+    Burn { amount: Uint128 },
+    /// Send is a base message to transfer tokens to a contract and trigger an action
+    /// on the receiving contract.
+    Send {
+        contract: String,
+        amount: Uint128,
+        msg: Binary,
+        memo: Option<String>,
+    },
     /// Only with "approval" extension. Allows spender to access an additional amount tokens
     /// from the owner's (env.sender) account. If expires is Some(), overwrites current allowance
     /// expiration with this one.
@@ -27,12 +44,49 @@ pub enum ExecuteMsg {
         amount: Uint128,
         expires: Option<Expiration>,
     },
+    /// Only with "approval" extension. Increases the allowance like `IncreaseAllowance` and
+    /// calls the spender contract with `ReceiveAllowance` in the same transaction, so it can
+    /// pull the tokens with `TransferFrom` right away.
+    IncreaseAllowanceAndCall {
+        spender: String,
+        amount: Uint128,
+        expires: Option<Expiration>,
+        msg: Binary,
+    },
+    /// Only with "approval" extension. Allows spender to access up to cap tokens from the
+    /// owner's (env.sender) account in every period of the given length in seconds. Unused
+    /// tokens do not carry over to the next period. Replaces the current allowance.
+    SetRecurringAllowance {
+        spender: String,
+        cap: Uint128,
+        period: u64,
+        expires: Option<Expiration>,
+    },
+    /// Only with "approval" extension. Checks up to limit allowances granted by owner after
+    /// the spender `start_after` and removes the expired ones. Unless the end was reached, the
+    /// last spender checked is returned in the `start_after` attribute to continue from.
+    /// Anyone can do this.
+    PruneExpiredAllowances {
+        owner: String,
+        start_after: Option<String>,
+        limit: Option<u32>,
+    },
     /// Only with "approval" extension. Transfers amount tokens from owner -> recipient
     /// if `env.sender` has sufficient pre-approval.
     TransferFrom {
         owner: String,
         recipient: String,
         amount: Uint128,
+        memo: Option<String>,
+    },
+    /// Only with "approval" extension. Sends amount tokens from owner -> contract
+    /// if `env.sender` has sufficient pre-approval.
+    SendFrom {
+        owner: String,
+        contract: String,
+        amount: Uint128,
+        msg: Binary,
+        memo: Option<String>,
     },
     /// Only with the "mintable" extension. If authorized, creates amount new tokens
     /// and adds to the recipient balance.
@@ -41,6 +95,149 @@ pub enum ExecuteMsg {
     /// a new minter. Setting the minter to None will remove the
     /// token's minter forever.
     UpdateMinter { new_minter: String },
+    /// Only with the "vesting" extension. Unlocks all tokens of the sender's
+    /// vesting schedule which have vested so far.
+    ReleaseVested {},
+    /// Only with the "escrow" extension. Locks amount tokens from the sender's account,
+    /// claimable by recipient once release_at has passed.
+    CreateEscrow {
+        recipient: String,
+        amount: Uint128,
+        release_at: Expiration,
+    },
+    /// Only with the "escrow" extension. Recipient receives the escrowed tokens
+    /// after the release time.
+    ClaimEscrow { id: u64 },
+    /// Only with the "escrow" extension. Sender gets the escrowed tokens back
+    /// before the release time.
+    CancelEscrow { id: u64 },
+    /// Only with the "streaming" extension. Locks amount tokens from the sender's account
+    /// which stream to recipient linearly between start_time and end_time.
+    CreateStream {
+        recipient: String,
+        amount: Uint128,
+        start_time: Timestamp,
+        end_time: Timestamp,
+    },
+    /// Only with the "streaming" extension. Recipient receives everything streamed so far.
+    WithdrawStream { id: u64 },
+    /// Only with the "streaming" extension. Either party can stop the stream. The recipient
+    /// receives what has streamed so far and the sender gets the remainder back.
+    CancelStream { id: u64 },
+    /// Change the admin. Only the current admin can do this.
+    UpdateAdmin { admin: Option<String> },
+    /// Add a new hook to be informed of balance changes of the given kinds.
+    /// Only the admin can do this.
+    AddHook {
+        addr: String,
+        kinds: Vec<BalanceChangeKind>,
+    },
+    /// Remove a hook. Only the admin can do this.
+    RemoveHook { addr: String },
+    /// Only with the "marketing" extension. If authorized, updates marketing metadata.
+    /// Setting None/null for any of these will leave it unchanged.
+    /// Setting Some("") will clear this field on the contract storage
+    UpdateMarketing {
+        /// A URL pointing to the project behind this token.
+        project: Option<String>,
+        /// A longer description of the token and it's utility. Designed for tooltips or such
+        description: Option<String>,
+        /// The address (if any) who can update this data structure
+        marketing: Option<String>,
+    },
+    /// If set as the "marketing" role on the contract, upload a new URL, SVG, or PNG for the token
+    UploadLogo(Logo),
+    /// Moves tokens sent by mistake to this contract (`from_contract_balance`) or to the
+    /// minter to the recipient. Tokens backing escrows and streams, or the minter's stakes,
+    /// claims and reward pool, cannot be recovered. Only the admin can do this.
+    RecoverTokens {
+        from_contract_balance: bool,
+        recipient: String,
+        amount: Uint128,
+    },
+}
+
+#[cw_serde]
+pub enum BalanceChangeKind {
+    Transfer,
+    Mint,
+    Burn,
+}
+
+/// BalanceChangedHookMsg should be de/serialized under `BalanceChangedHook()` variant in a ExecuteMsg.
+/// `from` is None for mints and `to` is None for burns.
+#[cw_serde]
+pub struct BalanceChangedHookMsg {
+    pub from: Option<String>,
+    pub to: Option<String>,
+    pub amount: Uint128,
+}
+
+impl BalanceChangedHookMsg {
+    /// serializes the message
+    pub fn into_binary(self) -> StdResult<Binary> {
+        let msg = BalanceChangedExecuteMsg::BalanceChangedHook(self);
+        to_json_binary(&msg)
+    }
+
+    /// creates a cosmos_msg sending this struct to the named contract
+    pub fn into_cosmos_msg<T: Into<String>>(self, contract_addr: T) -> StdResult<CosmosMsg> {
+        let msg = self.into_binary()?;
+        let execute = WasmMsg::Execute {
+            contract_addr: contract_addr.into(),
+            msg,
+            funds: vec![],
+        };
+        Ok(execute.into())
+    }
+}
+
+// This is just a helper to properly serialize the above message
+#[cw_serde]
+enum BalanceChangedExecuteMsg {
+    BalanceChangedHook(BalanceChangedHookMsg),
+}
+
+/// AllowanceReceiveMsg should be de/serialized under `ReceiveAllowance()` variant in a ExecuteMsg.
+/// `amount` is the allowance increase granted by `owner` to the receiving contract.
+#[cw_serde]
+pub struct AllowanceReceiveMsg {
+    pub owner: String,
+    pub amount: Uint128,
+    pub msg: Binary,
+}
+
+impl AllowanceReceiveMsg {
+    /// serializes the message
+    pub fn into_binary(self) -> StdResult<Binary> {
+        let msg = AllowanceReceiverExecuteMsg::ReceiveAllowance(self);
+        to_json_binary(&msg)
+    }
+
+    /// creates a cosmos_msg sending this struct to the named contract
+    pub fn into_cosmos_msg<T: Into<String>>(self, contract_addr: T) -> StdResult<CosmosMsg> {
+        let msg = self.into_binary()?;
+        let execute = WasmMsg::Execute {
+            contract_addr: contract_addr.into(),
+            msg,
+            funds: vec![],
+        };
+        Ok(execute.into())
+    }
+}
+
+// This is just a helper to properly serialize the above message
+#[cw_serde]
+enum AllowanceReceiverExecuteMsg {
+    ReceiveAllowance(AllowanceReceiveMsg),
+}
+
+#[cw_serde]
+pub struct InitialBalance {
+    pub address: String,
+    pub amount: Uint128,
+    /// If set, the amount is locked and vests according to this schedule
+    pub vesting: Option<VestingSchedule>,
 }
 
 #[cw_serde]
@@ -49,8 +246,18 @@ pub struct InstantiateMsg {
     pub name: String,
     pub symbol: String,
     pub decimals: u8,
-    pub initial_balances: Vec<Cw20Coin>,
+    pub initial_balances: Vec<InitialBalance>,
     pub mint: String,
+    pub admin: Option<String>,
+    pub marketing: Option<InstantiateMarketingInfo>,
+}
+
+#[cw_serde]
+pub struct InstantiateMarketingInfo {
+    pub project: Option<String>,
+    pub description: Option<String>,
+    pub marketing: Option<String>,
+    pub logo: Option<Logo>,
 }
 
 impl InstantiateMsg {
@@ -69,6 +276,15 @@ impl InstantiateMsg {
         if self.decimals > 18 {
             return Err(StdError::generic_err("Decimals must not exceed 18"));
         }
+        for balance in self.initial_balances.iter() {
+            if let Some(schedule) = &balance.vesting {
+                if !schedule.is_valid() {
+                    return Err(StdError::generic_err(
+                        "Vesting schedule must satisfy start <= cliff <= end and start < end",
+                    ));
+                }
+            }
+        }
         Ok(())
     }
 
@@ -111,13 +327,32 @@ pub enum QueryMsg {
     /// Returns how much spender can use from owner account, 0 if unset.
     #[returns(cw20::AllowanceResponse)]
     Allowance { owner: String, spender: String },
+    /// Only with "allowance" extension.
+    /// Returns how much spender can use from owner account, accounting for refills of
+    /// recurring allowances, and when it is refilled next.
+    #[returns(RecurringAllowanceResponse)]
+    RecurringAllowance { owner: String, spender: String },
     /// Only with "enumerable" extension (and "allowances")
     /// Returns all allowances this owner has approved. Supports pagination.
-    #[returns(cw20::AllAllowancesResponse)]
+    /// Expired allowances are left out if `hide_expired` is set, a page may then come back
+    /// short or empty with `start_after` set to continue from.
+    #[returns(OwnerAllowancesResponse)]
     AllAllowances {
         owner: String,
         start_after: Option<String>,
         limit: Option<u32>,
+        hide_expired: Option<bool>,
+    },
+    /// Only with "enumerable" extension (and "allowances")
+    /// Returns all allowances this spender has been granted. Supports pagination.
+    /// Expired allowances are left out if `hide_expired` is set, a page may then come back
+    /// short or empty with `start_after` set to continue from.
+    #[returns(SpenderAllowancesResponse)]
+    AllSpenderAllowances {
+        spender: String,
+        start_after: Option<String>,
+        limit: Option<u32>,
+        hide_expired: Option<bool>,
     },
     /// Only with "enumerable" extension
     /// Returns all accounts that have balances. Supports pagination.
@@ -126,8 +361,264 @@ pub enum QueryMsg {
         start_after: Option<String>,
         limit: Option<u32>,
     },
+    /// Only with "enumerable" extension
+    /// Returns all accounts along with their balances. Supports pagination.
+    /// Accounts which emptied their balance are left out if `skip_zero` is set.
+    #[returns(AllBalancesResponse)]
+    AllBalances {
+        start_after: Option<String>,
+        limit: Option<u32>,
+        skip_zero: Option<bool>,
+    },
+    /// Only with "enumerable" extension
+    /// Sums balances page by page and compares the result with the total supply.
+    /// Pass the `running_sum` and `last_account` of the previous page to continue.
+    #[returns(VerifySupplyResponse)]
+    VerifySupply {
+        start_after: Option<String>,
+        limit: Option<u32>,
+        running_sum: Option<Uint128>,
+    },
+    /// Only with "enumerable" extension
+    /// Returns the number of accounts holding a non-zero balance.
+    #[returns(HolderCountResponse)]
+    HolderCount {},
+    /// Only with "enumerable" extension
+    /// Returns the accounts with the largest balances, largest first. Supports pagination:
+    /// pass the last holder of the previous page as `start_after`, balance included, so
+    /// balances changing between pages neither skip nor repeat holders.
+    #[returns(TopHoldersResponse)]
+    TopHolders {
+        limit: Option<u32>,
+        start_after: Option<HolderInfo>,
+    },
     #[returns(PetStakingData)]
     PetStaking {},
+    /// Only with "vesting" extension.
+    /// Returns the vesting schedule of the given address and how much of it is locked.
+    #[returns(VestingResponse)]
+    Vesting { address: String },
+    /// Only with "escrow" extension.
+    /// Returns the escrow with the given id.
+    #[returns(EscrowResponse)]
+    Escrow { id: u64 },
+    /// Only with "escrow" extension.
+    /// Returns all escrows created by this sender. Supports pagination.
+    #[returns(EscrowsResponse)]
+    EscrowsBySender {
+        sender: String,
+        start_after: Option<u64>,
+        limit: Option<u32>,
+    },
+    /// Only with "escrow" extension.
+    /// Returns all escrows claimable by this recipient. Supports pagination.
+    #[returns(EscrowsResponse)]
+    EscrowsByRecipient {
+        recipient: String,
+        start_after: Option<u64>,
+        limit: Option<u32>,
+    },
+    /// Only with "streaming" extension.
+    /// Returns the stream with the given id.
+    #[returns(StreamResponse)]
+    Stream { id: u64 },
+    /// Only with "streaming" extension.
+    /// Returns the streams this address sends and receives. Supports pagination.
+    #[returns(StreamsByResponse)]
+    StreamsBy {
+        address: String,
+        start_after: Option<u64>,
+        limit: Option<u32>,
+    },
+    /// Returns past mints, optionally only those to the given recipient. Supports pagination.
+    #[returns(MintHistoryResponse)]
+    MintHistory {
+        start_after: Option<u64>,
+        limit: Option<u32>,
+        recipient: Option<String>,
+    },
+    /// Returns the total minted in each daily minting window. Supports pagination.
+    #[returns(MintWindowsResponse)]
+    MintWindows {
+        start_after: Option<u64>,
+        limit: Option<u32>,
+    },
+    /// Returns the current admin, if any.
+    #[returns(cw_controllers::AdminResponse)]
+    Admin {},
+    /// Returns all registered hooks and the balance changes they receive.
+    #[returns(HooksResponse)]
+    Hooks {},
+    /// Only with "marketing" extension
+    /// Returns more metadata on the contract to display in the client:
+    /// - description, logo, project url, etc.
+    #[returns(cw20::MarketingInfoResponse)]
+    MarketingInfo {},
+    /// Only with "marketing" extension
+    /// Downloads the embedded logo data (if stored on chain). Errors if no logo data is stored for this
+    /// contract.
+    #[returns(cw20::DownloadLogoResponse)]
+    DownloadLogo {},
+}
+
+#[cw_serde]
+pub struct AllowanceRefillResponse {
+    pub cap: Uint128,
+    /// Length of a period in seconds
+    pub period: u64,
+    pub next_reset: Timestamp,
+}
+
+#[cw_serde]
+pub struct RecurringAllowanceResponse {
+    /// Allowance left in the current period
+    pub remaining: Uint128,
+    pub expires: Expiration,
+    /// Unset for allowances which do not refill
+    pub refill: Option<AllowanceRefillResponse>,
+}
+
+#[cw_serde]
+pub struct VerifySupplyResponse {
+    pub total_supply: Uint128,
+    /// Sum of all balances up to and including `last_account`
+    pub running_sum: Uint128,
+    pub last_account: Option<String>,
+    /// Set once every balance has been summed
+    pub complete: bool,
+    /// Set if the balances are known not to add up to the total supply
+    pub mismatch: bool,
+}
+
+#[cw_serde]
+pub struct HolderCountResponse {
+    pub count: u64,
+}
+
+#[cw_serde]
+pub struct HolderInfo {
+    pub address: String,
+    pub balance: Uint128,
+}
+
+/// `cw20::AllAllowancesResponse`, which it matches unless `hide_expired` is set
+#[cw_serde]
+#[derive(Default)]
+pub struct OwnerAllowancesResponse {
+    pub allowances: Vec<AllowanceInfo>,
+    /// Last spender checked while hiding expired allowances, unless the end was reached
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub start_after: Option<String>,
+}
+
+/// `cw20::AllSpenderAllowancesResponse`, which it matches unless `hide_expired` is set
+#[cw_serde]
+#[derive(Default)]
+pub struct SpenderAllowancesResponse {
+    pub allowances: Vec<SpenderAllowanceInfo>,
+    /// Last owner checked while hiding expired allowances, unless the end was reached
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub start_after: Option<String>,
+}
+
+#[cw_serde]
+#[derive(Default)]
+pub struct AllBalancesResponse {
+    pub balances: Vec<HolderInfo>,
+}
+
+#[cw_serde]
+#[derive(Default)]
+pub struct TopHoldersResponse {
+    pub holders: Vec<HolderInfo>,
+}
+
+#[cw_serde]
+pub struct VestingResponse {
+    pub schedule: Option<VestingSchedule>,
+    pub total: Uint128,
+    pub released: Uint128,
+    /// Vested tokens which can be unlocked with `ReleaseVested`
+    pub releasable: Uint128,
+    /// Tokens which cannot be transferred yet
+    pub locked: Uint128,
+}
+
+#[cw_serde]
+pub struct EscrowResponse {
+    pub id: u64,
+    pub sender: String,
+    pub recipient: String,
+    pub amount: Uint128,
+    pub release_at: Expiration,
+}
+
+#[cw_serde]
+#[derive(Default)]
+pub struct EscrowsResponse {
+    pub escrows: Vec<EscrowResponse>,
+}
+
+#[cw_serde]
+pub struct StreamResponse {
+    pub id: u64,
+    pub sender: String,
+    pub recipient: String,
+    pub deposit: Uint128,
+    pub withdrawn: Uint128,
+    /// Streamed tokens the recipient can withdraw now
+    pub withdrawable: Uint128,
+    pub start_time: Timestamp,
+    pub end_time: Timestamp,
+}
+
+#[cw_serde]
+#[derive(Default)]
+pub struct StreamsByResponse {
+    pub sending: Vec<StreamResponse>,
+    pub receiving: Vec<StreamResponse>,
+}
+
+#[cw_serde]
+pub struct MintRecordResponse {
+    pub id: u64,
+    pub minter: String,
+    pub recipient: String,
+    pub amount: Uint128,
+    pub time: Timestamp,
+    /// Start time (in seconds) of the daily minting window
+    pub window: u64,
+}
+
+#[cw_serde]
+#[derive(Default)]
+pub struct MintHistoryResponse {
+    pub mints: Vec<MintRecordResponse>,
+}
+
+#[cw_serde]
+pub struct MintWindowResponse {
+    /// Start time (in seconds) of the daily minting window
+    pub window: u64,
+    pub total: Uint128,
+}
+
+#[cw_serde]
+#[derive(Default)]
+pub struct MintWindowsResponse {
+    pub windows: Vec<MintWindowResponse>,
+}
+
+#[cw_serde]
+pub struct HookResponse {
+    pub addr: String,
+    pub kinds: Vec<BalanceChangeKind>,
+}
+
+#[cw_serde]
+#[derive(Default)]
+pub struct HooksResponse {
+    pub hooks: Vec<HookResponse>,
 }
 
 #[derive(Serialize, Deserialize, JsonSchema)]
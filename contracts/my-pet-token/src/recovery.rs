@@ -0,0 +1,272 @@
+use cosmwasm_schema::cw_serde;
+use cosmwasm_std::{attr, DepsMut, Env, MessageInfo, Response, StdResult, Storage, Uint128};
+
+use crate::error::ContractError;
+use crate::hooks::prepare_hooks;
+use crate::msg::BalanceChangeKind;
+use crate::state::{ADMIN, BALANCES, HELD, TOKEN_INFO};
+
+// mirrors the pet-staking query for the tokens backing its stakes, claims and reward pool
+#[cw_serde]
+enum PetStakingQueryMsg {
+    Held {},
+}
+
+#[cw_serde]
+struct HeldResponse {
+    amount: Uint128,
+}
+
+/// Moves tokens sent by mistake to either the token contract itself or the minter
+/// (pet-staking) to the recipient. Only the surplus above what backs open escrows and
+/// streams, or what the minter holds for stakes, claims and its reward pool respectively,
+/// can be recovered.
+pub fn execute_recover_tokens(
+    deps: DepsMut,
+    env: Env,
+    info: MessageInfo,
+    from_contract_balance: bool,
+    recipient: String,
+    amount: Uint128,
+) -> Result<Response, ContractError> {
+    ADMIN.assert_admin(deps.as_ref(), &info.sender)?;
+    let rcpt_addr = deps.api.addr_validate(&recipient)?;
+
+    let (source, backing) = if from_contract_balance {
+        (
+            env.contract.address,
+            HELD.may_load(deps.storage)?.unwrap_or_default(),
+        )
+    } else {
+        let mint = TOKEN_INFO.load(deps.storage)?.mint;
+        let held: HeldResponse = deps
+            .querier
+            .query_wasm_smart(&mint, &PetStakingQueryMsg::Held {})?;
+        (mint, held.amount)
+    };
+    let balance = BALANCES
+        .may_load(deps.storage, &source)?
+        .unwrap_or_default();
+    let available = balance.saturating_sub(backing);
+    if amount > available {
+        return Err(ContractError::RecoverExceedsSurplus { available });
+    }
+
+    BALANCES.update(
+        deps.storage,
+        &source,
+        |balance: Option<Uint128>| -> StdResult<_> {
+            Ok(balance.unwrap_or_default().checked_sub(amount)?)
+        },
+    )?;
+    BALANCES.update(
+        deps.storage,
+        &rcpt_addr,
+        |balance: Option<Uint128>| -> StdResult<_> { Ok(balance.unwrap_or_default() + amount) },
+    )?;
+
+    let hooks = prepare_hooks(
+        deps.storage,
+        BalanceChangeKind::Transfer,
+        Some(&source),
+        Some(&rcpt_addr),
+        amount,
+    )?;
+
+    let res = Response::new().add_submessages(hooks).add_attributes(vec![
+        attr("action", "recover_tokens"),
+        attr("from", source),
+        attr("to", recipient),
+        attr("amount", amount),
+    ]);
+    Ok(res)
+}
+
+/// Adds tokens moved onto the contract's balance for an escrow or stream to `HELD`
+pub fn hold(storage: &mut dyn Storage, amount: Uint128) -> StdResult<()> {
+    let held = HELD.may_load(storage)?.unwrap_or_default();
+    HELD.save(storage, &held.checked_add(amount)?)
+}
+
+/// Removes tokens paid out of an escrow or stream from `HELD`
+pub fn unhold(storage: &mut dyn Storage, amount: Uint128) -> StdResult<()> {
+    let held = HELD.may_load(storage)?.unwrap_or_default();
+    HELD.save(storage, &held.checked_sub(amount)?)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use cosmwasm_std::testing::{mock_dependencies, mock_env, mock_info};
+    use cosmwasm_std::{to_json_binary, ContractResult, Deps, SystemResult, WasmQuery};
+    use cw20::Expiration;
+
+    use crate::contract::{execute, instantiate, query_balance};
+    use crate::msg::{ExecuteMsg, InitialBalance, InstantiateMsg};
+
+    const ADMIN_ADDR: &str = "admin";
+    const MINTER: &str = "staking";
+
+    fn get_balance<T: Into<String>>(deps: Deps, address: T) -> Uint128 {
+        query_balance(deps, address.into()).unwrap().balance
+    }
+
+    fn do_instantiate(deps: DepsMut) {
+        let instantiate_msg = InstantiateMsg {
+            name: "Auto Gen".to_string(),
+            symbol: "AUTO".to_string(),
+            decimals: 3,
+            initial_balances: vec![InitialBalance {
+                address: "owner".into(),
+                amount: Uint128::new(1000),
+                vesting: None,
+            }],
+            mint: MINTER.to_string(),
+            admin: Some(ADMIN_ADDR.to_string()),
+            marketing: None,
+        };
+        let info = mock_info("creator", &[]);
+        instantiate(deps, mock_env(), info, instantiate_msg).unwrap();
+    }
+
+    fn transfer(deps: DepsMut, to: &str, amount: u128) {
+        let msg = ExecuteMsg::Transfer {
+            recipient: to.into(),
+            amount: Uint128::new(amount),
+            memo: None,
+        };
+        execute(deps, mock_env(), mock_info("owner", &[]), msg).unwrap();
+    }
+
+    fn recover(from_contract_balance: bool, amount: u128) -> ExecuteMsg {
+        ExecuteMsg::RecoverTokens {
+            from_contract_balance,
+            recipient: "owner".into(),
+            amount: Uint128::new(amount),
+        }
+    }
+
+    #[test]
+    fn recover_from_contract_balance() {
+        let mut deps = mock_dependencies();
+        do_instantiate(deps.as_mut());
+        let env = mock_env();
+        let contract = env.contract.address.to_string();
+
+        // 100 sent by mistake, 200 held for an escrow and 300 for a stream
+        transfer(deps.as_mut(), &contract, 100);
+        let msg = ExecuteMsg::CreateEscrow {
+            recipient: "friend".into(),
+            amount: Uint128::new(200),
+            release_at: Expiration::AtHeight(env.block.height + 10),
+        };
+        execute(deps.as_mut(), env.clone(), mock_info("owner", &[]), msg).unwrap();
+        let msg = ExecuteMsg::CreateStream {
+            recipient: "friend".into(),
+            amount: Uint128::new(300),
+            start_time: env.block.time,
+            end_time: env.block.time.plus_seconds(100),
+        };
+        execute(deps.as_mut(), env.clone(), mock_info("owner", &[]), msg).unwrap();
+        assert_eq!(get_balance(deps.as_ref(), &contract), Uint128::new(600));
+
+        // only the admin can recover
+        let err = execute(
+            deps.as_mut(),
+            env.clone(),
+            mock_info("owner", &[]),
+            recover(true, 100),
+        )
+        .unwrap_err();
+        assert!(matches!(err, ContractError::Admin(_)));
+
+        // escrowed and streamed tokens cannot be touched
+        let err = execute(
+            deps.as_mut(),
+            env.clone(),
+            mock_info(ADMIN_ADDR, &[]),
+            recover(true, 101),
+        )
+        .unwrap_err();
+        assert_eq!(
+            err,
+            ContractError::RecoverExceedsSurplus {
+                available: Uint128::new(100)
+            }
+        );
+        execute(
+            deps.as_mut(),
+            env,
+            mock_info(ADMIN_ADDR, &[]),
+            recover(true, 100),
+        )
+        .unwrap();
+        assert_eq!(get_balance(deps.as_ref(), &contract), Uint128::new(500));
+        assert_eq!(get_balance(deps.as_ref(), "owner"), Uint128::new(500));
+        assert_eq!(HELD.load(&deps.storage).unwrap(), Uint128::new(500));
+
+        // payouts release what they held, and nothing else becomes recoverable
+        let mut env = mock_env();
+        env.block.time = env.block.time.plus_seconds(50);
+        let msg = ExecuteMsg::WithdrawStream { id: 1 };
+        execute(deps.as_mut(), env.clone(), mock_info("friend", &[]), msg).unwrap();
+        let msg = ExecuteMsg::CancelEscrow { id: 1 };
+        execute(deps.as_mut(), env.clone(), mock_info("owner", &[]), msg).unwrap();
+        assert_eq!(HELD.load(&deps.storage).unwrap(), Uint128::new(150));
+        assert_eq!(get_balance(deps.as_ref(), &contract), Uint128::new(150));
+        let err = execute(
+            deps.as_mut(),
+            env,
+            mock_info(ADMIN_ADDR, &[]),
+            recover(true, 1),
+        )
+        .unwrap_err();
+        assert_eq!(
+            err,
+            ContractError::RecoverExceedsSurplus {
+                available: Uint128::zero()
+            }
+        );
+    }
+
+    #[test]
+    fn recover_from_minter_balance() {
+        let mut deps = mock_dependencies();
+        do_instantiate(deps.as_mut());
+        // the minter holds 500 for its stakes, claims and reward pool
+        deps.querier.update_wasm(|query| match query {
+            WasmQuery::Smart { contract_addr, .. } if contract_addr == MINTER => {
+                let res = HeldResponse {
+                    amount: Uint128::new(500),
+                };
+                SystemResult::Ok(ContractResult::Ok(to_json_binary(&res).unwrap()))
+            }
+            _ => panic!("unexpected query"),
+        });
+        transfer(deps.as_mut(), MINTER, 600);
+
+        let err = execute(
+            deps.as_mut(),
+            mock_env(),
+            mock_info(ADMIN_ADDR, &[]),
+            recover(false, 101),
+        )
+        .unwrap_err();
+        assert_eq!(
+            err,
+            ContractError::RecoverExceedsSurplus {
+                available: Uint128::new(100)
+            }
+        );
+        execute(
+            deps.as_mut(),
+            mock_env(),
+            mock_info(ADMIN_ADDR, &[]),
+            recover(false, 100),
+        )
+        .unwrap();
+        assert_eq!(get_balance(deps.as_ref(), MINTER), Uint128::new(500));
+        assert_eq!(get_balance(deps.as_ref(), "owner"), Uint128::new(500));
+    }
+}
@@ -1,8 +1,11 @@
 use cosmwasm_schema::cw_serde;
-use cosmwasm_std::{Addr, OverflowError, Timestamp, Uint128};
-use cw_storage_plus::{Item, Map};
+use cosmwasm_std::{Addr, OverflowError, StdResult, Storage, Timestamp, Uint128};
+use cw_storage_plus::{Index, IndexList, IndexedMap, Item, KeyDeserialize, Map, MultiIndex};
 
-use cw20::AllowanceResponse;
+use cw20::{AllowanceResponse, Expiration, Logo, MarketingInfoResponse};
+use cw_controllers::Admin;
+
+use crate::msg::BalanceChangeKind;
 
 #[cw_serde]
 pub struct TokenInfo {
@@ -13,7 +16,6 @@ pub struct TokenInfo {
     pub mint: Addr,
 }
 
-
 #[cw_serde]
 pub struct PetStakingData {
     pub start_time: Timestamp,
@@ -42,10 +44,291 @@ impl PetStakingData {
     }
 }
 
+#[cw_serde]
+pub struct VestingSchedule {
+    /// Time from which tokens start vesting linearly
+    pub start_time: Timestamp,
+    /// Nothing is vested before this time
+    pub cliff_time: Timestamp,
+    /// All tokens are vested from this time on
+    pub end_time: Timestamp,
+}
+
+impl VestingSchedule {
+    pub fn is_valid(&self) -> bool {
+        // start <= cliff <= end && start < end
+        self.start_time.le(&self.cliff_time)
+            && self.cliff_time.le(&self.end_time)
+            && self.start_time.lt(&self.end_time)
+    }
+
+    /// Amount out of `total` that has vested at the given time
+    pub fn vested_amount(&self, total: Uint128, time: &Timestamp) -> Uint128 {
+        if time.lt(&self.cliff_time) {
+            return Uint128::zero();
+        }
+        if time.ge(&self.end_time) {
+            return total;
+        }
+        let elapsed = time.seconds() - self.start_time.seconds();
+        let duration = self.end_time.seconds() - self.start_time.seconds();
+        total.multiply_ratio(elapsed, duration)
+    }
+}
+
+#[cw_serde]
+pub struct Vesting {
+    pub schedule: VestingSchedule,
+    pub total: Uint128,
+    pub released: Uint128,
+}
+
+impl Vesting {
+    /// Tokens which are not yet released and therefore cannot be spent
+    pub fn locked(&self) -> Uint128 {
+        self.total - self.released
+    }
+
+    /// Tokens which have vested but not been released yet
+    pub fn releasable(&self, time: &Timestamp) -> Uint128 {
+        self.schedule
+            .vested_amount(self.total, time)
+            .saturating_sub(self.released)
+    }
+}
+
+#[cw_serde]
+pub struct Escrow {
+    pub sender: Addr,
+    pub recipient: Addr,
+    pub amount: Uint128,
+    pub release_at: Expiration,
+}
+
+pub struct EscrowIndexes<'a> {
+    pub sender: MultiIndex<'a, Addr, Escrow, u64>,
+    pub recipient: MultiIndex<'a, Addr, Escrow, u64>,
+}
+
+impl<'a> IndexList<Escrow> for EscrowIndexes<'a> {
+    fn get_indexes(&'_ self) -> Box<dyn Iterator<Item = &'_ dyn Index<Escrow>> + '_> {
+        let v: Vec<&dyn Index<Escrow>> = vec![&self.sender, &self.recipient];
+        Box::new(v.into_iter())
+    }
+}
+
+pub fn escrows<'a>() -> IndexedMap<'a, u64, Escrow, EscrowIndexes<'a>> {
+    let indexes = EscrowIndexes {
+        sender: MultiIndex::new(|_pk, e| e.sender.clone(), "escrow", "escrow__sender"),
+        recipient: MultiIndex::new(|_pk, e| e.recipient.clone(), "escrow", "escrow__recipient"),
+    };
+    IndexedMap::new("escrow", indexes)
+}
+
+#[cw_serde]
+pub struct Stream {
+    pub sender: Addr,
+    pub recipient: Addr,
+    pub deposit: Uint128,
+    pub withdrawn: Uint128,
+    pub start_time: Timestamp,
+    pub end_time: Timestamp,
+}
+
+impl Stream {
+    /// Amount of the deposit that has streamed to the recipient at the given time
+    pub fn streamed_amount(&self, time: &Timestamp) -> Uint128 {
+        if time.le(&self.start_time) {
+            return Uint128::zero();
+        }
+        if time.ge(&self.end_time) {
+            return self.deposit;
+        }
+        let elapsed = time.seconds() - self.start_time.seconds();
+        let duration = self.end_time.seconds() - self.start_time.seconds();
+        self.deposit.multiply_ratio(elapsed, duration)
+    }
+
+    /// Streamed tokens the recipient has not withdrawn yet
+    pub fn withdrawable(&self, time: &Timestamp) -> Uint128 {
+        self.streamed_amount(time) - self.withdrawn
+    }
+}
+
+pub struct StreamIndexes<'a> {
+    pub sender: MultiIndex<'a, Addr, Stream, u64>,
+    pub recipient: MultiIndex<'a, Addr, Stream, u64>,
+}
+
+impl<'a> IndexList<Stream> for StreamIndexes<'a> {
+    fn get_indexes(&'_ self) -> Box<dyn Iterator<Item = &'_ dyn Index<Stream>> + '_> {
+        let v: Vec<&dyn Index<Stream>> = vec![&self.sender, &self.recipient];
+        Box::new(v.into_iter())
+    }
+}
+
+pub fn streams<'a>() -> IndexedMap<'a, u64, Stream, StreamIndexes<'a>> {
+    let indexes = StreamIndexes {
+        sender: MultiIndex::new(|_pk, s| s.sender.clone(), "stream", "stream__sender"),
+        recipient: MultiIndex::new(|_pk, s| s.recipient.clone(), "stream", "stream__recipient"),
+    };
+    IndexedMap::new("stream", indexes)
+}
+
+#[cw_serde]
+pub struct AllowanceRefill {
+    /// Allowance available at the start of every period
+    pub cap: Uint128,
+    /// Length of a period in seconds
+    pub period: u64,
+    /// Start of the period the allowance was last refilled in
+    pub period_start: Timestamp,
+}
+
+impl AllowanceRefill {
+    /// Start of the period the given time falls in
+    pub fn current_period_start(&self, time: &Timestamp) -> Timestamp {
+        let elapsed = time.seconds().saturating_sub(self.period_start.seconds());
+        self.period_start
+            .plus_seconds(elapsed - elapsed % self.period)
+    }
+
+    /// Time at which the allowance is refilled next
+    pub fn next_reset(&self, time: &Timestamp) -> Timestamp {
+        self.current_period_start(time).plus_seconds(self.period)
+    }
+
+    /// Whether a new period began since the last refill
+    pub fn is_due(&self, time: &Timestamp) -> bool {
+        self.current_period_start(time) > self.period_start
+    }
+}
+
+#[cw_serde]
+pub struct MintRecord {
+    pub minter: Addr,
+    pub recipient: Addr,
+    pub amount: Uint128,
+    pub time: Timestamp,
+    /// Start time (in seconds) of the daily minting window this mint counted against
+    pub window: u64,
+}
+
+pub struct MintRecordIndexes<'a> {
+    pub recipient: MultiIndex<'a, Addr, MintRecord, u64>,
+}
+
+impl<'a> IndexList<MintRecord> for MintRecordIndexes<'a> {
+    fn get_indexes(&'_ self) -> Box<dyn Iterator<Item = &'_ dyn Index<MintRecord>> + '_> {
+        let v: Vec<&dyn Index<MintRecord>> = vec![&self.recipient];
+        Box::new(v.into_iter())
+    }
+}
+
+pub fn mint_history<'a>() -> IndexedMap<'a, u64, MintRecord, MintRecordIndexes<'a>> {
+    let indexes = MintRecordIndexes {
+        recipient: MultiIndex::new(
+            |_pk, m| m.recipient.clone(),
+            "mint_history",
+            "mint_history__recipient",
+        ),
+    };
+    IndexedMap::new("mint_history", indexes)
+}
+
+pub struct AllowanceIndexes<'a> {
+    pub spender: MultiIndex<'a, Addr, AllowanceResponse, (Addr, Addr)>,
+}
+
+impl<'a> IndexList<AllowanceResponse> for AllowanceIndexes<'a> {
+    fn get_indexes(&'_ self) -> Box<dyn Iterator<Item = &'_ dyn Index<AllowanceResponse>> + '_> {
+        let v: Vec<&dyn Index<AllowanceResponse>> = vec![&self.spender];
+        Box::new(v.into_iter())
+    }
+}
+
+// the spender is the second half of the (owner, spender) primary key
+fn allowance_spender(pk: &[u8], _: &AllowanceResponse) -> Addr {
+    let (_, spender) = <(Addr, Addr)>::from_slice(pk).expect("invalid allowance key");
+    spender
+}
+
+/// Keeps track of the number of accounts with a non-zero balance
+pub struct HolderCount<'a> {
+    count: Item<'a, u64>,
+}
+
+impl<'a> HolderCount<'a> {
+    pub const fn new(namespace: &'a str) -> Self {
+        Self {
+            count: Item::new(namespace),
+        }
+    }
+
+    pub fn load(&self, storage: &dyn Storage) -> StdResult<u64> {
+        Ok(self.count.may_load(storage)?.unwrap_or_default())
+    }
+
+    fn add(&self, storage: &mut dyn Storage, delta: i64) -> StdResult<()> {
+        let count = self.load(storage)?.saturating_add_signed(delta);
+        self.count.save(storage, &count)
+    }
+}
+
+impl<'a> Index<Uint128> for HolderCount<'a> {
+    fn save(&self, store: &mut dyn Storage, _pk: &[u8], data: &Uint128) -> StdResult<()> {
+        if data.is_zero() {
+            return Ok(());
+        }
+        self.add(store, 1)
+    }
+
+    fn remove(&self, store: &mut dyn Storage, _pk: &[u8], old_data: &Uint128) -> StdResult<()> {
+        if old_data.is_zero() {
+            return Ok(());
+        }
+        self.add(store, -1)
+    }
+}
+
+pub struct BalanceIndexes<'a> {
+    pub amount: MultiIndex<'a, u128, Uint128, Addr>,
+    pub holders: HolderCount<'a>,
+}
+
+impl<'a> IndexList<Uint128> for BalanceIndexes<'a> {
+    fn get_indexes(&'_ self) -> Box<dyn Iterator<Item = &'_ dyn Index<Uint128>> + '_> {
+        let v: Vec<&dyn Index<Uint128>> = vec![&self.amount, &self.holders];
+        Box::new(v.into_iter())
+    }
+}
+
+pub const ADMIN: Admin = Admin::new("admin");
 pub const TOKEN_INFO: Item<TokenInfo> = Item::new("token_info");
-pub const BALANCES: Map<&Addr, Uint128> = Map::new("balance");
-pub const ALLOWANCES: Map<(&Addr, &Addr), AllowanceResponse> = Map::new("allowance");
-// TODO: After https://github.com/CosmWasm/cw-plus/issues/670 is implemented, replace this with a `MultiIndex` over `ALLOWANCES`
-pub const ALLOWANCES_SPENDER: Map<(&Addr, &Addr), AllowanceResponse> =
-    Map::new("allowance_spender");
+pub const MARKETING_INFO: Item<MarketingInfoResponse> = Item::new("marketing_info");
+pub const LOGO: Item<Logo> = Item::new("logo");
+pub const BALANCES: IndexedMap<&Addr, Uint128, BalanceIndexes> = IndexedMap::new(
+    "balance",
+    BalanceIndexes {
+        amount: MultiIndex::new(|_pk, b| b.u128(), "balance", "balance__amount"),
+        holders: HolderCount::new("holder_count"),
+    },
+);
+pub const ALLOWANCES: IndexedMap<(&Addr, &Addr), AllowanceResponse, AllowanceIndexes> =
+    IndexedMap::new(
+        "allowance",
+        AllowanceIndexes {
+            spender: MultiIndex::new(allowance_spender, "allowance", "allowance__spender"),
+        },
+    );
+pub const ALLOWANCE_REFILLS: Map<(&Addr, &Addr), AllowanceRefill> = Map::new("allowance_refill");
 pub const PET_STAKING_DATA: Item<PetStakingData> = Item::new("pet_staking_data");
+pub const VESTING: Map<&Addr, Vesting> = Map::new("vesting");
+pub const ESCROW_COUNT: Item<u64> = Item::new("escrow_count");
+pub const STREAM_COUNT: Item<u64> = Item::new("stream_count");
+/// Tokens on the contract's own balance backing open escrows and streams
+pub const HELD: Item<Uint128> = Item::new("held");
+pub const MINT_COUNT: Item<u64> = Item::new("mint_count");
+/// Total minted per daily window, keyed by the window start time in seconds
+pub const MINT_WINDOW_TOTALS: Map<u64, Uint128> = Map::new("mint_window_totals");
+pub const HOOKS: Map<&Addr, Vec<BalanceChangeKind>> = Map::new("hooks");
@@ -0,0 +1,470 @@
+use cosmwasm_std::{
+    attr, Addr, Deps, DepsMut, Env, MessageInfo, Order, Response, StdResult, Storage, SubMsg,
+    Timestamp, Uint128,
+};
+use cw_storage_plus::Bound;
+
+use crate::error::ContractError;
+use crate::hooks::prepare_hooks;
+use crate::msg::{BalanceChangeKind, StreamResponse, StreamsByResponse};
+use crate::recovery::{hold, unhold};
+use crate::state::{streams, Stream, BALANCES, STREAM_COUNT};
+use crate::vesting::assert_unlocked;
+
+// settings for pagination
+const MAX_LIMIT: u32 = 30;
+const DEFAULT_LIMIT: u32 = 10;
+
+pub fn execute_create_stream(
+    deps: DepsMut,
+    env: Env,
+    info: MessageInfo,
+    recipient: String,
+    amount: Uint128,
+    start_time: Timestamp,
+    end_time: Timestamp,
+) -> Result<Response, ContractError> {
+    let rcpt_addr = deps.api.addr_validate(&recipient)?;
+    if amount.is_zero() {
+        return Err(ContractError::EmptyStream {});
+    }
+    if start_time >= end_time || end_time <= env.block.time {
+        return Err(ContractError::InvalidStreamTimes {});
+    }
+
+    assert_unlocked(deps.storage, &info.sender, &info.sender, None, amount)?;
+
+    // the deposit is held by the contract itself until it is withdrawn or cancelled
+    BALANCES.update(
+        deps.storage,
+        &info.sender,
+        |balance: Option<Uint128>| -> StdResult<_> {
+            Ok(balance.unwrap_or_default().checked_sub(amount)?)
+        },
+    )?;
+    BALANCES.update(
+        deps.storage,
+        &env.contract.address,
+        |balance: Option<Uint128>| -> StdResult<_> { Ok(balance.unwrap_or_default() + amount) },
+    )?;
+    hold(deps.storage, amount)?;
+
+    let id = STREAM_COUNT.may_load(deps.storage)?.unwrap_or_default() + 1;
+    STREAM_COUNT.save(deps.storage, &id)?;
+    let stream = Stream {
+        sender: info.sender.clone(),
+        recipient: rcpt_addr,
+        deposit: amount,
+        withdrawn: Uint128::zero(),
+        start_time,
+        end_time,
+    };
+    streams().save(deps.storage, id, &stream)?;
+
+    let hooks = prepare_hooks(
+        deps.storage,
+        BalanceChangeKind::Transfer,
+        Some(&info.sender),
+        Some(&env.contract.address),
+        amount,
+    )?;
+
+    let res = Response::new().add_submessages(hooks).add_attributes(vec![
+        attr("action", "create_stream"),
+        attr("id", id.to_string()),
+        attr("from", info.sender),
+        attr("to", recipient),
+        attr("amount", amount),
+        attr("start_time", start_time.to_string()),
+        attr("end_time", end_time.to_string()),
+    ]);
+    Ok(res)
+}
+
+pub fn execute_withdraw_stream(
+    deps: DepsMut,
+    env: Env,
+    info: MessageInfo,
+    id: u64,
+) -> Result<Response, ContractError> {
+    let mut stream = streams()
+        .may_load(deps.storage, id)?
+        .ok_or(ContractError::StreamNotFound {})?;
+    if stream.recipient != info.sender {
+        return Err(ContractError::Unauthorized {});
+    }
+
+    let amount = stream.withdrawable(&env.block.time);
+    if amount.is_zero() {
+        return Err(ContractError::NothingToWithdraw {});
+    }
+
+    stream.withdrawn += amount;
+    if stream.withdrawn == stream.deposit {
+        streams().remove(deps.storage, id)?;
+    } else {
+        streams().save(deps.storage, id, &stream)?;
+    }
+    let hooks = pay_out(
+        deps.storage,
+        &env.contract.address,
+        &stream.recipient,
+        amount,
+    )?;
+
+    let res = Response::new().add_submessages(hooks).add_attributes(vec![
+        attr("action", "withdraw_stream"),
+        attr("id", id.to_string()),
+        attr("to", stream.recipient),
+        attr("amount", amount),
+    ]);
+    Ok(res)
+}
+
+pub fn execute_cancel_stream(
+    deps: DepsMut,
+    env: Env,
+    info: MessageInfo,
+    id: u64,
+) -> Result<Response, ContractError> {
+    let stream = streams()
+        .may_load(deps.storage, id)?
+        .ok_or(ContractError::StreamNotFound {})?;
+    if stream.sender != info.sender && stream.recipient != info.sender {
+        return Err(ContractError::Unauthorized {});
+    }
+
+    // settle pro-rata: the recipient keeps what has streamed, the sender gets the rest
+    let streamed = stream.streamed_amount(&env.block.time);
+    let recipient_amount = streamed - stream.withdrawn;
+    let sender_amount = stream.deposit - streamed;
+
+    streams().remove(deps.storage, id)?;
+    let mut hooks = pay_out(
+        deps.storage,
+        &env.contract.address,
+        &stream.recipient,
+        recipient_amount,
+    )?;
+    hooks.extend(pay_out(
+        deps.storage,
+        &env.contract.address,
+        &stream.sender,
+        sender_amount,
+    )?);
+
+    let res = Response::new().add_submessages(hooks).add_attributes(vec![
+        attr("action", "cancel_stream"),
+        attr("id", id.to_string()),
+        attr("by", info.sender),
+        attr("sender", stream.sender),
+        attr("sender_amount", sender_amount),
+        attr("recipient", stream.recipient),
+        attr("recipient_amount", recipient_amount),
+    ]);
+    Ok(res)
+}
+
+// moves streamed tokens from the contract's balance to `to` and returns the hooks
+fn pay_out(
+    storage: &mut dyn Storage,
+    contract: &Addr,
+    to: &Addr,
+    amount: Uint128,
+) -> StdResult<Vec<SubMsg>> {
+    if amount.is_zero() {
+        return Ok(vec![]);
+    }
+    unhold(storage, amount)?;
+    BALANCES.update(
+        storage,
+        contract,
+        |balance: Option<Uint128>| -> StdResult<_> {
+            Ok(balance.unwrap_or_default().checked_sub(amount)?)
+        },
+    )?;
+    BALANCES.update(storage, to, |balance: Option<Uint128>| -> StdResult<_> {
+        Ok(balance.unwrap_or_default() + amount)
+    })?;
+    prepare_hooks(
+        storage,
+        BalanceChangeKind::Transfer,
+        Some(contract),
+        Some(to),
+        amount,
+    )
+}
+
+fn to_stream_response(env: &Env, id: u64, stream: Stream) -> StreamResponse {
+    StreamResponse {
+        id,
+        withdrawable: stream.withdrawable(&env.block.time),
+        sender: stream.sender.into(),
+        recipient: stream.recipient.into(),
+        deposit: stream.deposit,
+        withdrawn: stream.withdrawn,
+        start_time: stream.start_time,
+        end_time: stream.end_time,
+    }
+}
+
+pub fn query_stream(deps: Deps, env: Env, id: u64) -> StdResult<StreamResponse> {
+    let stream = streams().load(deps.storage, id)?;
+    Ok(to_stream_response(&env, id, stream))
+}
+
+pub fn query_streams_by(
+    deps: Deps,
+    env: Env,
+    address: String,
+    start_after: Option<u64>,
+    limit: Option<u32>,
+) -> StdResult<StreamsByResponse> {
+    let addr = deps.api.addr_validate(&address)?;
+    let limit = limit.unwrap_or(DEFAULT_LIMIT).min(MAX_LIMIT) as usize;
+
+    let sending = streams()
+        .idx
+        .sender
+        .prefix(addr.clone())
+        .range(
+            deps.storage,
+            start_after.map(Bound::exclusive),
+            None,
+            Order::Ascending,
+        )
+        .take(limit)
+        .map(|item| item.map(|(id, stream)| to_stream_response(&env, id, stream)))
+        .collect::<StdResult<_>>()?;
+    let receiving = streams()
+        .idx
+        .recipient
+        .prefix(addr)
+        .range(
+            deps.storage,
+            start_after.map(Bound::exclusive),
+            None,
+            Order::Ascending,
+        )
+        .take(limit)
+        .map(|item| item.map(|(id, stream)| to_stream_response(&env, id, stream)))
+        .collect::<StdResult<_>>()?;
+    Ok(StreamsByResponse { sending, receiving })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use cosmwasm_std::testing::{mock_dependencies, mock_env, mock_info};
+
+    use crate::contract::{execute, instantiate, query_balance};
+    use crate::msg::{ExecuteMsg, InitialBalance, InstantiateMsg};
+
+    fn get_balance<T: Into<String>>(deps: Deps, address: T) -> Uint128 {
+        query_balance(deps, address.into()).unwrap().balance
+    }
+
+    // this will set up the instantiation for other tests
+    fn do_instantiate(deps: DepsMut, addr: &str, amount: Uint128) {
+        let instantiate_msg = InstantiateMsg {
+            name: "Auto Gen".to_string(),
+            symbol: "AUTO".to_string(),
+            decimals: 3,
+            initial_balances: vec![InitialBalance {
+                address: addr.into(),
+                amount,
+                vesting: None,
+            }],
+            mint: "test_minter".to_string(),
+            admin: None,
+            marketing: None,
+        };
+        let info = mock_info("creator", &[]);
+        instantiate(deps, mock_env(), info, instantiate_msg).unwrap();
+    }
+
+    // streams 1000 tokens over 100 seconds starting now
+    fn create_stream(deps: DepsMut, sender: &str, recipient: &str) -> u64 {
+        let env = mock_env();
+        let msg = ExecuteMsg::CreateStream {
+            recipient: recipient.into(),
+            amount: Uint128::new(1000),
+            start_time: env.block.time,
+            end_time: env.block.time.plus_seconds(100),
+        };
+        let res = execute(deps, env, mock_info(sender, &[]), msg).unwrap();
+        res.attributes[1].value.parse().unwrap()
+    }
+
+    #[test]
+    fn invalid_times_rejected() {
+        let mut deps = mock_dependencies();
+        do_instantiate(deps.as_mut(), "payer", Uint128::new(5000));
+
+        let env = mock_env();
+        let msg = ExecuteMsg::CreateStream {
+            recipient: "member".into(),
+            amount: Uint128::new(1000),
+            start_time: env.block.time,
+            end_time: env.block.time,
+        };
+        let err = execute(deps.as_mut(), env.clone(), mock_info("payer", &[]), msg).unwrap_err();
+        assert_eq!(err, ContractError::InvalidStreamTimes {});
+
+        let msg = ExecuteMsg::CreateStream {
+            recipient: "member".into(),
+            amount: Uint128::new(1000),
+            start_time: env.block.time.minus_seconds(100),
+            end_time: env.block.time.minus_seconds(1),
+        };
+        let err = execute(deps.as_mut(), env.clone(), mock_info("payer", &[]), msg).unwrap_err();
+        assert_eq!(err, ContractError::InvalidStreamTimes {});
+
+        let msg = ExecuteMsg::CreateStream {
+            recipient: "member".into(),
+            amount: Uint128::zero(),
+            start_time: env.block.time,
+            end_time: env.block.time.plus_seconds(100),
+        };
+        let err = execute(deps.as_mut(), env, mock_info("payer", &[]), msg).unwrap_err();
+        assert_eq!(err, ContractError::EmptyStream {});
+    }
+
+    #[test]
+    fn withdraw_streamed_amount() {
+        let mut deps = mock_dependencies();
+        do_instantiate(deps.as_mut(), "payer", Uint128::new(5000));
+        let id = create_stream(deps.as_mut(), "payer", "member");
+        assert_eq!(get_balance(deps.as_ref(), "payer"), Uint128::new(4000));
+
+        // nothing streamed yet
+        let err = execute(
+            deps.as_mut(),
+            mock_env(),
+            mock_info("member", &[]),
+            ExecuteMsg::WithdrawStream { id },
+        )
+        .unwrap_err();
+        assert_eq!(err, ContractError::NothingToWithdraw {});
+
+        let mut env = mock_env();
+        env.block.time = env.block.time.plus_seconds(25);
+        let err = execute(
+            deps.as_mut(),
+            env.clone(),
+            mock_info("payer", &[]),
+            ExecuteMsg::WithdrawStream { id },
+        )
+        .unwrap_err();
+        assert_eq!(err, ContractError::Unauthorized {});
+
+        execute(
+            deps.as_mut(),
+            env.clone(),
+            mock_info("member", &[]),
+            ExecuteMsg::WithdrawStream { id },
+        )
+        .unwrap();
+        assert_eq!(get_balance(deps.as_ref(), "member"), Uint128::new(250));
+        let stream = query_stream(deps.as_ref(), env.clone(), id).unwrap();
+        assert_eq!(stream.withdrawn, Uint128::new(250));
+        assert_eq!(stream.withdrawable, Uint128::zero());
+
+        // after the end everything is paid out and the stream is removed
+        env.block.time = env.block.time.plus_seconds(1000);
+        execute(
+            deps.as_mut(),
+            env.clone(),
+            mock_info("member", &[]),
+            ExecuteMsg::WithdrawStream { id },
+        )
+        .unwrap();
+        assert_eq!(get_balance(deps.as_ref(), "member"), Uint128::new(1000));
+        assert_eq!(
+            get_balance(deps.as_ref(), env.contract.address.clone()),
+            Uint128::zero()
+        );
+        query_stream(deps.as_ref(), env, id).unwrap_err();
+    }
+
+    #[test]
+    fn cancel_settles_pro_rata() {
+        let mut deps = mock_dependencies();
+        do_instantiate(deps.as_mut(), "payer", Uint128::new(5000));
+        let id = create_stream(deps.as_mut(), "payer", "member");
+
+        let mut env = mock_env();
+        env.block.time = env.block.time.plus_seconds(30);
+        execute(
+            deps.as_mut(),
+            env.clone(),
+            mock_info("member", &[]),
+            ExecuteMsg::WithdrawStream { id },
+        )
+        .unwrap();
+
+        let err = execute(
+            deps.as_mut(),
+            env.clone(),
+            mock_info("stranger", &[]),
+            ExecuteMsg::CancelStream { id },
+        )
+        .unwrap_err();
+        assert_eq!(err, ContractError::Unauthorized {});
+
+        env.block.time = env.block.time.plus_seconds(30);
+        execute(
+            deps.as_mut(),
+            env.clone(),
+            mock_info("member", &[]),
+            ExecuteMsg::CancelStream { id },
+        )
+        .unwrap();
+        assert_eq!(get_balance(deps.as_ref(), "member"), Uint128::new(600));
+        assert_eq!(get_balance(deps.as_ref(), "payer"), Uint128::new(4400));
+        assert_eq!(
+            get_balance(deps.as_ref(), env.contract.address),
+            Uint128::zero()
+        );
+    }
+
+    #[test]
+    fn query_streams_by_works() {
+        let mut deps = mock_dependencies();
+        do_instantiate(deps.as_mut(), "payer", Uint128::new(5000));
+        let id1 = create_stream(deps.as_mut(), "payer", "alice");
+        let id2 = create_stream(deps.as_mut(), "payer", "bob");
+        let msg = ExecuteMsg::Transfer {
+            recipient: "alice".into(),
+            amount: Uint128::new(1000),
+            memo: None,
+        };
+        execute(deps.as_mut(), mock_env(), mock_info("payer", &[]), msg).unwrap();
+        let id3 = create_stream(deps.as_mut(), "alice", "payer");
+
+        let streams =
+            query_streams_by(deps.as_ref(), mock_env(), "payer".into(), None, None).unwrap();
+        let sending: Vec<_> = streams.sending.iter().map(|s| s.id).collect();
+        let receiving: Vec<_> = streams.receiving.iter().map(|s| s.id).collect();
+        assert_eq!(sending, vec![id1, id2]);
+        assert_eq!(receiving, vec![id3]);
+
+        let streams = query_streams_by(
+            deps.as_ref(),
+            mock_env(),
+            "payer".into(),
+            Some(id1),
+            Some(1),
+        )
+        .unwrap();
+        assert_eq!(streams.sending.len(), 1);
+        assert_eq!(streams.sending[0].id, id2);
+        assert_eq!(streams.receiving[0].id, id3);
+
+        let streams =
+            query_streams_by(deps.as_ref(), mock_env(), "bob".into(), None, None).unwrap();
+        assert_eq!(streams.sending, vec![]);
+        assert_eq!(streams.receiving.len(), 1);
+    }
+}
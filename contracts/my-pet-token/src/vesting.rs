@@ -0,0 +1,299 @@
+use cosmwasm_std::{
+    attr, Addr, Deps, DepsMut, Env, MessageInfo, Response, StdResult, Storage, Uint128,
+};
+
+use crate::error::ContractError;
+use crate::msg::VestingResponse;
+use crate::state::{BALANCES, TOKEN_INFO, VESTING};
+
+pub fn execute_release_vested(
+    deps: DepsMut,
+    env: Env,
+    info: MessageInfo,
+) -> Result<Response, ContractError> {
+    let mut vesting = VESTING
+        .may_load(deps.storage, &info.sender)?
+        .ok_or(ContractError::NoVesting {})?;
+
+    let amount = vesting.releasable(&env.block.time);
+    if amount.is_zero() {
+        return Err(ContractError::NothingToRelease {});
+    }
+
+    vesting.released += amount;
+    if vesting.locked().is_zero() {
+        VESTING.remove(deps.storage, &info.sender);
+    } else {
+        VESTING.save(deps.storage, &info.sender, &vesting)?;
+    }
+
+    let res = Response::new().add_attributes(vec![
+        attr("action", "release_vested"),
+        attr("owner", info.sender),
+        attr("amount", amount),
+        attr("locked", vesting.locked()),
+    ]);
+    Ok(res)
+}
+
+/// Ensures that spending `amount` leaves enough balance to cover the locked vesting tokens.
+/// Locked tokens may only leave the account when they are pulled by the minter (the
+/// staking contract) into its own account, so they can be staked but not sold.
+pub fn assert_unlocked(
+    storage: &dyn Storage,
+    owner: &Addr,
+    spender: &Addr,
+    recipient: Option<&Addr>,
+    amount: Uint128,
+) -> Result<(), ContractError> {
+    let vesting = match VESTING.may_load(storage, owner)? {
+        Some(vesting) => vesting,
+        None => return Ok(()),
+    };
+    let balance = BALANCES.may_load(storage, owner)?.unwrap_or_default();
+    if balance.saturating_sub(amount) >= vesting.locked() {
+        return Ok(());
+    }
+    let mint = TOKEN_INFO.load(storage)?.mint;
+    if spender == mint && recipient == Some(&mint) {
+        return Ok(());
+    }
+    Err(ContractError::TokensLocked {})
+}
+
+pub fn query_vesting(deps: Deps, env: Env, address: String) -> StdResult<VestingResponse> {
+    let address = deps.api.addr_validate(&address)?;
+    let res = match VESTING.may_load(deps.storage, &address)? {
+        Some(vesting) => VestingResponse {
+            releasable: vesting.releasable(&env.block.time),
+            locked: vesting.locked(),
+            total: vesting.total,
+            released: vesting.released,
+            schedule: Some(vesting.schedule),
+        },
+        None => VestingResponse {
+            schedule: None,
+            total: Uint128::zero(),
+            released: Uint128::zero(),
+            releasable: Uint128::zero(),
+            locked: Uint128::zero(),
+        },
+    };
+    Ok(res)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use cosmwasm_std::testing::{mock_dependencies, mock_env, mock_info};
+    use cosmwasm_std::StdError;
+
+    use crate::contract::{execute, instantiate, query_balance};
+    use crate::msg::{ExecuteMsg, InitialBalance, InstantiateMsg};
+    use crate::state::VestingSchedule;
+
+    const MINTER: &str = "staking";
+
+    fn do_instantiate(deps: DepsMut, env: &Env) -> VestingSchedule {
+        let schedule = VestingSchedule {
+            start_time: env.block.time,
+            cliff_time: env.block.time.plus_days(30),
+            end_time: env.block.time.plus_days(100),
+        };
+        let instantiate_msg = InstantiateMsg {
+            name: "Auto Gen".to_string(),
+            symbol: "AUTO".to_string(),
+            decimals: 3,
+            initial_balances: vec![
+                InitialBalance {
+                    address: "team".into(),
+                    amount: Uint128::new(1000),
+                    vesting: Some(schedule.clone()),
+                },
+                InitialBalance {
+                    address: "public".into(),
+                    amount: Uint128::new(1000),
+                    vesting: None,
+                },
+            ],
+            mint: MINTER.to_string(),
+            admin: None,
+            marketing: None,
+        };
+        let info = mock_info("creator", &[]);
+        instantiate(deps, env.clone(), info, instantiate_msg).unwrap();
+        schedule
+    }
+
+    #[test]
+    fn invalid_schedule_rejected() {
+        let mut deps = mock_dependencies();
+        let env = mock_env();
+        let instantiate_msg = InstantiateMsg {
+            name: "Auto Gen".to_string(),
+            symbol: "AUTO".to_string(),
+            decimals: 3,
+            initial_balances: vec![InitialBalance {
+                address: "team".into(),
+                amount: Uint128::new(1000),
+                vesting: Some(VestingSchedule {
+                    start_time: env.block.time,
+                    cliff_time: env.block.time.plus_days(200),
+                    end_time: env.block.time.plus_days(100),
+                }),
+            }],
+            mint: MINTER.to_string(),
+            admin: None,
+            marketing: None,
+        };
+        let err = instantiate(
+            deps.as_mut(),
+            env,
+            mock_info("creator", &[]),
+            instantiate_msg,
+        )
+        .unwrap_err();
+        assert!(matches!(
+            err,
+            ContractError::Std(StdError::GenericErr { .. })
+        ));
+    }
+
+    #[test]
+    fn locked_tokens_cannot_be_transferred() {
+        let mut deps = mock_dependencies();
+        let env = mock_env();
+        let schedule = do_instantiate(deps.as_mut(), &env);
+
+        let vesting = query_vesting(deps.as_ref(), env.clone(), "team".into()).unwrap();
+        assert_eq!(
+            vesting,
+            VestingResponse {
+                schedule: Some(schedule),
+                total: Uint128::new(1000),
+                released: Uint128::zero(),
+                releasable: Uint128::zero(),
+                locked: Uint128::new(1000),
+            }
+        );
+
+        // full balance is reported, but none of it can be spent
+        assert_eq!(
+            query_balance(deps.as_ref(), "team".into()).unwrap().balance,
+            Uint128::new(1000)
+        );
+        let msg = ExecuteMsg::Transfer {
+            recipient: "buyer".into(),
+            amount: Uint128::new(1),
+            memo: None,
+        };
+        let err = execute(deps.as_mut(), env.clone(), mock_info("team", &[]), msg).unwrap_err();
+        assert_eq!(err, ContractError::TokensLocked {});
+
+        // nothing to release before the cliff
+        let err = execute(
+            deps.as_mut(),
+            env,
+            mock_info("team", &[]),
+            ExecuteMsg::ReleaseVested {},
+        )
+        .unwrap_err();
+        assert_eq!(err, ContractError::NothingToRelease {});
+
+        // accounts without schedule are unaffected
+        let msg = ExecuteMsg::Transfer {
+            recipient: "buyer".into(),
+            amount: Uint128::new(1000),
+            memo: None,
+        };
+        execute(deps.as_mut(), mock_env(), mock_info("public", &[]), msg).unwrap();
+    }
+
+    #[test]
+    fn release_vested_unlocks_linearly() {
+        let mut deps = mock_dependencies();
+        let mut env = mock_env();
+        do_instantiate(deps.as_mut(), &env);
+
+        // half way through the schedule
+        env.block.time = env.block.time.plus_days(50);
+        let vesting = query_vesting(deps.as_ref(), env.clone(), "team".into()).unwrap();
+        assert_eq!(vesting.releasable, Uint128::new(500));
+
+        execute(
+            deps.as_mut(),
+            env.clone(),
+            mock_info("team", &[]),
+            ExecuteMsg::ReleaseVested {},
+        )
+        .unwrap();
+
+        // can send the released part, but not more
+        let msg = ExecuteMsg::Transfer {
+            recipient: "buyer".into(),
+            amount: Uint128::new(501),
+            memo: None,
+        };
+        let err = execute(deps.as_mut(), env.clone(), mock_info("team", &[]), msg).unwrap_err();
+        assert_eq!(err, ContractError::TokensLocked {});
+        let msg = ExecuteMsg::Transfer {
+            recipient: "buyer".into(),
+            amount: Uint128::new(500),
+            memo: None,
+        };
+        execute(deps.as_mut(), env.clone(), mock_info("team", &[]), msg).unwrap();
+
+        // after the end everything is released and the schedule is removed
+        env.block.time = env.block.time.plus_days(50);
+        execute(
+            deps.as_mut(),
+            env.clone(),
+            mock_info("team", &[]),
+            ExecuteMsg::ReleaseVested {},
+        )
+        .unwrap();
+        let vesting = query_vesting(deps.as_ref(), env, "team".into()).unwrap();
+        assert_eq!(vesting.schedule, None);
+        assert!(VESTING
+            .may_load(&deps.storage, &Addr::unchecked("team"))
+            .unwrap()
+            .is_none());
+    }
+
+    #[test]
+    fn locked_tokens_can_be_staked() {
+        let mut deps = mock_dependencies();
+        let env = mock_env();
+        do_instantiate(deps.as_mut(), &env);
+
+        let msg = ExecuteMsg::IncreaseAllowance {
+            spender: MINTER.into(),
+            amount: Uint128::new(1000),
+            expires: None,
+        };
+        execute(deps.as_mut(), env.clone(), mock_info("team", &[]), msg).unwrap();
+
+        // other recipients are still rejected when pulled by the minter
+        let msg = ExecuteMsg::TransferFrom {
+            owner: "team".into(),
+            recipient: "buyer".into(),
+            amount: Uint128::new(400),
+            memo: None,
+        };
+        let err = execute(deps.as_mut(), env.clone(), mock_info(MINTER, &[]), msg).unwrap_err();
+        assert_eq!(err, ContractError::TokensLocked {});
+
+        let msg = ExecuteMsg::TransferFrom {
+            owner: "team".into(),
+            recipient: MINTER.into(),
+            amount: Uint128::new(400),
+            memo: None,
+        };
+        execute(deps.as_mut(), env, mock_info(MINTER, &[]), msg).unwrap();
+        assert_eq!(
+            query_balance(deps.as_ref(), MINTER.into()).unwrap().balance,
+            Uint128::new(400)
+        );
+    }
+}
@@ -1,20 +1,58 @@
 #[cfg(not(feature = "library"))]
 use cosmwasm_std::entry_point;
 use cosmwasm_std::{
-    to_json_binary, Binary, Deps, DepsMut, Env, MessageInfo, Response, StdResult, SubMsg, Uint128,
+    from_json, to_json_binary, Addr, Binary, Deps, DepsMut, Env, MessageInfo, Order, Response,
+    StdError, StdResult, Storage, SubMsg, Uint128, WasmMsg,
 };
 
 use cw2::set_contract_version;
+use cw20::{BalanceResponse, Cw20ExecuteMsg, Cw20QueryMsg};
+use cw_storage_plus::Bound;
 use cw_utils::maybe_addr;
 
 use crate::error::ContractError;
-use crate::msg::{ExecuteMsg, InstantiateMsg, QueryMsg, StakedResponse, TotalStakeResponse};
-use crate::state::{Config, ADMIN, BALANCES, CONFIG, TOTAL};
+use crate::limits::{assert_deposit, load_limits, validate_limits};
+use crate::liquid::{
+    execute_liquid_stake, execute_receive, execute_set_receipt_token, query_liquid_pool,
+};
+use crate::lockup::{
+    execute_update_lock_tiers, locked_amount, member_hooks, open_position, query_lock_tiers,
+    query_positions, update_weight,
+};
+use crate::msg::{
+    AllowanceReceiveMsg, ConfigResponse, ExecuteMsg, HeldResponse, InstantiateMsg, QueryMsg,
+    ReceiveMsg, StakedResponse, TotalStakeResponse, VerifyStakeResponse,
+};
+use crate::operators::{
+    assert_operator, execute_approve_operator, execute_revoke_operator, query_operators,
+};
+use crate::penalty::{
+    collect_penalty, execute_update_withdraw_penalty, query_simulate_withdraw,
+    query_withdraw_penalty, record_deposit, take_penalty,
+};
+use crate::rewards::{
+    execute_claim_rewards, execute_compound, execute_set_auto_compound, query_rewards,
+    set_reward_rate,
+};
+use crate::slashing::{execute_slash, execute_update_slashing, query_slashing};
+use crate::state::{
+    Config, StakeLimits, ADMIN, BALANCES, CONFIG, HOOKS, REWARD_POOL, REWARD_STATE, STAKE_LIMITS,
+    TOTAL, UNBONDING, UNBONDING_PERIOD,
+};
+use crate::transfer::{
+    execute_cancel_transfer_stake, execute_complete_transfer_stake, execute_transfer_stake,
+    query_pending_transfer, reserved_stake,
+};
+use crate::unbonding::{execute_claim, pay_out, query_claims, validate_unbonding_period};
 
 // version info for migration info
 const CONTRACT_NAME: &str = "crates.io:pet-staking";
 const CONTRACT_VERSION: &str = env!("CARGO_PKG_VERSION");
 
+// settings for pagination
+const MAX_LIMIT: u32 = 30;
+const DEFAULT_LIMIT: u32 = 10;
+
 // Note, you can use StdResult in some functions where you do not
 // make use of the custom errors
 #[cfg_attr(not(feature = "library"), entry_point)]
@@ -48,12 +86,180 @@ pub fn execute(
         ExecuteMsg::UpdateAdmin { admin } => {
             Ok(ADMIN.execute_update_admin(deps, info, maybe_addr(api, admin)?)?)
         }
-        ExecuteMsg::Stake { amount } => stake(deps, env, info, amount),
+        ExecuteMsg::Stake {
+            amount,
+            lock_duration,
+        } => stake(deps, env, info, amount, lock_duration),
+        ExecuteMsg::StakeFor {
+            beneficiary,
+            amount,
+        } => stake_for(deps, env, info, beneficiary, amount),
+        ExecuteMsg::UpdateConfig {
+            reward_rate,
+            unbonding_period,
+            limits,
+        } => update_config(deps, env, info, reward_rate, unbonding_period, limits),
+        ExecuteMsg::UpdateStakeLimits { limits } => {
+            update_config(deps, env, info, None, None, Some(limits))
+        }
+        ExecuteMsg::UpdateRewardRate { rate } => {
+            update_config(deps, env, info, Some(rate), None, None)
+        }
+        ExecuteMsg::UpdateLockTiers { tiers } => execute_update_lock_tiers(deps, info, tiers),
+        ExecuteMsg::UpdateWithdrawPenalty { penalty } => {
+            execute_update_withdraw_penalty(deps, info, penalty)
+        }
+        ExecuteMsg::ClaimRewards {} => execute_claim_rewards(deps, env, info.sender),
+        ExecuteMsg::SetAutoCompound { enabled } => execute_set_auto_compound(deps, info, enabled),
+        ExecuteMsg::Compound { addresses } => execute_compound(deps, env, addresses),
+        ExecuteMsg::SetReceiptToken { address } => execute_set_receipt_token(deps, info, address),
+        ExecuteMsg::LiquidStake { amount } => execute_liquid_stake(deps, env, info, amount),
+        ExecuteMsg::Receive(msg) => execute_receive(deps, env, info, msg),
         ExecuteMsg::Withdraw { amount } => withdraw(deps, env, info, amount),
+        ExecuteMsg::Claim {} => execute_claim(deps, env, info.sender),
+        ExecuteMsg::ApproveOperator { operator, expires } => {
+            execute_approve_operator(deps, env, info, operator, expires)
+        }
+        ExecuteMsg::RevokeOperator { operator } => execute_revoke_operator(deps, info, operator),
+        ExecuteMsg::StakeFrom {
+            owner,
+            amount,
+            lock_duration,
+        } => stake_from(deps, env, info, owner, amount, lock_duration),
+        ExecuteMsg::WithdrawFrom { owner, amount } => withdraw_from(deps, env, info, owner, amount),
+        ExecuteMsg::ClaimRewardsFrom { owner } => {
+            let owner = assert_operator(deps.as_ref(), &env, owner, &info.sender)?;
+            execute_claim_rewards(deps, env, owner)
+        }
+        ExecuteMsg::ClaimFrom { owner } => {
+            let owner = assert_operator(deps.as_ref(), &env, owner, &info.sender)?;
+            execute_claim(deps, env, owner)
+        }
+        ExecuteMsg::AddHook { addr } => {
+            Ok(HOOKS.execute_add_hook(&ADMIN, deps, info, api.addr_validate(&addr)?)?)
+        }
+        ExecuteMsg::RemoveHook { addr } => {
+            Ok(HOOKS.execute_remove_hook(&ADMIN, deps, info, api.addr_validate(&addr)?)?)
+        }
+        ExecuteMsg::UpdateSlashing { slasher, treasury } => {
+            execute_update_slashing(deps, info, slasher, treasury)
+        }
+        ExecuteMsg::Slash {
+            address,
+            percentage,
+        } => execute_slash(deps, env, info, address, percentage),
+        ExecuteMsg::TransferStake { recipient, amount } => {
+            execute_transfer_stake(deps, env, info, recipient, amount)
+        }
+        ExecuteMsg::CompleteTransferStake { sender } => {
+            execute_complete_transfer_stake(deps, env, sender)
+        }
+        ExecuteMsg::CancelTransferStake {} => execute_cancel_transfer_stake(deps, info),
         ExecuteMsg::Mint { amount } => mint(deps, env, info, amount),
+        ExecuteMsg::ReceiveAllowance(msg) => receive_allowance(deps, env, info, msg),
+        ExecuteMsg::RecoverForeignCw20 {
+            token,
+            recipient,
+            amount,
+        } => recover_foreign_cw20(deps, env, info, token, recipient, amount),
     }
 }
 
+/// Only the given parameters change, the staked token is fixed at instantiation
+pub fn update_config(
+    deps: DepsMut,
+    env: Env,
+    info: MessageInfo,
+    reward_rate: Option<Uint128>,
+    unbonding_period: Option<u64>,
+    limits: Option<StakeLimits>,
+) -> Result<Response, ContractError> {
+    ADMIN.assert_admin(deps.as_ref(), &info.sender)?;
+    if let Some(period) = unbonding_period {
+        validate_unbonding_period(period)?;
+    }
+    if let Some(limits) = &limits {
+        validate_limits(limits)?;
+    }
+
+    let mut res = Response::new().add_attribute("action", "update_config");
+    if let Some(rate) = reward_rate {
+        set_reward_rate(deps.storage, &env.block.time, rate)?;
+        res = res.add_attribute("reward_rate", rate);
+    }
+    if let Some(period) = unbonding_period {
+        UNBONDING_PERIOD.save(deps.storage, &period)?;
+        res = res.add_attribute("unbonding_period", period.to_string());
+    }
+    if let Some(limits) = limits {
+        STAKE_LIMITS.save(deps.storage, &limits)?;
+        res = res
+            .add_attribute("min_stake", limits.min_stake)
+            .add_attribute(
+                "max_stake_per_address",
+                limit_attr(limits.max_stake_per_address),
+            )
+            .add_attribute("max_total_stake", limit_attr(limits.max_total_stake));
+    }
+    Ok(res.add_attribute("sender", info.sender))
+}
+
+fn limit_attr(limit: Option<Uint128>) -> String {
+    limit.map_or_else(|| "none".to_string(), |limit| limit.to_string())
+}
+
+pub fn recover_foreign_cw20(
+    deps: DepsMut,
+    env: Env,
+    info: MessageInfo,
+    token: String,
+    recipient: String,
+    amount: Uint128,
+) -> Result<Response, ContractError> {
+    ADMIN.assert_admin(deps.as_ref(), &info.sender)?;
+    let token = deps.api.addr_validate(&token)?;
+    let recipient = deps.api.addr_validate(&recipient)?;
+
+    // staked tokens must stay, only what was sent here by mistake can leave
+    let config = CONFIG.load(deps.storage)?;
+    if token == config.addr {
+        let balance: BalanceResponse = deps.querier.query_wasm_smart(
+            &token,
+            &Cw20QueryMsg::Balance {
+                address: env.contract.address.into(),
+            },
+        )?;
+        let available = balance.balance.saturating_sub(held(deps.storage)?);
+        if amount > available {
+            return Err(ContractError::RecoverExceedsSurplus { available });
+        }
+    }
+
+    let msg = WasmMsg::Execute {
+        contract_addr: token.to_string(),
+        msg: to_json_binary(&Cw20ExecuteMsg::Transfer {
+            recipient: recipient.to_string(),
+            amount,
+        })?,
+        funds: vec![],
+    };
+    let res = Response::new()
+        .add_submessage(SubMsg::new(msg))
+        .add_attribute("action", "recover_foreign_cw20")
+        .add_attribute("token", token)
+        .add_attribute("to", recipient)
+        .add_attribute("amount", amount);
+
+    Ok(res)
+}
+
+// staked tokens this contract must keep for the stakes, claims and the reward pool
+fn held(storage: &dyn Storage) -> StdResult<Uint128> {
+    Ok(TOTAL.load(storage)?
+        + REWARD_POOL.may_load(storage)?.unwrap_or_default()
+        + UNBONDING.may_load(storage)?.unwrap_or_default())
+}
+
 pub fn mint(
     deps: DepsMut,
     _env: Env,
@@ -76,8 +282,29 @@ pub fn mint(
 
 pub fn withdraw(
     deps: DepsMut,
-    _env: Env,
+    env: Env,
+    info: MessageInfo,
+    amount: Uint128,
+) -> Result<Response, ContractError> {
+    unbond(deps, env, info.sender, amount)
+}
+
+pub fn withdraw_from(
+    deps: DepsMut,
+    env: Env,
     info: MessageInfo,
+    owner: String,
+    amount: Uint128,
+) -> Result<Response, ContractError> {
+    let owner = assert_operator(deps.as_ref(), &env, owner, &info.sender)?;
+    unbond(deps, env, owner, amount)
+}
+
+// removes the stake and pays it out to the staker, minus any early withdrawal penalty
+fn unbond(
+    deps: DepsMut,
+    env: Env,
+    staker: Addr,
     amount: Uint128,
 ) -> Result<Response, ContractError> {
     let total = TOTAL
@@ -86,26 +313,38 @@ pub fn withdraw(
         .checked_sub(amount)
         .map_err(|_x| ContractError::NoFunds {})?;
 
-    let config = CONFIG
-        .may_load(deps.storage)?
-        .ok_or(ContractError::Unauthorized {})?;
-
-    BALANCES.update(
-        deps.storage,
-        &info.sender,
-        |balance: Option<Uint128>| -> StdResult<_> {
-            Ok(balance.unwrap_or_default().checked_sub(amount)?)
-        },
-    )?;
+    let stake = BALANCES
+        .may_load(deps.storage, &staker)?
+        .unwrap_or_default();
+    let remaining = stake.checked_sub(amount).map_err(StdError::overflow)?;
+    if remaining < locked_amount(deps.storage, &staker, &env.block.time)? {
+        return Err(ContractError::StakeLocked {});
+    }
+    if remaining < reserved_stake(deps.storage, &staker)? {
+        return Err(ContractError::StakeReserved {});
+    }
+    let penalty = take_penalty(deps.storage, &env.block.time, &staker, stake, amount)?;
+    BALANCES.save(deps.storage, &staker, &remaining)?;
+    let diff = update_weight(deps.storage, &env, &staker)?;
 
     TOTAL.save(deps.storage, &total)?;
 
-    let msg = SubMsg::new(config.new_transfer(&info.sender, amount)?);
+    let mut msgs: Vec<_> = pay_out(deps.storage, &env, &staker, amount - penalty)?
+        .into_iter()
+        .collect();
+    msgs.extend(collect_penalty(
+        deps.storage,
+        &env.block.time,
+        &staker,
+        penalty,
+    )?);
+    msgs.extend(member_hooks(deps.storage, diff.into_iter().collect())?);
     let res = Response::new()
-        .add_submessage(msg)
+        .add_submessages(msgs)
         .add_attribute("action", "withdraw")
-        .add_attribute("to", info.sender.into_string())
-        .add_attribute("amount", amount);
+        .add_attribute("to", staker.into_string())
+        .add_attribute("amount", amount)
+        .add_attribute("penalty", penalty);
 
     Ok(res)
 }
@@ -115,32 +354,126 @@ pub fn stake(
     env: Env,
     info: MessageInfo,
     amount: Uint128,
+    lock_duration: Option<u64>,
+) -> Result<Response, ContractError> {
+    bond(
+        deps,
+        env,
+        info.sender.clone(),
+        info.sender,
+        amount,
+        lock_duration,
+    )
+}
+
+pub fn stake_from(
+    deps: DepsMut,
+    env: Env,
+    info: MessageInfo,
+    owner: String,
+    amount: Uint128,
+    lock_duration: Option<u64>,
+) -> Result<Response, ContractError> {
+    let owner = assert_operator(deps.as_ref(), &env, owner, &info.sender)?;
+    bond(deps, env, owner.clone(), owner, amount, lock_duration)
+}
+
+pub fn stake_for(
+    deps: DepsMut,
+    env: Env,
+    info: MessageInfo,
+    beneficiary: String,
+    amount: Uint128,
+) -> Result<Response, ContractError> {
+    let beneficiary = deps.api.addr_validate(&beneficiary)?;
+    bond(deps, env, info.sender, beneficiary, amount, None)
+}
+
+pub fn receive_allowance(
+    deps: DepsMut,
+    env: Env,
+    info: MessageInfo,
+    wrapper: AllowanceReceiveMsg,
+) -> Result<Response, ContractError> {
+    let config = CONFIG
+        .may_load(deps.storage)?
+        .ok_or(ContractError::NoData {})?;
+    // only the staked token can tell us about allowances
+    if info.sender != config.addr {
+        return Err(ContractError::Unauthorized {});
+    }
+
+    let owner = deps.api.addr_validate(&wrapper.owner)?;
+    let msg: ReceiveMsg = from_json(&wrapper.msg)?;
+    match msg {
+        ReceiveMsg::Bond { beneficiary } => {
+            let beneficiary = match beneficiary {
+                Some(beneficiary) => deps.api.addr_validate(&beneficiary)?,
+                None => owner.clone(),
+            };
+            bond(deps, env, owner, beneficiary, wrapper.amount, None)
+        }
+    }
+}
+
+// records the stake of the beneficiary and pulls the tokens from the payer's account
+fn bond(
+    deps: DepsMut,
+    env: Env,
+    payer: Addr,
+    staker: Addr,
+    amount: Uint128,
+    lock_duration: Option<u64>,
 ) -> Result<Response, ContractError> {
     let config = CONFIG
         .may_load(deps.storage)?
         .ok_or(ContractError::NoData {})?;
+    // the liquid pool's stake only changes with receipts
+    if staker == env.contract.address {
+        return Err(ContractError::LiquidPoolTarget {});
+    }
+    // vesting tokens may be staked, but only by their holder, who cannot sell them this way
+    if payer != staker && !config.query_locked(&deps.querier, &payer).is_zero() {
+        return Err(ContractError::VestingLocked {});
+    }
+
+    let stake = BALANCES
+        .may_load(deps.storage, &staker)?
+        .unwrap_or_default();
+    let total = TOTAL
+        .may_load(deps.storage)?
+        .ok_or(ContractError::NoData {})?
+        .checked_add(amount)
+        .map_err(|_| ContractError::Unauthorized {})?;
+    assert_deposit(
+        deps.storage,
+        amount,
+        Some(stake.checked_add(amount).map_err(StdError::overflow)?),
+        total,
+    )?;
 
+    if let Some(duration) = lock_duration {
+        open_position(deps.storage, &env.block.time, &staker, amount, duration)?;
+    }
     BALANCES.update(
         deps.storage,
-        &info.sender,
+        &staker,
         |balance: Option<Uint128>| -> StdResult<_> {
             Ok(balance.unwrap_or_default().checked_add(amount)?)
         },
     )?;
-
-    let total = TOTAL
-        .may_load(deps.storage)?
-        .ok_or(ContractError::NoData {})?
-        .checked_add(amount)
-        .map_err(|_| ContractError::Unauthorized {})?;
+    let diff = update_weight(deps.storage, &env, &staker)?;
+    record_deposit(deps.storage, &env.block.time, &staker, amount)?;
 
     TOTAL.save(deps.storage, &total)?;
 
-    let msg =
-        SubMsg::new(config.new_transfer_from_msg(&info.sender, &env.contract.address, amount)?);
+    let msg = SubMsg::new(config.new_transfer_from_msg(&payer, &env.contract.address, amount)?);
     let res = Response::new()
         .add_submessage(msg)
+        .add_submessages(member_hooks(deps.storage, diff.into_iter().collect())?)
         .add_attribute("action", "stake")
+        .add_attribute("from", payer)
+        .add_attribute("beneficiary", staker)
         .add_attribute("to", env.contract.address)
         .add_attribute("amount", amount);
 
@@ -148,11 +481,40 @@ pub fn stake(
 }
 
 #[cfg_attr(not(feature = "library"), entry_point)]
-pub fn query(deps: Deps, _env: Env, msg: QueryMsg) -> StdResult<Binary> {
+pub fn query(deps: Deps, env: Env, msg: QueryMsg) -> StdResult<Binary> {
     match msg {
         QueryMsg::TotalStaked {} => to_json_binary(&query_total_stake(deps)?),
+        QueryMsg::Held {} => to_json_binary(&query_held(deps)?),
         QueryMsg::Staked { address } => to_json_binary(&query_staked(deps, address)?),
+        QueryMsg::Claims { address } => to_json_binary(&query_claims(deps, address)?),
         QueryMsg::Admin {} => to_json_binary(&ADMIN.query_admin(deps)?),
+        QueryMsg::Config {} => to_json_binary(&query_config(deps)?),
+        QueryMsg::WithdrawPenalty {} => to_json_binary(&query_withdraw_penalty(deps)?),
+        QueryMsg::SimulateWithdraw { address, amount } => to_json_binary(&query_simulate_withdraw(
+            deps,
+            env.clone(),
+            address,
+            amount,
+        )?),
+        QueryMsg::Rewards { address } => {
+            to_json_binary(&query_rewards(deps, env.clone(), address)?)
+        }
+        QueryMsg::LiquidPool {} => to_json_binary(&query_liquid_pool(deps, env.clone())?),
+        QueryMsg::Operators { owner } => {
+            to_json_binary(&query_operators(deps, env.clone(), owner)?)
+        }
+        QueryMsg::Hooks {} => to_json_binary(&HOOKS.query_hooks(deps)?),
+        QueryMsg::Slashing {} => to_json_binary(&query_slashing(deps)?),
+        QueryMsg::LockTiers {} => to_json_binary(&query_lock_tiers(deps)?),
+        QueryMsg::Positions { address } => to_json_binary(&query_positions(deps, env, address)?),
+        QueryMsg::PendingTransfer { address } => {
+            to_json_binary(&query_pending_transfer(deps, address)?)
+        }
+        QueryMsg::VerifyStake {
+            start_after,
+            limit,
+            running_sum,
+        } => to_json_binary(&query_verify_stake(deps, start_after, limit, running_sum)?),
     }
 }
 
@@ -161,6 +523,29 @@ fn query_total_stake(deps: Deps) -> StdResult<TotalStakeResponse> {
     Ok(TotalStakeResponse { stake: total_stake })
 }
 
+fn query_held(deps: Deps) -> StdResult<HeldResponse> {
+    Ok(HeldResponse {
+        amount: held(deps.storage)?,
+    })
+}
+
+pub fn query_config(deps: Deps) -> StdResult<ConfigResponse> {
+    let config = CONFIG.load(deps.storage)?;
+    let limits = load_limits(deps.storage)?;
+    let reward_rate = REWARD_STATE
+        .may_load(deps.storage)?
+        .map(|state| state.rate)
+        .unwrap_or_default();
+    Ok(ConfigResponse {
+        token: config.addr,
+        reward_rate,
+        unbonding_period: UNBONDING_PERIOD.may_load(deps.storage)?.unwrap_or_default(),
+        min_stake: limits.min_stake,
+        max_stake_per_address: limits.max_stake_per_address,
+        max_total_stake: limits.max_total_stake,
+    })
+}
+
 pub fn query_staked(deps: Deps, addr: String) -> StdResult<StakedResponse> {
     let address = deps.api.addr_validate(&addr)?;
     let balance = BALANCES
@@ -169,15 +554,51 @@ pub fn query_staked(deps: Deps, addr: String) -> StdResult<StakedResponse> {
     Ok(StakedResponse { stake: balance })
 }
 
+pub fn query_verify_stake(
+    deps: Deps,
+    start_after: Option<String>,
+    limit: Option<u32>,
+    running_sum: Option<Uint128>,
+) -> StdResult<VerifyStakeResponse> {
+    let limit = limit.unwrap_or(DEFAULT_LIMIT).min(MAX_LIMIT) as usize;
+    let start = start_after.clone().map(|s| Bound::ExclusiveRaw(s.into()));
+    let total = TOTAL.load(deps.storage)?;
+
+    // fetch one more to know whether this is the last page
+    let mut stakes = BALANCES
+        .range(deps.storage, start, None, Order::Ascending)
+        .take(limit + 1)
+        .collect::<StdResult<Vec<_>>>()?;
+    let complete = stakes.len() <= limit;
+    stakes.truncate(limit);
+
+    let mut running_sum = running_sum.unwrap_or_default();
+    for (_, stake) in &stakes {
+        running_sum = running_sum.checked_add(*stake)?;
+    }
+    let last_address = stakes.pop().map(|(addr, _)| addr.into()).or(start_after);
+    let mismatch = running_sum > total || (complete && running_sum != total);
+
+    Ok(VerifyStakeResponse {
+        total,
+        running_sum,
+        last_address,
+        complete,
+        mismatch,
+    })
+}
+
 #[cfg(test)]
 mod tests {
     use cosmwasm_std::{
+        attr,
         testing::{mock_dependencies, mock_env, mock_info},
-        Addr, WasmMsg,
+        Addr, ContractResult, SystemResult, WasmQuery,
     };
-    use cw20::Cw20ExecuteMsg;
+    use cw_controllers::AdminError;
 
     use super::*;
+    use crate::msg::TokenQueryMsg;
 
     const INIT_ADMIN: &str = "juan";
     const CW20_ADDRESS: &str = "wasm1234567890";
@@ -247,6 +668,7 @@ mod tests {
 
         let msg = ExecuteMsg::Stake {
             amount: 999_999u128.into(),
+            lock_duration: None,
         };
 
         let res = execute(deps.as_mut(), mock_env(), mock_info("mintu", &[]), msg).unwrap();
@@ -274,6 +696,7 @@ mod tests {
 
         let msg = ExecuteMsg::Stake {
             amount: 1u128.into(),
+            lock_duration: None,
         };
 
         execute(deps.as_mut(), mock_env(), mock_info("sam", &[]), msg).unwrap();
@@ -302,6 +725,7 @@ mod tests {
 
         let msg = ExecuteMsg::Stake {
             amount: 999_999u128.into(),
+            lock_duration: None,
         };
 
         execute(deps.as_mut(), mock_env(), mock_info("mintu", &[]), msg).unwrap();
@@ -339,4 +763,362 @@ mod tests {
             }
         )
     }
+
+    #[test]
+    fn verify_stake() {
+        let mut deps = mock_dependencies();
+        default_instantiate(deps.as_mut());
+
+        for (staker, amount) in [("mintu", 600u128), ("sam", 300), ("zoe", 100)] {
+            let msg = ExecuteMsg::Stake {
+                amount: amount.into(),
+                lock_duration: None,
+            };
+            execute(deps.as_mut(), mock_env(), mock_info(staker, &[]), msg).unwrap();
+        }
+
+        // walk all pages, carrying the running sum
+        let page = query_verify_stake(deps.as_ref(), None, Some(2), None).unwrap();
+        assert_eq!(
+            page,
+            VerifyStakeResponse {
+                total: 1000u128.into(),
+                running_sum: 900u128.into(),
+                last_address: Some("sam".into()),
+                complete: false,
+                mismatch: false,
+            }
+        );
+        let page = query_verify_stake(
+            deps.as_ref(),
+            page.last_address,
+            Some(2),
+            Some(page.running_sum),
+        )
+        .unwrap();
+        assert_eq!(page.running_sum, Uint128::new(1000));
+        assert!(page.complete);
+        assert!(!page.mismatch);
+
+        // a total which drifted from the stakes is reported
+        TOTAL.save(&mut deps.storage, &Uint128::new(999)).unwrap();
+        let page = query_verify_stake(deps.as_ref(), None, None, None).unwrap();
+        assert!(page.complete);
+        assert!(page.mismatch);
+    }
+
+    #[test]
+    fn recover_foreign_cw20() {
+        let mut deps = mock_dependencies();
+        default_instantiate(deps.as_mut());
+        deps.querier.update_wasm(|query| match query {
+            WasmQuery::Smart { contract_addr, .. } if contract_addr == CW20_ADDRESS => {
+                let res = BalanceResponse {
+                    balance: 1_050u128.into(),
+                };
+                SystemResult::Ok(ContractResult::Ok(to_json_binary(&res).unwrap()))
+            }
+            _ => panic!("unexpected query"),
+        });
+
+        let msg = ExecuteMsg::Stake {
+            amount: 1_000u128.into(),
+            lock_duration: None,
+        };
+        execute(deps.as_mut(), mock_env(), mock_info("mintu", &[]), msg).unwrap();
+        assert_eq!(
+            query_held(deps.as_ref()).unwrap().amount,
+            Uint128::new(1_000)
+        );
+
+        let recover = |token: &str, amount: u128| ExecuteMsg::RecoverForeignCw20 {
+            token: token.into(),
+            recipient: "mintu".into(),
+            amount: amount.into(),
+        };
+
+        // only the admin can recover
+        let err = execute(
+            deps.as_mut(),
+            mock_env(),
+            mock_info("mintu", &[]),
+            recover("other_token", 5),
+        )
+        .unwrap_err();
+        assert!(matches!(err, ContractError::Admin(_)));
+
+        // other tokens can be recovered in full
+        let res = execute(
+            deps.as_mut(),
+            mock_env(),
+            mock_info(INIT_ADMIN, &[]),
+            recover("other_token", 5),
+        )
+        .unwrap();
+        assert_eq!(
+            res.messages[0],
+            SubMsg::new(WasmMsg::Execute {
+                contract_addr: "other_token".into(),
+                msg: to_json_binary(&Cw20ExecuteMsg::Transfer {
+                    recipient: "mintu".into(),
+                    amount: 5u128.into()
+                })
+                .unwrap(),
+                funds: vec![]
+            })
+        );
+
+        // staked tokens back the stakes, only the surplus can be recovered
+        let err = execute(
+            deps.as_mut(),
+            mock_env(),
+            mock_info(INIT_ADMIN, &[]),
+            recover(CW20_ADDRESS, 51),
+        )
+        .unwrap_err();
+        assert_eq!(
+            err,
+            ContractError::RecoverExceedsSurplus {
+                available: 50u128.into()
+            }
+        );
+        execute(
+            deps.as_mut(),
+            mock_env(),
+            mock_info(INIT_ADMIN, &[]),
+            recover(CW20_ADDRESS, 50),
+        )
+        .unwrap();
+    }
+
+    #[test]
+    fn stake_via_allowance() {
+        let mut deps = mock_dependencies();
+        default_instantiate(deps.as_mut());
+
+        let msg = ExecuteMsg::ReceiveAllowance(AllowanceReceiveMsg {
+            owner: "mintu".into(),
+            amount: 500u128.into(),
+            msg: to_json_binary(&ReceiveMsg::Bond { beneficiary: None }).unwrap(),
+        });
+
+        // only the staked token may call
+        let err = execute(
+            deps.as_mut(),
+            mock_env(),
+            mock_info("mintu", &[]),
+            msg.clone(),
+        )
+        .unwrap_err();
+        assert_eq!(err, ContractError::Unauthorized {});
+
+        let res = execute(deps.as_mut(), mock_env(), mock_info(CW20_ADDRESS, &[]), msg).unwrap();
+        assert_eq!(
+            res.messages[0],
+            SubMsg::new(WasmMsg::Execute {
+                contract_addr: CW20_ADDRESS.into(),
+                msg: to_json_binary(&Cw20ExecuteMsg::TransferFrom {
+                    owner: "mintu".into(),
+                    recipient: mock_env().contract.address.into(),
+                    amount: 500u128.into()
+                })
+                .unwrap(),
+                funds: vec![]
+            })
+        );
+        let staked = query_staked(deps.as_ref(), "mintu".into()).unwrap();
+        assert_eq!(staked.stake, Uint128::new(500));
+    }
+
+    #[test]
+    fn stake_for_beneficiary() {
+        let mut deps = mock_dependencies();
+        default_instantiate(deps.as_mut());
+
+        let transfer_from = |owner: &str, amount: u128| {
+            SubMsg::new(WasmMsg::Execute {
+                contract_addr: CW20_ADDRESS.into(),
+                msg: to_json_binary(&Cw20ExecuteMsg::TransferFrom {
+                    owner: owner.into(),
+                    recipient: mock_env().contract.address.into(),
+                    amount: amount.into(),
+                })
+                .unwrap(),
+                funds: vec![],
+            })
+        };
+
+        // the guild pays, the member gets the stake
+        let msg = ExecuteMsg::StakeFor {
+            beneficiary: "member".into(),
+            amount: 300u128.into(),
+        };
+        let res = execute(deps.as_mut(), mock_env(), mock_info("guild", &[]), msg).unwrap();
+        assert_eq!(res.messages, vec![transfer_from("guild", 300)]);
+        assert!(res.attributes.contains(&attr("from", "guild")));
+        assert!(res.attributes.contains(&attr("beneficiary", "member")));
+
+        let msg = ExecuteMsg::ReceiveAllowance(AllowanceReceiveMsg {
+            owner: "guild".into(),
+            amount: 200u128.into(),
+            msg: to_json_binary(&ReceiveMsg::Bond {
+                beneficiary: Some("member".into()),
+            })
+            .unwrap(),
+        });
+        let res = execute(deps.as_mut(), mock_env(), mock_info(CW20_ADDRESS, &[]), msg).unwrap();
+        assert_eq!(res.messages, vec![transfer_from("guild", 200)]);
+
+        let staked = query_staked(deps.as_ref(), "member".into()).unwrap();
+        assert_eq!(staked.stake, Uint128::new(500));
+        let staked = query_staked(deps.as_ref(), "guild".into()).unwrap();
+        assert_eq!(staked.stake, Uint128::zero());
+    }
+
+    #[test]
+    fn vesting_tokens_only_stake_for_holder() {
+        let mut deps = mock_dependencies();
+        default_instantiate(deps.as_mut());
+        deps.querier.update_wasm(|query| match query {
+            WasmQuery::Smart { contract_addr, msg } if contract_addr == CW20_ADDRESS => {
+                let TokenQueryMsg::Vesting { address } = from_json(msg).unwrap();
+                let locked = if address == "team" { 1_000 } else { 0 };
+                // the token answers with its whole vesting response
+                let res = format!(r#"{{"schedule":null,"total":"1000","locked":"{locked}"}}"#);
+                SystemResult::Ok(ContractResult::Ok(Binary::from(res.as_bytes())))
+            }
+            _ => panic!("unexpected query"),
+        });
+
+        let stake_for = ExecuteMsg::StakeFor {
+            beneficiary: "buyer".into(),
+            amount: 300u128.into(),
+        };
+        let err = execute(
+            deps.as_mut(),
+            mock_env(),
+            mock_info("team", &[]),
+            stake_for.clone(),
+        )
+        .unwrap_err();
+        assert_eq!(err, ContractError::VestingLocked {});
+
+        let bond = ExecuteMsg::ReceiveAllowance(AllowanceReceiveMsg {
+            owner: "team".into(),
+            amount: 300u128.into(),
+            msg: to_json_binary(&ReceiveMsg::Bond {
+                beneficiary: Some("buyer".into()),
+            })
+            .unwrap(),
+        });
+        let err = execute(
+            deps.as_mut(),
+            mock_env(),
+            mock_info(CW20_ADDRESS, &[]),
+            bond,
+        )
+        .unwrap_err();
+        assert_eq!(err, ContractError::VestingLocked {});
+        let staked = query_staked(deps.as_ref(), "buyer".into()).unwrap();
+        assert_eq!(staked.stake, Uint128::zero());
+
+        // the holder can still stake the locked tokens for itself
+        let msg = ExecuteMsg::StakeFor {
+            beneficiary: "team".into(),
+            amount: 300u128.into(),
+        };
+        execute(deps.as_mut(), mock_env(), mock_info("team", &[]), msg).unwrap();
+        let staked = query_staked(deps.as_ref(), "team".into()).unwrap();
+        assert_eq!(staked.stake, Uint128::new(300));
+
+        // and others without vesting can stake for anyone
+        execute(
+            deps.as_mut(),
+            mock_env(),
+            mock_info("guild", &[]),
+            stake_for,
+        )
+        .unwrap();
+        let staked = query_staked(deps.as_ref(), "buyer".into()).unwrap();
+        assert_eq!(staked.stake, Uint128::new(300));
+    }
+
+    #[test]
+    fn update_config() {
+        let mut deps = mock_dependencies();
+        default_instantiate(deps.as_mut());
+
+        let msg = ExecuteMsg::UpdateConfig {
+            reward_rate: Some(7u128.into()),
+            unbonding_period: Some(3600),
+            limits: None,
+        };
+        let err = execute(
+            deps.as_mut(),
+            mock_env(),
+            mock_info("mallory", &[]),
+            msg.clone(),
+        )
+        .unwrap_err();
+        assert_eq!(err, ContractError::Admin(AdminError::NotAdmin {}));
+
+        let res = execute(deps.as_mut(), mock_env(), mock_info(INIT_ADMIN, &[]), msg).unwrap();
+        assert_eq!(
+            res.attributes,
+            vec![
+                attr("action", "update_config"),
+                attr("reward_rate", "7"),
+                attr("unbonding_period", "3600"),
+                attr("sender", INIT_ADMIN),
+            ]
+        );
+
+        // a failed field leaves the others untouched
+        let msg = ExecuteMsg::UpdateConfig {
+            reward_rate: Some(9u128.into()),
+            unbonding_period: None,
+            limits: Some(StakeLimits {
+                min_stake: Uint128::new(100),
+                max_stake_per_address: Some(Uint128::new(50)),
+                max_total_stake: None,
+            }),
+        };
+        let err = execute(deps.as_mut(), mock_env(), mock_info(INIT_ADMIN, &[]), msg).unwrap_err();
+        assert_eq!(err, ContractError::InvalidStakeLimits {});
+
+        let config = query_config(deps.as_ref()).unwrap();
+        assert_eq!(
+            config,
+            ConfigResponse {
+                token: Addr::unchecked(CW20_ADDRESS),
+                reward_rate: Uint128::new(7),
+                unbonding_period: 3600,
+                min_stake: Uint128::zero(),
+                max_stake_per_address: None,
+                max_total_stake: None,
+            }
+        );
+
+        // the older messages change a single field
+        let msgs = vec![
+            ExecuteMsg::UpdateRewardRate {
+                rate: Uint128::new(9),
+            },
+            ExecuteMsg::UpdateStakeLimits {
+                limits: StakeLimits {
+                    min_stake: Uint128::new(10),
+                    max_stake_per_address: None,
+                    max_total_stake: Some(Uint128::new(1000)),
+                },
+            },
+        ];
+        for msg in msgs {
+            execute(deps.as_mut(), mock_env(), mock_info(INIT_ADMIN, &[]), msg).unwrap();
+        }
+        let config = query_config(deps.as_ref()).unwrap();
+        assert_eq!(config.reward_rate, Uint128::new(9));
+        assert_eq!(config.unbonding_period, 3600);
+        assert_eq!(config.min_stake, Uint128::new(10));
+        assert_eq!(config.max_total_stake, Some(Uint128::new(1000)));
+    }
 }
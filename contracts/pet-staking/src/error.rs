@@ -1,4 +1,4 @@
-use cosmwasm_std::StdError;
+use cosmwasm_std::{StdError, Uint128};
 use thiserror::Error;
 
 use cw_controllers::{AdminError, HookError};
@@ -37,4 +37,87 @@ pub enum ContractError {
 
     #[error("No data in ReceiveMsg")]
     NoData {},
+
+    #[error("Lock tiers need a non-zero duration and a multiplier of at least 1, without repeating durations")]
+    InvalidLockTier {},
+
+    #[error("No lock tier for a duration of {duration} seconds")]
+    UnknownLockDuration { duration: u64 },
+
+    #[error("Cannot withdraw stake which is still locked")]
+    StakeLocked {},
+
+    #[error("Withdraw penalty needs a non-zero holding time and a percentage of at most 100%")]
+    InvalidPenalty {},
+
+    #[error("Cannot compound more than {max} addresses at once")]
+    TooManyAddresses { max: u32 },
+
+    #[error("No receipt token is set for liquid staking")]
+    NoReceiptToken {},
+
+    #[error("Cannot change the receipt token while receipts are outstanding")]
+    ReceiptTokenInUse {},
+
+    #[error("Liquid stake is too small to mint a receipt")]
+    LiquidStakeTooSmall {},
+
+    #[error("Liquid pool has receipts outstanding but no stake left")]
+    LiquidPoolEmpty {},
+
+    #[error("Cannot stake for, transfer stake to or slash the liquid pool")]
+    LiquidPoolTarget {},
+
+    #[error("Cannot transfer stake to yourself")]
+    CannotTransferToSelf {},
+
+    #[error("Cannot approve yourself as operator")]
+    CannotApproveSelf {},
+
+    #[error("Cannot approve an operator with an expiration in the past")]
+    Expired {},
+
+    #[error("No such operator")]
+    OperatorNotFound {},
+
+    #[error("No treasury is set for slashing")]
+    SlashingNotConfigured {},
+
+    #[error("Slash percentage must be above 0% and at most 100%")]
+    InvalidSlashPercentage {},
+
+    #[error("Unbonding period cannot exceed {max} seconds")]
+    InvalidUnbondingPeriod { max: u64 },
+
+    #[error("Maximum stakes cannot be below the minimum stake")]
+    InvalidStakeLimits {},
+
+    #[error("Must stake at least {min}")]
+    StakeBelowMinimum { min: Uint128 },
+
+    #[error("Cannot stake more than {max} per address")]
+    StakeAboveMaximum { max: Uint128 },
+
+    #[error("Total stake cannot exceed {cap}")]
+    TotalStakeCapReached { cap: Uint128 },
+
+    #[error(
+        "Holders of locked vesting tokens cannot stake for or transfer stake to other addresses"
+    )]
+    VestingLocked {},
+
+    #[error("A stake transfer to another recipient is still pending")]
+    TransferPending {},
+
+    #[error("No stake transfer is pending")]
+    NoPendingTransfer {},
+
+    #[error("The stake transfer is still unbonding")]
+    TransferNotReleased {},
+
+    #[error("Cannot withdraw stake reserved for a pending transfer")]
+    StakeReserved {},
+
+    #[error("Cannot recover more than the {available} tokens not backing stakes")]
+    RecoverExceedsSurplus { available: Uint128 },
 }
@@ -1,6 +1,15 @@
 pub mod contract;
 mod error;
+pub mod limits;
+pub mod liquid;
+pub mod lockup;
 pub mod msg;
+pub mod operators;
+pub mod penalty;
+pub mod rewards;
+pub mod slashing;
 pub mod state;
+pub mod transfer;
+pub mod unbonding;
 
 pub use crate::error::ContractError;
@@ -0,0 +1,205 @@
+use cosmwasm_std::{StdResult, Storage, Uint128};
+
+use crate::error::ContractError;
+use crate::state::{StakeLimits, STAKE_LIMITS};
+
+pub fn validate_limits(limits: &StakeLimits) -> Result<(), ContractError> {
+    let below_min = |max: Option<Uint128>| max.is_some_and(|max| max < limits.min_stake);
+    if below_min(limits.max_stake_per_address) || below_min(limits.max_total_stake) {
+        return Err(ContractError::InvalidStakeLimits {});
+    }
+    Ok(())
+}
+
+pub fn load_limits(storage: &dyn Storage) -> StdResult<StakeLimits> {
+    Ok(STAKE_LIMITS.may_load(storage)?.unwrap_or_default())
+}
+
+/// Checks a new deposit of amount, which brings the stake of the staker and the total stake
+/// to the given values. The liquid pool passes no stake, as it is not bound by the maximum
+/// per address.
+pub fn assert_deposit(
+    storage: &dyn Storage,
+    amount: Uint128,
+    stake: Option<Uint128>,
+    total: Uint128,
+) -> Result<(), ContractError> {
+    let limits = load_limits(storage)?;
+    assert_min_stake(&limits, amount)?;
+    if let Some(stake) = stake {
+        assert_max_stake(&limits, stake)?;
+    }
+    match limits.max_total_stake {
+        Some(cap) if total > cap => Err(ContractError::TotalStakeCapReached { cap }),
+        _ => Ok(()),
+    }
+}
+
+/// Checks that amount is enough to be staked or moved on its own
+pub fn assert_min_stake(limits: &StakeLimits, amount: Uint128) -> Result<(), ContractError> {
+    if amount.is_zero() {
+        return Err(ContractError::NoFunds {});
+    }
+    if amount < limits.min_stake {
+        return Err(ContractError::StakeBelowMinimum {
+            min: limits.min_stake,
+        });
+    }
+    Ok(())
+}
+
+/// Checks the stake of a single address against the maximum per address
+pub fn assert_max_stake(limits: &StakeLimits, stake: Uint128) -> Result<(), ContractError> {
+    match limits.max_stake_per_address {
+        Some(max) if stake > max => Err(ContractError::StakeAboveMaximum { max }),
+        _ => Ok(()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use cosmwasm_std::testing::{mock_dependencies, mock_env, mock_info};
+    use cosmwasm_std::{Addr, DepsMut, Response};
+
+    use crate::contract::{execute, instantiate, query_config};
+    use crate::msg::{ExecuteMsg, InstantiateMsg};
+
+    const INIT_ADMIN: &str = "juan";
+    const CW20_ADDRESS: &str = "wasm1234567890";
+
+    fn do_instantiate(mut deps: DepsMut, limits: StakeLimits) {
+        let msg = InstantiateMsg {
+            addr: Addr::unchecked(CW20_ADDRESS),
+            admin: Some(INIT_ADMIN.into()),
+        };
+        let info = mock_info("creator", &[]);
+        instantiate(deps.branch(), mock_env(), info, msg).unwrap();
+
+        let msg = ExecuteMsg::UpdateConfig {
+            reward_rate: None,
+            unbonding_period: None,
+            limits: Some(limits),
+        };
+        execute(deps, mock_env(), mock_info(INIT_ADMIN, &[]), msg).unwrap();
+    }
+
+    fn limits() -> StakeLimits {
+        StakeLimits {
+            min_stake: Uint128::new(10),
+            max_stake_per_address: Some(Uint128::new(100)),
+            max_total_stake: Some(Uint128::new(150)),
+        }
+    }
+
+    fn stake(deps: DepsMut, staker: &str, amount: u128) -> Result<Response, ContractError> {
+        let msg = ExecuteMsg::Stake {
+            amount: amount.into(),
+            lock_duration: None,
+        };
+        execute(deps, mock_env(), mock_info(staker, &[]), msg)
+    }
+
+    #[test]
+    fn only_admin_sets_valid_limits() {
+        let mut deps = mock_dependencies();
+        do_instantiate(deps.as_mut(), limits());
+
+        let msg = ExecuteMsg::UpdateConfig {
+            reward_rate: None,
+            unbonding_period: None,
+            limits: Some(StakeLimits::default()),
+        };
+        let err = execute(deps.as_mut(), mock_env(), mock_info("mintu", &[]), msg).unwrap_err();
+        assert!(matches!(err, ContractError::Admin(_)));
+
+        let msg = ExecuteMsg::UpdateConfig {
+            reward_rate: None,
+            unbonding_period: None,
+            limits: Some(StakeLimits {
+                max_total_stake: Some(Uint128::new(5)),
+                ..limits()
+            }),
+        };
+        let err = execute(deps.as_mut(), mock_env(), mock_info(INIT_ADMIN, &[]), msg).unwrap_err();
+        assert_eq!(err, ContractError::InvalidStakeLimits {});
+
+        let config = query_config(deps.as_ref()).unwrap();
+        assert_eq!(config.token, Addr::unchecked(CW20_ADDRESS));
+        assert_eq!(config.min_stake, Uint128::new(10));
+        assert_eq!(config.max_stake_per_address, Some(Uint128::new(100)));
+        assert_eq!(config.max_total_stake, Some(Uint128::new(150)));
+    }
+
+    #[test]
+    fn stakes_stay_within_limits() {
+        let mut deps = mock_dependencies();
+        do_instantiate(deps.as_mut(), limits());
+
+        let err = stake(deps.as_mut(), "mintu", 0).unwrap_err();
+        assert_eq!(err, ContractError::NoFunds {});
+        let err = stake(deps.as_mut(), "mintu", 9).unwrap_err();
+        assert_eq!(
+            err,
+            ContractError::StakeBelowMinimum {
+                min: Uint128::new(10)
+            }
+        );
+        stake(deps.as_mut(), "mintu", 90).unwrap();
+        let err = stake(deps.as_mut(), "mintu", 11).unwrap_err();
+        assert_eq!(
+            err,
+            ContractError::StakeAboveMaximum {
+                max: Uint128::new(100)
+            }
+        );
+        stake(deps.as_mut(), "lisa", 60).unwrap();
+        let err = stake(deps.as_mut(), "mintu", 10).unwrap_err();
+        assert_eq!(
+            err,
+            ContractError::TotalStakeCapReached {
+                cap: Uint128::new(150)
+            }
+        );
+
+        // the maximum per address and the minimum stake also bound transferred stake
+        let transfer = |amount: u128| ExecuteMsg::TransferStake {
+            recipient: "mintu".into(),
+            amount: amount.into(),
+        };
+        let err = execute(
+            deps.as_mut(),
+            mock_env(),
+            mock_info("lisa", &[]),
+            transfer(20),
+        )
+        .unwrap_err();
+        assert_eq!(
+            err,
+            ContractError::StakeAboveMaximum {
+                max: Uint128::new(100)
+            }
+        );
+        let err = execute(
+            deps.as_mut(),
+            mock_env(),
+            mock_info("lisa", &[]),
+            transfer(9),
+        )
+        .unwrap_err();
+        assert_eq!(
+            err,
+            ContractError::StakeBelowMinimum {
+                min: Uint128::new(10)
+            }
+        );
+        execute(
+            deps.as_mut(),
+            mock_env(),
+            mock_info("lisa", &[]),
+            transfer(10),
+        )
+        .unwrap();
+    }
+}
@@ -0,0 +1,634 @@
+use cosmwasm_std::{
+    attr, from_json, to_json_binary, Addr, Decimal, Deps, DepsMut, Env, MessageInfo, Response,
+    StdError, StdResult, Storage, SubMsg, Uint128, WasmMsg,
+};
+use cw20::{Cw20ExecuteMsg, Cw20ReceiveMsg};
+use cw4::MemberDiff;
+
+use crate::error::ContractError;
+use crate::limits::assert_deposit;
+use crate::lockup::{member_hooks, update_weight};
+use crate::msg::{LiquidPoolResponse, ReceiptMsg};
+use crate::penalty::{collect_penalty, record_deposit, take_penalty};
+use crate::rewards::{compound_stake, query_rewards, take_from_reward_pool};
+use crate::state::{LiquidPool, ADMIN, BALANCES, CONFIG, LIQUID_POOL, REWARD_POOL, TOTAL};
+use crate::unbonding::pay_out;
+
+/// Receipts and stake the pool is priced as if it held on top of its own. Keeps a tiny first
+/// stake and the rewards it earns from pricing receipts so high that later stakes lose most
+/// of their value to rounding.
+const VIRTUAL_SHARES: u128 = 1_000;
+
+/// Sets the cw20 minted as receipt for liquid stakes. This contract must be its minter.
+/// Only the admin can do this, and only while no receipts are outstanding.
+pub fn execute_set_receipt_token(
+    deps: DepsMut,
+    info: MessageInfo,
+    address: String,
+) -> Result<Response, ContractError> {
+    ADMIN.assert_admin(deps.as_ref(), &info.sender)?;
+    let receipt = deps.api.addr_validate(&address)?;
+
+    if let Some(pool) = LIQUID_POOL.may_load(deps.storage)? {
+        if !pool.shares.is_zero() {
+            return Err(ContractError::ReceiptTokenInUse {});
+        }
+    }
+    let pool = LiquidPool {
+        receipt,
+        shares: Uint128::zero(),
+    };
+    LIQUID_POOL.save(deps.storage, &pool)?;
+
+    let res = Response::new().add_attributes(vec![
+        attr("action", "set_receipt_token"),
+        attr("receipt", address),
+        attr("sender", info.sender),
+    ]);
+    Ok(res)
+}
+
+/// Stakes amount for the liquid pool and mints receipts for the pool's share of it
+pub fn execute_liquid_stake(
+    deps: DepsMut,
+    env: Env,
+    info: MessageInfo,
+    amount: Uint128,
+) -> Result<Response, ContractError> {
+    let mut pool = LIQUID_POOL
+        .may_load(deps.storage)?
+        .ok_or(ContractError::NoReceiptToken {})?;
+    // receipts can be transferred, so vesting tokens cannot be staked for them
+    let config = CONFIG.load(deps.storage)?;
+    if !config.query_locked(&deps.querier, &info.sender).is_zero() {
+        return Err(ContractError::VestingLocked {});
+    }
+    let mut msgs = vec![];
+    let mut diffs = vec![];
+    let staked = compound_pool(deps.storage, &env, true, &mut msgs, &mut diffs)?;
+    let total = TOTAL.load(deps.storage)?;
+    assert_deposit(
+        deps.storage,
+        amount,
+        None,
+        total.checked_add(amount).map_err(StdError::overflow)?,
+    )?;
+
+    if !pool.shares.is_zero() && staked.is_zero() {
+        return Err(ContractError::LiquidPoolEmpty {});
+    }
+    let shares = amount.multiply_ratio(pool.shares + virtual_shares(), staked + virtual_shares());
+    if shares.is_zero() {
+        return Err(ContractError::LiquidStakeTooSmall {});
+    }
+    diffs.extend(change_pool_stake(
+        deps.storage,
+        &env,
+        staked.checked_add(amount).map_err(StdError::overflow)?,
+    )?);
+    record_deposit(deps.storage, &env.block.time, &env.contract.address, amount)?;
+    pool.shares = pool
+        .shares
+        .checked_add(shares)
+        .map_err(StdError::overflow)?;
+    LIQUID_POOL.save(deps.storage, &pool)?;
+
+    msgs.push(SubMsg::new(config.new_transfer_from_msg(
+        &info.sender,
+        &env.contract.address,
+        amount,
+    )?));
+    msgs.push(receipt_msg(
+        &pool.receipt,
+        Cw20ExecuteMsg::Mint {
+            recipient: info.sender.to_string(),
+            amount: shares,
+        },
+    )?);
+    msgs.extend(member_hooks(deps.storage, diffs)?);
+    let res = Response::new()
+        .add_submessages(msgs)
+        .add_attribute("action", "liquid_stake")
+        .add_attribute("from", info.sender)
+        .add_attribute("amount", amount)
+        .add_attribute("shares", shares);
+    Ok(res)
+}
+
+/// Receipts sent back with `Send` are burned and the stake they represent is paid out,
+/// after the unbonding period if one is set. The withdraw penalty applies to the deposits
+/// of the pool, which all liquid stakers share. Redeeming never mints, so rewards the reward
+/// pool does not cover stay pending for the pool.
+pub fn execute_receive(
+    deps: DepsMut,
+    env: Env,
+    info: MessageInfo,
+    wrapper: Cw20ReceiveMsg,
+) -> Result<Response, ContractError> {
+    let pool = LIQUID_POOL
+        .may_load(deps.storage)?
+        .ok_or(ContractError::NoReceiptToken {})?;
+    // only the receipt token can tell us about received receipts
+    if info.sender != pool.receipt {
+        return Err(ContractError::Unauthorized {});
+    }
+
+    let owner = deps.api.addr_validate(&wrapper.sender)?;
+    let msg: ReceiptMsg = from_json(&wrapper.msg)?;
+    match msg {
+        ReceiptMsg::Redeem {} => redeem(deps, env, pool, owner, wrapper.amount),
+    }
+}
+
+fn redeem(
+    deps: DepsMut,
+    env: Env,
+    mut pool: LiquidPool,
+    owner: Addr,
+    shares: Uint128,
+) -> Result<Response, ContractError> {
+    let mut msgs = vec![];
+    let mut diffs = vec![];
+    // redeeming must not depend on a mint the token may refuse, so only what the reward pool
+    // covers is compounded here. The rest stays pending for the pool.
+    let staked = compound_pool(deps.storage, &env, false, &mut msgs, &mut diffs)?;
+
+    let amount = shares.multiply_ratio(staked + virtual_shares(), pool.shares + virtual_shares());
+    let penalty = take_penalty(
+        deps.storage,
+        &env.block.time,
+        &env.contract.address,
+        staked,
+        amount,
+    )?;
+    diffs.extend(change_pool_stake(
+        deps.storage,
+        &env,
+        staked.checked_sub(amount).map_err(StdError::overflow)?,
+    )?);
+    pool.shares = pool
+        .shares
+        .checked_sub(shares)
+        .map_err(StdError::overflow)?;
+    LIQUID_POOL.save(deps.storage, &pool)?;
+
+    msgs.push(receipt_msg(
+        &pool.receipt,
+        Cw20ExecuteMsg::Burn { amount: shares },
+    )?);
+    msgs.extend(pay_out(deps.storage, &env, &owner, amount - penalty)?);
+    msgs.extend(collect_penalty(
+        deps.storage,
+        &env.block.time,
+        &env.contract.address,
+        penalty,
+    )?);
+    msgs.extend(member_hooks(deps.storage, diffs)?);
+    let res = Response::new()
+        .add_submessages(msgs)
+        .add_attribute("action", "redeem")
+        .add_attribute("to", owner)
+        .add_attribute("shares", shares)
+        .add_attribute("amount", amount)
+        .add_attribute("penalty", penalty);
+    Ok(res)
+}
+
+fn virtual_shares() -> Uint128 {
+    Uint128::new(VIRTUAL_SHARES)
+}
+
+// the liquid pool stakes as this contract, so its rewards raise the exchange rate. Without
+// `mint` only what the reward pool holds is compounded.
+fn compound_pool(
+    storage: &mut dyn Storage,
+    env: &Env,
+    mint: bool,
+    msgs: &mut Vec<SubMsg>,
+    diffs: &mut Vec<MemberDiff>,
+) -> Result<Uint128, ContractError> {
+    let pool_addr = &env.contract.address;
+    let max = if mint {
+        None
+    } else {
+        Some(REWARD_POOL.may_load(storage)?.unwrap_or_default())
+    };
+    let rewards = compound_stake(storage, env, pool_addr, max, diffs)?;
+    if !rewards.is_zero() {
+        let minted = rewards - take_from_reward_pool(storage, rewards)?;
+        if !minted.is_zero() {
+            let config = CONFIG.load(storage)?;
+            msgs.push(SubMsg::new(config.new_mint(pool_addr, minted)?));
+        }
+    }
+    Ok(BALANCES.may_load(storage, pool_addr)?.unwrap_or_default())
+}
+
+fn change_pool_stake(
+    storage: &mut dyn Storage,
+    env: &Env,
+    stake: Uint128,
+) -> StdResult<Option<MemberDiff>> {
+    let pool_addr = &env.contract.address;
+    let old = BALANCES.may_load(storage, pool_addr)?.unwrap_or_default();
+    BALANCES.save(storage, pool_addr, &stake)?;
+    let diff = update_weight(storage, env, pool_addr)?;
+    let total = TOTAL.load(storage)?;
+    TOTAL.save(storage, &(total.checked_add(stake)?.checked_sub(old)?))?;
+    Ok(diff)
+}
+
+fn receipt_msg(receipt: &Addr, msg: Cw20ExecuteMsg) -> StdResult<SubMsg> {
+    Ok(SubMsg::new(WasmMsg::Execute {
+        contract_addr: receipt.to_string(),
+        msg: to_json_binary(&msg)?,
+        funds: vec![],
+    }))
+}
+
+pub fn query_liquid_pool(deps: Deps, env: Env) -> StdResult<LiquidPoolResponse> {
+    let pool = match LIQUID_POOL.may_load(deps.storage)? {
+        Some(pool) => pool,
+        None => {
+            return Ok(LiquidPoolResponse {
+                receipt: None,
+                shares: Uint128::zero(),
+                staked: Uint128::zero(),
+                exchange_rate: Decimal::one(),
+            })
+        }
+    };
+    let pool_addr = env.contract.address.to_string();
+    let pending = query_rewards(deps, env.clone(), pool_addr)?.pending;
+    let staked = BALANCES
+        .may_load(deps.storage, &env.contract.address)?
+        .unwrap_or_default()
+        .checked_add(pending)?;
+    let exchange_rate =
+        Decimal::checked_from_ratio(staked + virtual_shares(), pool.shares + virtual_shares())
+            .map_err(|err| StdError::generic_err(err.to_string()))?;
+    Ok(LiquidPoolResponse {
+        receipt: Some(pool.receipt),
+        shares: pool.shares,
+        staked,
+        exchange_rate,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use cosmwasm_std::testing::{mock_dependencies, mock_env, mock_info};
+    use cosmwasm_std::{Binary, ContractResult, SystemResult, WasmQuery};
+
+    use crate::contract::{execute, instantiate};
+    use crate::msg::{ExecuteMsg, InstantiateMsg};
+    use crate::state::{PenaltyDestination, WithdrawPenalty};
+
+    const INIT_ADMIN: &str = "juan";
+    const CW20_ADDRESS: &str = "wasm1234567890";
+    const RECEIPT: &str = "receipt";
+
+    fn do_instantiate(mut deps: DepsMut) {
+        let msg = InstantiateMsg {
+            addr: Addr::unchecked(CW20_ADDRESS),
+            admin: Some(INIT_ADMIN.into()),
+        };
+        let info = mock_info("creator", &[]);
+        instantiate(deps.branch(), mock_env(), info, msg).unwrap();
+
+        let msg = ExecuteMsg::SetReceiptToken {
+            address: RECEIPT.into(),
+        };
+        execute(deps.branch(), mock_env(), mock_info(INIT_ADMIN, &[]), msg).unwrap();
+        let msg = ExecuteMsg::UpdateConfig {
+            reward_rate: Some(1000u128.into()),
+            unbonding_period: None,
+            limits: None,
+        };
+        execute(deps, mock_env(), mock_info(INIT_ADMIN, &[]), msg).unwrap();
+    }
+
+    fn liquid_stake(deps: DepsMut, env: &Env, staker: &str, amount: u128) -> Response {
+        let msg = ExecuteMsg::LiquidStake {
+            amount: amount.into(),
+        };
+        execute(deps, env.clone(), mock_info(staker, &[]), msg).unwrap()
+    }
+
+    fn redeem_msg(owner: &str, shares: u128) -> ExecuteMsg {
+        ExecuteMsg::Receive(Cw20ReceiveMsg {
+            sender: owner.into(),
+            amount: shares.into(),
+            msg: to_json_binary(&ReceiptMsg::Redeem {}).unwrap(),
+        })
+    }
+
+    #[test]
+    fn receipt_token_needs_admin_and_no_receipts() {
+        let mut deps = mock_dependencies();
+        do_instantiate(deps.as_mut());
+
+        let msg = ExecuteMsg::SetReceiptToken {
+            address: "other".into(),
+        };
+        let err = execute(
+            deps.as_mut(),
+            mock_env(),
+            mock_info("mintu", &[]),
+            msg.clone(),
+        )
+        .unwrap_err();
+        assert!(matches!(err, ContractError::Admin(_)));
+
+        liquid_stake(deps.as_mut(), &mock_env(), "mintu", 100);
+        let err = execute(deps.as_mut(), mock_env(), mock_info(INIT_ADMIN, &[]), msg).unwrap_err();
+        assert_eq!(err, ContractError::ReceiptTokenInUse {});
+    }
+
+    fn redeem(deps: DepsMut, env: &Env, owner: &str, shares: u128) -> Response {
+        execute(
+            deps,
+            env.clone(),
+            mock_info(RECEIPT, &[]),
+            redeem_msg(owner, shares),
+        )
+        .unwrap()
+    }
+
+    #[test]
+    fn vesting_tokens_cannot_be_liquid_staked() {
+        let mut deps = mock_dependencies();
+        do_instantiate(deps.as_mut());
+        deps.querier.update_wasm(|query| match query {
+            WasmQuery::Smart { contract_addr, .. } if contract_addr == CW20_ADDRESS => {
+                let res = Binary::from(br#"{"locked":"1000"}"#);
+                SystemResult::Ok(ContractResult::Ok(res))
+            }
+            _ => panic!("unexpected query"),
+        });
+
+        let msg = ExecuteMsg::LiquidStake {
+            amount: 100u128.into(),
+        };
+        let err = execute(deps.as_mut(), mock_env(), mock_info("team", &[]), msg).unwrap_err();
+        assert_eq!(err, ContractError::VestingLocked {});
+        assert!(LIQUID_POOL.load(&deps.storage).unwrap().shares.is_zero());
+    }
+
+    #[test]
+    fn tiny_first_stake_cannot_skim_later_stakes() {
+        let mut deps = mock_dependencies();
+        do_instantiate(deps.as_mut());
+        let mut env = mock_env();
+
+        // a single receipt earns all rewards while the pool is the only staker
+        liquid_stake(deps.as_mut(), &env, "mallory", 1);
+        env.block.time = env.block.time.plus_seconds(100);
+
+        let msg = ExecuteMsg::LiquidStake {
+            amount: 50u128.into(),
+        };
+        let err = execute(deps.as_mut(), env.clone(), mock_info("lisa", &[]), msg).unwrap_err();
+        assert_eq!(err, ContractError::LiquidStakeTooSmall {});
+
+        // the virtual shares take most of those rewards, and rounding costs little
+        liquid_stake(deps.as_mut(), &env, "lisa", 100_000);
+        let res = redeem(deps.as_mut(), &env, "mallory", 1);
+        assert!(res.attributes.contains(&attr("amount", "100")));
+        let res = redeem(deps.as_mut(), &env, "lisa", 991);
+        assert!(res.attributes.contains(&attr("amount", "99996")));
+    }
+
+    #[test]
+    fn pool_cannot_be_targeted() {
+        let mut deps = mock_dependencies();
+        do_instantiate(deps.as_mut());
+        let env = mock_env();
+        let contract = env.contract.address.to_string();
+        liquid_stake(deps.as_mut(), &env, "mintu", 100);
+        let msg = ExecuteMsg::UpdateSlashing {
+            slasher: None,
+            treasury: "treasury".into(),
+        };
+        execute(deps.as_mut(), env.clone(), mock_info(INIT_ADMIN, &[]), msg).unwrap();
+
+        let msg = ExecuteMsg::Stake {
+            amount: 100u128.into(),
+            lock_duration: None,
+        };
+        execute(deps.as_mut(), env.clone(), mock_info("mintu", &[]), msg).unwrap();
+        let msgs = [
+            (
+                "mintu",
+                ExecuteMsg::StakeFor {
+                    beneficiary: contract.clone(),
+                    amount: 100u128.into(),
+                },
+            ),
+            (
+                "mintu",
+                ExecuteMsg::TransferStake {
+                    recipient: contract.clone(),
+                    amount: 100u128.into(),
+                },
+            ),
+            (
+                INIT_ADMIN,
+                ExecuteMsg::Slash {
+                    address: contract,
+                    percentage: Decimal::percent(50),
+                },
+            ),
+        ];
+        for (sender, msg) in msgs {
+            let err = execute(deps.as_mut(), env.clone(), mock_info(sender, &[]), msg).unwrap_err();
+            assert_eq!(err, ContractError::LiquidPoolTarget {});
+        }
+    }
+
+    #[test]
+    fn early_redeem_pays_penalty() {
+        let mut deps = mock_dependencies();
+        do_instantiate(deps.as_mut());
+        let msg = ExecuteMsg::UpdateWithdrawPenalty {
+            penalty: Some(WithdrawPenalty {
+                min_hold: 24 * 60 * 60,
+                percentage: Decimal::percent(10),
+                destination: PenaltyDestination::Burn,
+            }),
+        };
+        execute(deps.as_mut(), mock_env(), mock_info(INIT_ADMIN, &[]), msg).unwrap();
+        let mut env = mock_env();
+
+        liquid_stake(deps.as_mut(), &env, "mintu", 100_000);
+        let res = redeem(deps.as_mut(), &env, "mintu", 40_000);
+        let config = CONFIG.load(&deps.storage).unwrap();
+        assert_eq!(
+            res.messages[1..],
+            [
+                SubMsg::new(
+                    config
+                        .clone()
+                        .new_transfer(&Addr::unchecked("mintu"), 36_000u128.into())
+                        .unwrap()
+                ),
+                SubMsg::new(config.new_burn(4_000u128.into()).unwrap()),
+            ]
+        );
+
+        // once the deposit of the pool matured, receipts are redeemed in full
+        env.block.time = env.block.time.plus_seconds(24 * 60 * 60);
+        let res = redeem(deps.as_mut(), &env, "mintu", 10_000);
+        assert!(res.attributes.contains(&attr("penalty", "0")));
+    }
+
+    #[test]
+    fn rewards_raise_exchange_rate() {
+        let mut deps = mock_dependencies();
+        do_instantiate(deps.as_mut());
+        let mut env = mock_env();
+        let contract = env.contract.address.to_string();
+
+        // first receipts are minted 1:1
+        let res = liquid_stake(deps.as_mut(), &env, "mintu", 100_000);
+        assert_eq!(
+            res.messages[1],
+            receipt_msg(
+                &Addr::unchecked(RECEIPT),
+                Cw20ExecuteMsg::Mint {
+                    recipient: "mintu".into(),
+                    amount: 100_000u128.into(),
+                }
+            )
+            .unwrap()
+        );
+
+        // the pool earns all rewards while it is the only staker
+        env.block.time = env.block.time.plus_seconds(100);
+        let pool = query_liquid_pool(deps.as_ref(), env.clone()).unwrap();
+        assert_eq!(pool.staked, Uint128::new(200_000));
+        assert_eq!(
+            pool.exchange_rate,
+            Decimal::from_ratio(201_000u128, 101_000u128)
+        );
+
+        // rewards are compounded first, so new receipts are minted at the new rate
+        let res = liquid_stake(deps.as_mut(), &env, "lisa", 100_000);
+        let config = CONFIG.load(&deps.storage).unwrap();
+        assert_eq!(
+            res.messages,
+            vec![
+                SubMsg::new(
+                    config
+                        .clone()
+                        .new_mint(&env.contract.address, 100_000u128.into())
+                        .unwrap()
+                ),
+                SubMsg::new(
+                    config
+                        .clone()
+                        .new_transfer_from_msg(
+                            &Addr::unchecked("lisa"),
+                            &env.contract.address,
+                            100_000u128.into()
+                        )
+                        .unwrap()
+                ),
+                receipt_msg(
+                    &Addr::unchecked(RECEIPT),
+                    Cw20ExecuteMsg::Mint {
+                        recipient: "lisa".into(),
+                        amount: 50_248u128.into(),
+                    }
+                )
+                .unwrap(),
+            ]
+        );
+        assert_eq!(TOTAL.load(&deps.storage).unwrap(), Uint128::new(300_000));
+        assert_eq!(
+            BALANCES
+                .load(&deps.storage, &Addr::unchecked(&contract))
+                .unwrap(),
+            Uint128::new(300_000)
+        );
+
+        // only the receipt token can redeem
+        let err = execute(
+            deps.as_mut(),
+            env.clone(),
+            mock_info(CW20_ADDRESS, &[]),
+            redeem_msg("mintu", 100_000),
+        )
+        .unwrap_err();
+        assert_eq!(err, ContractError::Unauthorized {});
+
+        let res = execute(
+            deps.as_mut(),
+            env.clone(),
+            mock_info(RECEIPT, &[]),
+            redeem_msg("mintu", 100_000),
+        )
+        .unwrap();
+        assert_eq!(
+            res.messages,
+            vec![
+                receipt_msg(
+                    &Addr::unchecked(RECEIPT),
+                    Cw20ExecuteMsg::Burn {
+                        amount: 100_000u128.into()
+                    }
+                )
+                .unwrap(),
+                SubMsg::new(
+                    config
+                        .new_transfer(&Addr::unchecked("mintu"), 199_010u128.into())
+                        .unwrap()
+                ),
+            ]
+        );
+        let pool = query_liquid_pool(deps.as_ref(), env).unwrap();
+        assert_eq!(pool.shares, Uint128::new(50_248));
+        assert_eq!(pool.staked, Uint128::new(100_990));
+        assert_eq!(TOTAL.load(&deps.storage).unwrap(), Uint128::new(100_990));
+    }
+
+    #[test]
+    fn redeem_never_mints() {
+        let mut deps = mock_dependencies();
+        do_instantiate(deps.as_mut());
+        let mut env = mock_env();
+
+        liquid_stake(deps.as_mut(), &env, "mintu", 100_000);
+        env.block.time = env.block.time.plus_seconds(100);
+
+        // the reward pool is empty, so the pending rewards are not compounded and no mint is
+        // needed to pay out the redemption
+        let res = redeem(deps.as_mut(), &env, "mintu", 50_000);
+        let config = CONFIG.load(&deps.storage).unwrap();
+        assert_eq!(
+            res.messages,
+            vec![
+                receipt_msg(
+                    &Addr::unchecked(RECEIPT),
+                    Cw20ExecuteMsg::Burn {
+                        amount: 50_000u128.into()
+                    }
+                )
+                .unwrap(),
+                SubMsg::new(
+                    config
+                        .new_transfer(&Addr::unchecked("mintu"), 50_000u128.into())
+                        .unwrap()
+                ),
+            ]
+        );
+
+        // the rewards stay pending for the remaining receipts
+        let pool = query_liquid_pool(deps.as_ref(), env).unwrap();
+        assert_eq!(pool.shares, Uint128::new(50_000));
+        assert_eq!(pool.staked, Uint128::new(150_000));
+        assert_eq!(TOTAL.load(&deps.storage).unwrap(), Uint128::new(50_000));
+    }
+}
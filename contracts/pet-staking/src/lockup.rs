@@ -0,0 +1,566 @@
+use cosmwasm_std::{
+    attr, Addr, Decimal, Deps, DepsMut, Env, MessageInfo, Order, Response, StdResult, Storage,
+    SubMsg, Timestamp, Uint128,
+};
+use cw4::{MemberChangedHookMsg, MemberDiff};
+
+use crate::error::ContractError;
+use crate::msg::{LockTiersResponse, PositionResponse, PositionsResponse};
+use crate::rewards::settle_rewards;
+use crate::state::{
+    LockTier, Position, ADMIN, BALANCES, EXPIRING_BOOST, HOOKS, LOCK_BUCKET, LOCK_TIERS, MEMBERS,
+    POSITIONS, WEIGHTS,
+};
+
+pub fn execute_update_lock_tiers(
+    deps: DepsMut,
+    info: MessageInfo,
+    tiers: Vec<LockTier>,
+) -> Result<Response, ContractError> {
+    ADMIN.assert_admin(deps.as_ref(), &info.sender)?;
+
+    for (i, tier) in tiers.iter().enumerate() {
+        if tier.duration == 0
+            || tier.multiplier < Decimal::one()
+            || tiers[..i].iter().any(|t| t.duration == tier.duration)
+        {
+            return Err(ContractError::InvalidLockTier {});
+        }
+    }
+    LOCK_TIERS.save(deps.storage, &tiers)?;
+
+    let res = Response::new().add_attributes(vec![
+        attr("action", "update_lock_tiers"),
+        attr("tiers", tiers.len().to_string()),
+        attr("sender", info.sender),
+    ]);
+    Ok(res)
+}
+
+/// When a lock of the given duration starting at time unlocks, rounded up to a full day
+pub fn unlock_time(time: &Timestamp, duration: u64) -> Timestamp {
+    let unlock = time.seconds().saturating_add(duration);
+    Timestamp::from_seconds(unlock.div_ceil(LOCK_BUCKET).saturating_mul(LOCK_BUCKET))
+}
+
+/// Locks amount of the staker's stake for the tier with the given duration, merging it into
+/// the staker's position which unlocks on the same day
+pub fn open_position(
+    storage: &mut dyn Storage,
+    time: &Timestamp,
+    staker: &Addr,
+    amount: Uint128,
+    duration: u64,
+) -> Result<u64, ContractError> {
+    let tier = LOCK_TIERS
+        .may_load(storage)?
+        .unwrap_or_default()
+        .into_iter()
+        .find(|tier| tier.duration == duration)
+        .ok_or(ContractError::UnknownLockDuration { duration })?;
+
+    let position = Position {
+        amount,
+        boost: amount * (tier.multiplier - Decimal::one()),
+        unlock_at: unlock_time(time, duration),
+    };
+    let id = position.unlock_at.seconds();
+    EXPIRING_BOOST.update(storage, id, |boost| -> StdResult<_> {
+        Ok(boost.unwrap_or_default().checked_add(position.boost)?)
+    })?;
+    add_position(storage, staker, position)?;
+    Ok(id)
+}
+
+fn add_position(storage: &mut dyn Storage, staker: &Addr, position: Position) -> StdResult<()> {
+    let id = position.unlock_at.seconds();
+    POSITIONS.update(storage, (staker, id), |merged| -> StdResult<_> {
+        Ok(match merged {
+            Some(merged) => Position {
+                amount: merged.amount.checked_add(position.amount)?,
+                boost: merged.boost.checked_add(position.boost)?,
+                unlock_at: position.unlock_at,
+            },
+            None => position,
+        })
+    })?;
+    Ok(())
+}
+
+fn load_positions(storage: &dyn Storage, staker: &Addr) -> StdResult<Vec<(u64, Position)>> {
+    POSITIONS
+        .prefix(staker)
+        .range(storage, None, None, Order::Ascending)
+        .collect()
+}
+
+/// Stake of the staker which cannot be withdrawn yet
+pub fn locked_amount(storage: &dyn Storage, staker: &Addr, time: &Timestamp) -> StdResult<Uint128> {
+    let mut locked = Uint128::zero();
+    for (_, position) in load_positions(storage, staker)? {
+        if position.unlock_at > *time {
+            locked = locked.checked_add(position.amount)?;
+        }
+    }
+    Ok(locked)
+}
+
+/// Moves the locked positions backing amount out of stake to the recipient. Unlocked stake
+/// is moved first, then the positions from oldest to newest, keeping their terms.
+pub fn transfer_positions(
+    storage: &mut dyn Storage,
+    time: &Timestamp,
+    from: &Addr,
+    to: &Addr,
+    stake: Uint128,
+    amount: Uint128,
+) -> StdResult<()> {
+    let locked = locked_amount(storage, from, time)?;
+    let mut remaining = amount.saturating_sub(stake.saturating_sub(locked));
+    for (id, mut position) in load_positions(storage, from)? {
+        if remaining.is_zero() {
+            break;
+        }
+        if position.unlock_at <= *time {
+            continue;
+        }
+        let take = remaining.min(position.amount);
+        let boost = position.boost.multiply_ratio(take, position.amount);
+        remaining -= take;
+        position.amount -= take;
+        position.boost -= boost;
+        if position.amount.is_zero() {
+            POSITIONS.remove(storage, (from, id));
+        } else {
+            POSITIONS.save(storage, (from, id), &position)?;
+        }
+        let moved = Position {
+            amount: take,
+            boost,
+            unlock_at: position.unlock_at,
+        };
+        add_position(storage, to, moved)?;
+    }
+    Ok(())
+}
+
+/// Takes percentage of every position. Rounds up, so locked stake never exceeds the stake
+/// left after slashing. Expired positions must be dropped before.
+pub fn slash_positions(
+    storage: &mut dyn Storage,
+    staker: &Addr,
+    percentage: Decimal,
+) -> StdResult<()> {
+    for (id, mut position) in load_positions(storage, staker)? {
+        let slashed_boost = position.boost.mul_ceil(percentage);
+        position.amount -= position.amount.mul_ceil(percentage);
+        position.boost -= slashed_boost;
+        if !slashed_boost.is_zero() {
+            let expiring = EXPIRING_BOOST.load(storage, id)?;
+            EXPIRING_BOOST.save(storage, id, &expiring.checked_sub(slashed_boost)?)?;
+        }
+        if position.amount.is_zero() {
+            POSITIONS.remove(storage, (staker, id));
+        } else {
+            POSITIONS.save(storage, (staker, id), &position)?;
+        }
+    }
+    Ok(())
+}
+
+// unlocked stake counts once, locked positions count with their multiplier
+fn weight(stake: Uint128, positions: &[(u64, Position)], time: &Timestamp) -> StdResult<Uint128> {
+    let mut weight = stake;
+    for (_, position) in positions {
+        if position.unlock_at > *time {
+            weight = weight.checked_add(position.boost)?;
+        }
+    }
+    Ok(weight)
+}
+
+/// Boosts of the staker's positions which unlocked by time, by unlock time in seconds
+pub fn expired_boosts(
+    storage: &dyn Storage,
+    staker: &Addr,
+    time: &Timestamp,
+) -> StdResult<Vec<(u64, Uint128)>> {
+    Ok(load_positions(storage, staker)?
+        .into_iter()
+        .filter(|(_, position)| position.unlock_at <= *time)
+        .map(|(id, position)| (id, position.boost))
+        .collect())
+}
+
+/// Drops positions which unlocked and stores the staker's current weight as member weight,
+/// settling the rewards earned at the previous weight. The boost of a dropped position
+/// earned rewards up to its unlock time. Called whenever the stake changes, returns the
+/// change of the member weight for `member_hooks`.
+pub fn update_weight(
+    storage: &mut dyn Storage,
+    env: &Env,
+    staker: &Addr,
+) -> StdResult<Option<MemberDiff>> {
+    let time = &env.block.time;
+    let expired = expired_boosts(storage, staker, time)?;
+    for (id, _) in &expired {
+        POSITIONS.remove(storage, (staker, *id));
+    }
+
+    let stake = BALANCES.may_load(storage, staker)?.unwrap_or_default();
+    let weight = weight(stake, &load_positions(storage, staker)?, time)?;
+    let old_points = MEMBERS.may_load(storage, staker)?;
+    let old_weight = staker_weight(storage, staker)?;
+    settle_rewards(storage, time, staker, old_weight, &expired, weight)?;
+    let points = if weight.is_zero() {
+        MEMBERS.remove(storage, staker, env.block.height)?;
+        WEIGHTS.remove(storage, staker);
+        None
+    } else {
+        // a weight too large for cw4 points must not keep the stake from changing
+        let points = u64::try_from(weight.u128()).unwrap_or(u64::MAX);
+        MEMBERS.save(storage, staker, &points, env.block.height)?;
+        WEIGHTS.save(storage, staker, &weight)?;
+        Some(points)
+    };
+
+    if points == old_points {
+        return Ok(None);
+    }
+    Ok(Some(MemberDiff::new(staker, old_points, points)))
+}
+
+/// Weight the staker's rewards were last settled at
+pub fn staker_weight(storage: &dyn Storage, staker: &Addr) -> StdResult<Uint128> {
+    match WEIGHTS.may_load(storage, staker)? {
+        Some(weight) => Ok(weight),
+        None => Ok(MEMBERS
+            .may_load(storage, staker)?
+            .unwrap_or_default()
+            .into()),
+    }
+}
+
+/// Notifies the hooks of the member weights changed by a message, in one
+/// `MemberChangedHookMsg`. Several changes of one member are joined into one.
+pub fn member_hooks(storage: &dyn Storage, diffs: Vec<MemberDiff>) -> StdResult<Vec<SubMsg>> {
+    let mut joined: Vec<MemberDiff> = vec![];
+    for diff in diffs {
+        match joined.iter_mut().find(|joined| joined.key == diff.key) {
+            Some(joined) => joined.new = diff.new,
+            None => joined.push(diff),
+        }
+    }
+    joined.retain(|diff| diff.old != diff.new);
+    if joined.is_empty() {
+        return Ok(vec![]);
+    }
+
+    let msg = MemberChangedHookMsg::new(joined);
+    HOOKS.prepare_hooks(storage, |hook| {
+        msg.clone().into_cosmos_msg(hook).map(SubMsg::new)
+    })
+}
+
+pub fn query_lock_tiers(deps: Deps) -> StdResult<LockTiersResponse> {
+    let tiers = LOCK_TIERS.may_load(deps.storage)?.unwrap_or_default();
+    Ok(LockTiersResponse { tiers })
+}
+
+pub fn query_positions(deps: Deps, env: Env, address: String) -> StdResult<PositionsResponse> {
+    let address = deps.api.addr_validate(&address)?;
+    let time = &env.block.time;
+    let positions: Vec<_> = load_positions(deps.storage, &address)?
+        .into_iter()
+        .filter(|(_, position)| position.unlock_at > *time)
+        .collect();
+
+    let stake = BALANCES
+        .may_load(deps.storage, &address)?
+        .unwrap_or_default();
+    let weight = weight(stake, &positions, time)?;
+    let positions = positions
+        .into_iter()
+        .map(|(id, position)| PositionResponse {
+            id,
+            amount: position.amount,
+            multiplier: Decimal::from_ratio(position.amount + position.boost, position.amount),
+            unlock_at: position.unlock_at,
+        })
+        .collect();
+    Ok(PositionsResponse { positions, weight })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use cosmwasm_std::testing::{mock_dependencies, mock_env, mock_info};
+
+    use crate::contract::{execute, instantiate};
+    use crate::msg::{ExecuteMsg, InstantiateMsg};
+
+    const INIT_ADMIN: &str = "juan";
+    const CW20_ADDRESS: &str = "wasm1234567890";
+    const DAY: u64 = 24 * 60 * 60;
+
+    fn do_instantiate(deps: DepsMut) {
+        let msg = InstantiateMsg {
+            addr: Addr::unchecked(CW20_ADDRESS),
+            admin: Some(INIT_ADMIN.into()),
+        };
+        let info = mock_info("creator", &[]);
+        instantiate(deps, mock_env(), info, msg).unwrap();
+    }
+
+    fn tiers() -> Vec<LockTier> {
+        vec![
+            LockTier {
+                duration: 30 * DAY,
+                multiplier: Decimal::percent(125),
+            },
+            LockTier {
+                duration: 90 * DAY,
+                multiplier: Decimal::percent(150),
+            },
+            LockTier {
+                duration: 180 * DAY,
+                multiplier: Decimal::percent(200),
+            },
+        ]
+    }
+
+    fn stake(deps: DepsMut, env: &Env, amount: u128, lock_duration: Option<u64>) {
+        let msg = ExecuteMsg::Stake {
+            amount: amount.into(),
+            lock_duration,
+        };
+        execute(deps, env.clone(), mock_info("mintu", &[]), msg).unwrap();
+    }
+
+    #[test]
+    fn only_admin_sets_valid_tiers() {
+        let mut deps = mock_dependencies();
+        do_instantiate(deps.as_mut());
+
+        let msg = ExecuteMsg::UpdateLockTiers { tiers: tiers() };
+        let err = execute(
+            deps.as_mut(),
+            mock_env(),
+            mock_info("mintu", &[]),
+            msg.clone(),
+        )
+        .unwrap_err();
+        assert!(matches!(err, ContractError::Admin(_)));
+        execute(deps.as_mut(), mock_env(), mock_info(INIT_ADMIN, &[]), msg).unwrap();
+        assert_eq!(query_lock_tiers(deps.as_ref()).unwrap().tiers, tiers());
+
+        for tier in [
+            LockTier {
+                duration: 0,
+                multiplier: Decimal::percent(125),
+            },
+            LockTier {
+                duration: 30 * DAY,
+                multiplier: Decimal::percent(90),
+            },
+            tiers()[0].clone(),
+        ] {
+            let mut invalid = tiers();
+            invalid.push(tier);
+            let msg = ExecuteMsg::UpdateLockTiers { tiers: invalid };
+            let err =
+                execute(deps.as_mut(), mock_env(), mock_info(INIT_ADMIN, &[]), msg).unwrap_err();
+            assert_eq!(err, ContractError::InvalidLockTier {});
+        }
+    }
+
+    #[test]
+    fn weight_changes_notify_hooks() {
+        let mut deps = mock_dependencies();
+        do_instantiate(deps.as_mut());
+        let admin_msgs = vec![
+            ExecuteMsg::UpdateLockTiers { tiers: tiers() },
+            ExecuteMsg::AddHook {
+                addr: "guild".into(),
+            },
+            ExecuteMsg::UpdateConfig {
+                reward_rate: Some(Uint128::one()),
+                unbonding_period: None,
+                limits: None,
+            },
+        ];
+        for msg in admin_msgs {
+            execute(deps.as_mut(), mock_env(), mock_info(INIT_ADMIN, &[]), msg).unwrap();
+        }
+        let hook_msg = |diff| {
+            SubMsg::new(
+                MemberChangedHookMsg::one(diff)
+                    .into_cosmos_msg("guild")
+                    .unwrap(),
+            )
+        };
+
+        let mut env = mock_env();
+        let msg = ExecuteMsg::Stake {
+            amount: 100u128.into(),
+            lock_duration: Some(30 * DAY),
+        };
+        let res = execute(deps.as_mut(), env.clone(), mock_info("mintu", &[]), msg).unwrap();
+        let diff = MemberDiff::new("mintu", None, Some(125));
+        assert_eq!(res.messages[1..], [hook_msg(diff)]);
+
+        // the lock running out and the withdrawal are reported as one change
+        env.block.time = unlock_time(&env.block.time, 30 * DAY);
+        let msg = ExecuteMsg::Withdraw {
+            amount: 40u128.into(),
+        };
+        let res = execute(deps.as_mut(), env.clone(), mock_info("mintu", &[]), msg).unwrap();
+        let diff = MemberDiff::new("mintu", Some(125), Some(60));
+        assert_eq!(res.messages[1..], [hook_msg(diff)]);
+
+        // claiming rewards leaves the weight as it is
+        env.block.time = env.block.time.plus_seconds(DAY);
+        let msg = ExecuteMsg::ClaimRewards {};
+        let res = execute(deps.as_mut(), env, mock_info("mintu", &[]), msg).unwrap();
+        assert_eq!(res.messages.len(), 1);
+    }
+
+    #[test]
+    fn locked_stake_is_boosted_until_expiry() {
+        let mut deps = mock_dependencies();
+        do_instantiate(deps.as_mut());
+        let msg = ExecuteMsg::UpdateLockTiers { tiers: tiers() };
+        execute(deps.as_mut(), mock_env(), mock_info(INIT_ADMIN, &[]), msg).unwrap();
+
+        let mut env = mock_env();
+        let msg = ExecuteMsg::Stake {
+            amount: 100u128.into(),
+            lock_duration: Some(7 * DAY),
+        };
+        let err = execute(deps.as_mut(), env.clone(), mock_info("mintu", &[]), msg).unwrap_err();
+        assert_eq!(
+            err,
+            ContractError::UnknownLockDuration { duration: 7 * DAY }
+        );
+
+        stake(deps.as_mut(), &env, 100, None);
+        stake(deps.as_mut(), &env, 200, Some(30 * DAY));
+        stake(deps.as_mut(), &env, 300, Some(180 * DAY));
+
+        let positions = query_positions(deps.as_ref(), env.clone(), "mintu".into()).unwrap();
+        assert_eq!(positions.positions.len(), 2);
+        let unlock_at = unlock_time(&env.block.time, 180 * DAY);
+        assert_eq!(
+            positions.positions[1],
+            PositionResponse {
+                id: unlock_at.seconds(),
+                amount: 300u128.into(),
+                multiplier: Decimal::percent(200),
+                unlock_at,
+            }
+        );
+        assert!(unlock_at >= env.block.time.plus_seconds(180 * DAY));
+        assert_eq!(unlock_at.seconds() % DAY, 0);
+        // 100 + 200 * 1.25 + 300 * 2
+        assert_eq!(positions.weight, Uint128::new(950));
+        let member = MEMBERS
+            .may_load(&deps.storage, &Addr::unchecked("mintu"))
+            .unwrap();
+        assert_eq!(member, Some(950));
+
+        // only the unlocked part can be withdrawn
+        let withdraw = |amount: u128| ExecuteMsg::Withdraw {
+            amount: amount.into(),
+        };
+        let err = execute(
+            deps.as_mut(),
+            env.clone(),
+            mock_info("mintu", &[]),
+            withdraw(101),
+        )
+        .unwrap_err();
+        assert_eq!(err, ContractError::StakeLocked {});
+        execute(
+            deps.as_mut(),
+            env.clone(),
+            mock_info("mintu", &[]),
+            withdraw(100),
+        )
+        .unwrap();
+
+        // at the end of the 30th day the first position unlocks and loses its boost
+        let first = positions.positions[0].id;
+        env.block.time = unlock_time(&env.block.time, 30 * DAY);
+        execute(
+            deps.as_mut(),
+            env.clone(),
+            mock_info("mintu", &[]),
+            withdraw(200),
+        )
+        .unwrap();
+        let positions = query_positions(deps.as_ref(), env, "mintu".into()).unwrap();
+        let ids: Vec<_> = positions.positions.iter().map(|p| p.id).collect();
+        assert_eq!(ids, vec![unlock_at.seconds()]);
+        assert_eq!(positions.weight, Uint128::new(600));
+        let member = MEMBERS
+            .may_load(&deps.storage, &Addr::unchecked("mintu"))
+            .unwrap();
+        assert_eq!(member, Some(600));
+        assert!(POSITIONS
+            .may_load(&deps.storage, (&Addr::unchecked("mintu"), first))
+            .unwrap()
+            .is_none());
+    }
+
+    #[test]
+    fn weights_beyond_cw4_points() {
+        let mut deps = mock_dependencies();
+        do_instantiate(deps.as_mut());
+        let msg = ExecuteMsg::UpdateLockTiers { tiers: tiers() };
+        execute(deps.as_mut(), mock_env(), mock_info(INIT_ADMIN, &[]), msg).unwrap();
+        let mut env = mock_env();
+
+        // 18 decimals: 100 tokens locked weigh more than u64::MAX
+        let amount = 100 * 10u128.pow(18);
+        stake(deps.as_mut(), &env, amount, Some(180 * DAY));
+        let mintu = Addr::unchecked("mintu");
+        let member = MEMBERS.may_load(&deps.storage, &mintu).unwrap();
+        assert_eq!(member, Some(u64::MAX));
+        assert_eq!(
+            WEIGHTS.load(&deps.storage, &mintu).unwrap(),
+            Uint128::new(2 * amount)
+        );
+
+        // the stake still changes once the lock ran out
+        env.block.time = unlock_time(&env.block.time, 180 * DAY);
+        let msg = ExecuteMsg::Withdraw {
+            amount: (amount - 10u128.pow(18)).into(),
+        };
+        execute(deps.as_mut(), env, mock_info("mintu", &[]), msg).unwrap();
+        let member = MEMBERS.may_load(&deps.storage, &mintu).unwrap();
+        assert_eq!(member, Some(10u64.pow(18)));
+    }
+
+    #[test]
+    fn locks_of_a_day_merge() {
+        let mut deps = mock_dependencies();
+        do_instantiate(deps.as_mut());
+        let msg = ExecuteMsg::UpdateLockTiers { tiers: tiers() };
+        execute(deps.as_mut(), mock_env(), mock_info(INIT_ADMIN, &[]), msg).unwrap();
+
+        // locks unlocking on the same day share one position and average their multipliers
+        let mut env = mock_env();
+        env.block.time = Timestamp::from_seconds(env.block.time.seconds() / DAY * DAY);
+        stake(deps.as_mut(), &env, 100, Some(90 * DAY));
+        env.block.time = env.block.time.plus_seconds(60 * DAY - 60);
+        stake(deps.as_mut(), &env, 100, Some(30 * DAY));
+
+        let positions = query_positions(deps.as_ref(), env, "mintu".into()).unwrap();
+        assert_eq!(positions.positions.len(), 1);
+        assert_eq!(positions.positions[0].amount, Uint128::new(200));
+        assert_eq!(positions.positions[0].multiplier, Decimal::permille(1375));
+        // 100 * 1.5 + 100 * 1.25
+        assert_eq!(positions.weight, Uint128::new(275));
+    }
+}
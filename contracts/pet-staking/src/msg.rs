@@ -1,5 +1,10 @@
 use cosmwasm_schema::{cw_serde, QueryResponses};
-use cosmwasm_std::{Addr, Uint128};
+use cosmwasm_std::{Addr, Binary, Decimal, Timestamp, Uint128};
+use cw20::Cw20ReceiveMsg;
+use cw_utils::Expiration;
+use serde::Deserialize;
+
+use crate::state::{LockTier, PenaltyDestination, PendingTransfer, StakeLimits, WithdrawPenalty};
 pub use cw_controllers::ClaimsResponse;
 
 #[cw_serde]
@@ -13,16 +18,196 @@ pub struct InstantiateMsg {
 
 #[cw_serde]
 pub enum ExecuteMsg {
-    Stake { amount: Uint128 },
-    Withdraw { amount: Uint128 },
-    Mint { amount: Uint128 },
-    UpdateAdmin { admin: Option<String> },
+    /// Stakes amount tokens pulled with `TransferFrom`. If lock_duration is set, the stake
+    /// is locked for that many seconds, rounded up to the end of the day, and weighted by
+    /// the multiplier of the matching tier.
+    Stake {
+        amount: Uint128,
+        lock_duration: Option<u64>,
+    },
+    /// Stakes amount tokens pulled from the sender with `TransferFrom` for the beneficiary.
+    /// Senders with locked vesting tokens can only stake for themselves.
+    StakeFor {
+        beneficiary: String,
+        amount: Uint128,
+    },
+    /// Withdraws amount of the sender's stake. Deposits held shorter than the minimum holding
+    /// time of the withdraw penalty are charged the penalty. If an unbonding period is set,
+    /// the tokens can be claimed with `Claim` once it passed.
+    Withdraw {
+        amount: Uint128,
+    },
+    /// Pays out the sender's withdrawn stake which finished unbonding.
+    Claim {},
+    /// Lets the operator stake, withdraw and claim rewards for the sender until it expires
+    ApproveOperator {
+        operator: String,
+        expires: Option<Expiration>,
+    },
+    RevokeOperator {
+        operator: String,
+    },
+    /// Like `Stake`, pulling the tokens from the owner. Only the owner's operators can do this.
+    StakeFrom {
+        owner: String,
+        amount: Uint128,
+        lock_duration: Option<u64>,
+    },
+    /// Like `Withdraw`, paying out to the owner. Only the owner's operators can do this.
+    WithdrawFrom {
+        owner: String,
+        amount: Uint128,
+    },
+    /// Like `ClaimRewards`, paying out to the owner. Only the owner's operators can do this.
+    ClaimRewardsFrom {
+        owner: String,
+    },
+    /// Like `Claim`, paying out to the owner. Only the owner's operators can do this.
+    ClaimFrom {
+        owner: String,
+    },
+    /// Adds a contract notified with `MemberChangedHookMsg` whenever a member weight changes,
+    /// by staking, withdrawing, transfers, compounding, slashing or a lock running out.
+    /// Only the admin can do this.
+    AddHook {
+        addr: String,
+    },
+    RemoveHook {
+        addr: String,
+    },
+    /// Sets who can slash besides the admin and where slashed tokens go.
+    /// Only the admin can do this.
+    UpdateSlashing {
+        slasher: Option<String>,
+        treasury: String,
+    },
+    /// Takes percentage of the address' stake and pending claims and sends it to the
+    /// treasury. Only the admin or the slasher can do this.
+    Slash {
+        address: String,
+        percentage: Decimal,
+    },
+    /// Moves amount of the sender's stake to the recipient, together with its locks,
+    /// deposit times and share of the pending rewards. Amount must reach the minimum stake.
+    /// Senders with locked vesting tokens cannot transfer stake. If an unbonding period is
+    /// set, the stake stays with the sender, reserved for the recipient, until the period
+    /// passed and `CompleteTransferStake` moves it. A sender can have one pending transfer,
+    /// adding to it waits the whole period again.
+    TransferStake {
+        recipient: String,
+        amount: Uint128,
+    },
+    /// Moves the stake of the pending transfer of the sender once the unbonding period
+    /// passed. Anyone can do this.
+    CompleteTransferStake {
+        sender: String,
+    },
+    /// Drops the pending transfer of the sender.
+    CancelTransferStake {},
+    Mint {
+        amount: Uint128,
+    },
+    UpdateAdmin {
+        admin: Option<String>,
+    },
+    /// Changes the given parameters, leaving the others as they are. The staked token
+    /// cannot be changed. Only the admin can do this.
+    UpdateConfig {
+        /// Rewards minted to stakers per second, split by their weight
+        reward_rate: Option<Uint128>,
+        /// Seconds withdrawn stake waits before it can be claimed
+        unbonding_period: Option<u64>,
+        /// Minimum stake and maximum stakes per address and in total
+        limits: Option<StakeLimits>,
+    },
+    /// `UpdateConfig` setting only the stake limits, kept for existing clients
+    UpdateStakeLimits {
+        limits: StakeLimits,
+    },
+    /// `UpdateConfig` setting only the reward rate, kept for existing clients
+    UpdateRewardRate {
+        rate: Uint128,
+    },
+    /// Replaces the lock tiers stakes can choose from. Only the admin can do this.
+    UpdateLockTiers {
+        tiers: Vec<LockTier>,
+    },
+    /// Sets or clears the penalty on early withdrawals. Only the admin can do this.
+    UpdateWithdrawPenalty {
+        penalty: Option<WithdrawPenalty>,
+    },
+    /// Pays the pending rewards of the sender to the sender, out of the collected penalties
+    /// first and minted for the rest.
+    ClaimRewards {},
+    /// Opts the sender in or out of having their rewards staked by `Compound`.
+    SetAutoCompound {
+        enabled: bool,
+    },
+    /// Stakes the pending rewards of the given addresses which opted in to auto-compounding.
+    /// Anyone can call this.
+    Compound {
+        addresses: Vec<String>,
+    },
+    /// Sets the cw20 minted as receipt for liquid stakes. Only the admin can do this.
+    SetReceiptToken {
+        address: String,
+    },
+    /// Stakes amount tokens pulled with `TransferFrom` in the liquid pool and mints receipts
+    /// at the current exchange rate. Rewards of the pool raise the exchange rate.
+    /// The pool stakes as this contract, which is a member with the pool's weight. It is not
+    /// bound by the maximum stake per address, and nobody can stake for, transfer stake to
+    /// or slash it. Senders with locked vesting tokens cannot stake in the pool.
+    LiquidStake {
+        amount: Uint128,
+    },
+    /// Receipts sent back with `Send` and `ReceiptMsg::Redeem` are burned and paid out
+    /// in staked tokens at the current exchange rate. The withdraw penalty is charged on
+    /// the deposits of the pool, oldest first.
+    Receive(Cw20ReceiveMsg),
+    /// Called by the staked token after `owner` granted this contract an allowance with
+    /// `IncreaseAllowanceAndCall`. `msg` must be a `ReceiveMsg`.
+    ReceiveAllowance(AllowanceReceiveMsg),
+    /// Sends cw20 tokens held by this contract to the recipient. For the staked token,
+    /// only the surplus above the total staked can be recovered. Only the admin can do this.
+    RecoverForeignCw20 {
+        token: String,
+        recipient: String,
+        amount: Uint128,
+    },
+}
+
+/// Sent by the staked token when an allowance is granted to this contract
+#[cw_serde]
+pub struct AllowanceReceiveMsg {
+    pub owner: String,
+    pub amount: Uint128,
+    pub msg: Binary,
+}
+
+/// Vesting query of the staked token
+#[cw_serde]
+pub enum TokenQueryMsg {
+    Vesting { address: String },
+}
+
+/// Part of the staked token's vesting response read by this contract
+#[derive(Deserialize)]
+pub struct TokenVestingResponse {
+    /// Tokens which cannot be transferred yet
+    pub locked: Uint128,
 }
 
 #[cw_serde]
 pub enum ReceiveMsg {
-    /// Only valid cw20 message is to bond the tokens
-    Bond {},
+    /// Only valid cw20 message is to bond the tokens, for the beneficiary if given. Owners
+    /// with locked vesting tokens can only bond for themselves.
+    Bond { beneficiary: Option<String> },
+}
+
+#[cw_serde]
+pub enum ReceiptMsg {
+    /// Pays out the stake represented by the received receipts
+    Redeem {},
 }
 
 #[cw_serde]
@@ -33,8 +218,56 @@ pub enum QueryMsg {
     Staked { address: String },
     #[returns(Addr)]
     Admin {},
+    /// Returns the staked token, reward rate, unbonding period and stake limits.
+    #[returns(ConfigResponse)]
+    Config {},
     #[returns(Uint128)]
     TotalStaked {},
+    /// Returns the staked tokens held for stakes, unbonding claims and the reward pool.
+    /// Only tokens above this can be recovered.
+    #[returns(HeldResponse)]
+    Held {},
+    /// Returns the withdrawn stake of this address which is unbonding or can be claimed.
+    #[returns(ClaimsResponse)]
+    Claims { address: String },
+    /// Returns the withdraw penalty and the penalties collected in the reward pool.
+    #[returns(WithdrawPenaltyResponse)]
+    WithdrawPenalty {},
+    /// Returns what withdrawing amount of this address' stake would pay out and cost.
+    #[returns(SimulateWithdrawResponse)]
+    SimulateWithdraw { address: String, amount: Uint128 },
+    /// Returns the pending rewards of this address and the current reward rate.
+    #[returns(RewardsResponse)]
+    Rewards { address: String },
+    /// Returns the receipt token, the stake of the liquid pool and its exchange rate.
+    #[returns(LiquidPoolResponse)]
+    LiquidPool {},
+    /// Returns the operators of this owner which did not expire.
+    #[returns(OperatorsResponse)]
+    Operators { owner: String },
+    /// Returns the contracts notified of membership changes.
+    #[returns(cw_controllers::HooksResponse)]
+    Hooks {},
+    /// Returns the slasher and the treasury receiving slashed tokens.
+    #[returns(SlashingResponse)]
+    Slashing {},
+    /// Returns the lock tiers stakes can choose from.
+    #[returns(LockTiersResponse)]
+    LockTiers {},
+    /// Returns the locked positions of this address and its weight.
+    #[returns(PositionsResponse)]
+    Positions { address: String },
+    /// Returns the stake transfer of this address waiting out the unbonding period.
+    #[returns(PendingTransferResponse)]
+    PendingTransfer { address: String },
+    /// Sums stakes page by page and compares the result with the total staked.
+    /// Pass the `running_sum` and `last_address` of the previous page to continue.
+    #[returns(VerifyStakeResponse)]
+    VerifyStake {
+        start_after: Option<String>,
+        limit: Option<u32>,
+        running_sum: Option<Uint128>,
+    },
 }
 
 #[cw_serde]
@@ -46,3 +279,105 @@ pub struct StakedResponse {
 pub struct TotalStakeResponse {
     pub stake: Uint128,
 }
+
+#[cw_serde]
+pub struct HeldResponse {
+    pub amount: Uint128,
+}
+
+#[cw_serde]
+pub struct VerifyStakeResponse {
+    pub total: Uint128,
+    /// Sum of all stakes up to and including `last_address`
+    pub running_sum: Uint128,
+    pub last_address: Option<String>,
+    /// Set once every stake has been summed
+    pub complete: bool,
+    /// Set if the stakes are known not to add up to the total
+    pub mismatch: bool,
+}
+
+#[cw_serde]
+pub struct PendingTransferResponse {
+    pub transfer: Option<PendingTransfer>,
+}
+
+#[cw_serde]
+pub struct LockTiersResponse {
+    pub tiers: Vec<LockTier>,
+}
+
+#[cw_serde]
+pub struct PositionResponse {
+    /// The unlock time in seconds, locks which unlock on the same day share a position
+    pub id: u64,
+    pub amount: Uint128,
+    /// Average multiplier of the locks merged into this position
+    pub multiplier: Decimal,
+    pub unlock_at: Timestamp,
+}
+
+#[cw_serde]
+pub struct PositionsResponse {
+    pub positions: Vec<PositionResponse>,
+    /// Stake weighted by the multipliers of the positions which are still locked
+    pub weight: Uint128,
+}
+
+#[cw_serde]
+pub struct WithdrawPenaltyResponse {
+    pub penalty: Option<WithdrawPenalty>,
+    pub reward_pool: Uint128,
+}
+
+#[cw_serde]
+pub struct SimulateWithdrawResponse {
+    pub received: Uint128,
+    pub penalty: Uint128,
+    pub destination: Option<PenaltyDestination>,
+}
+
+#[cw_serde]
+pub struct RewardsResponse {
+    pub pending: Uint128,
+    pub auto_compound: bool,
+    pub rate: Uint128,
+}
+
+#[cw_serde]
+pub struct LiquidPoolResponse {
+    pub receipt: Option<Addr>,
+    pub shares: Uint128,
+    /// Stake of the pool including its pending rewards
+    pub staked: Uint128,
+    /// Staked tokens paid out per receipt
+    pub exchange_rate: Decimal,
+}
+
+#[cw_serde]
+pub struct OperatorResponse {
+    pub operator: String,
+    pub expires: Expiration,
+}
+
+#[cw_serde]
+pub struct OperatorsResponse {
+    pub operators: Vec<OperatorResponse>,
+}
+
+#[cw_serde]
+pub struct SlashingResponse {
+    pub slasher: Option<Addr>,
+    pub treasury: Option<Addr>,
+}
+
+#[cw_serde]
+pub struct ConfigResponse {
+    /// The staked token
+    pub token: Addr,
+    pub reward_rate: Uint128,
+    pub unbonding_period: u64,
+    pub min_stake: Uint128,
+    pub max_stake_per_address: Option<Uint128>,
+    pub max_total_stake: Option<Uint128>,
+}
@@ -0,0 +1,236 @@
+use cosmwasm_std::{attr, Addr, Deps, DepsMut, Env, MessageInfo, Order, Response, StdResult};
+use cw_utils::Expiration;
+
+use crate::error::ContractError;
+use crate::msg::{OperatorResponse, OperatorsResponse};
+use crate::state::OPERATORS;
+
+pub fn execute_approve_operator(
+    deps: DepsMut,
+    env: Env,
+    info: MessageInfo,
+    operator: String,
+    expires: Option<Expiration>,
+) -> Result<Response, ContractError> {
+    let operator_addr = deps.api.addr_validate(&operator)?;
+    if operator_addr == info.sender {
+        return Err(ContractError::CannotApproveSelf {});
+    }
+    let expires = expires.unwrap_or_default();
+    if expires.is_expired(&env.block) {
+        return Err(ContractError::Expired {});
+    }
+    OPERATORS.save(deps.storage, (&info.sender, &operator_addr), &expires)?;
+
+    let res = Response::new().add_attributes(vec![
+        attr("action", "approve_operator"),
+        attr("owner", info.sender),
+        attr("operator", operator),
+        attr("expires", expires.to_string()),
+    ]);
+    Ok(res)
+}
+
+pub fn execute_revoke_operator(
+    deps: DepsMut,
+    info: MessageInfo,
+    operator: String,
+) -> Result<Response, ContractError> {
+    let operator_addr = deps.api.addr_validate(&operator)?;
+    if !OPERATORS.has(deps.storage, (&info.sender, &operator_addr)) {
+        return Err(ContractError::OperatorNotFound {});
+    }
+    OPERATORS.remove(deps.storage, (&info.sender, &operator_addr));
+
+    let res = Response::new().add_attributes(vec![
+        attr("action", "revoke_operator"),
+        attr("owner", info.sender),
+        attr("operator", operator),
+    ]);
+    Ok(res)
+}
+
+/// Returns the owner if the sender is the owner or one of its operators
+pub fn assert_operator(
+    deps: Deps,
+    env: &Env,
+    owner: String,
+    sender: &Addr,
+) -> Result<Addr, ContractError> {
+    let owner = deps.api.addr_validate(&owner)?;
+    if owner == sender {
+        return Ok(owner);
+    }
+    match OPERATORS.may_load(deps.storage, (&owner, sender))? {
+        Some(expires) if !expires.is_expired(&env.block) => Ok(owner),
+        _ => Err(ContractError::Unauthorized {}),
+    }
+}
+
+pub fn query_operators(deps: Deps, env: Env, owner: String) -> StdResult<OperatorsResponse> {
+    let owner = deps.api.addr_validate(&owner)?;
+    let operators = OPERATORS
+        .prefix(&owner)
+        .range(deps.storage, None, None, Order::Ascending)
+        .filter(|item| match item {
+            Ok((_, expires)) => !expires.is_expired(&env.block),
+            Err(_) => true,
+        })
+        .map(|item| {
+            item.map(|(operator, expires)| OperatorResponse {
+                operator: operator.into(),
+                expires,
+            })
+        })
+        .collect::<StdResult<_>>()?;
+    Ok(OperatorsResponse { operators })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use cosmwasm_std::testing::{mock_dependencies, mock_env, mock_info};
+    use cosmwasm_std::{SubMsg, Uint128};
+
+    use crate::contract::{execute, instantiate, query_staked};
+    use crate::msg::{ExecuteMsg, InstantiateMsg};
+    use crate::state::CONFIG;
+
+    const INIT_ADMIN: &str = "juan";
+    const CW20_ADDRESS: &str = "wasm1234567890";
+
+    fn do_instantiate(deps: DepsMut) {
+        let msg = InstantiateMsg {
+            addr: Addr::unchecked(CW20_ADDRESS),
+            admin: Some(INIT_ADMIN.into()),
+        };
+        let info = mock_info("creator", &[]);
+        instantiate(deps, mock_env(), info, msg).unwrap();
+    }
+
+    fn approve(deps: DepsMut, operator: &str, expires: Option<Expiration>) {
+        let msg = ExecuteMsg::ApproveOperator {
+            operator: operator.into(),
+            expires,
+        };
+        execute(deps, mock_env(), mock_info("owner", &[]), msg).unwrap();
+    }
+
+    #[test]
+    fn approve_and_revoke_operators() {
+        let mut deps = mock_dependencies();
+        do_instantiate(deps.as_mut());
+        let env = mock_env();
+
+        approve(deps.as_mut(), "custodian", None);
+        approve(
+            deps.as_mut(),
+            "expired",
+            Some(Expiration::AtHeight(env.block.height + 1)),
+        );
+        let msg = ExecuteMsg::ApproveOperator {
+            operator: "late".into(),
+            expires: Some(Expiration::AtHeight(env.block.height)),
+        };
+        let err = execute(deps.as_mut(), env.clone(), mock_info("owner", &[]), msg).unwrap_err();
+        assert_eq!(err, ContractError::Expired {});
+        let msg = ExecuteMsg::ApproveOperator {
+            operator: "owner".into(),
+            expires: None,
+        };
+        let err = execute(deps.as_mut(), env.clone(), mock_info("owner", &[]), msg).unwrap_err();
+        assert_eq!(err, ContractError::CannotApproveSelf {});
+
+        // expired operators are hidden
+        let mut later = env.clone();
+        later.block.height += 1;
+        assert_eq!(
+            query_operators(deps.as_ref(), later, "owner".into()).unwrap(),
+            OperatorsResponse {
+                operators: vec![OperatorResponse {
+                    operator: "custodian".into(),
+                    expires: Expiration::Never {},
+                }]
+            }
+        );
+
+        let msg = ExecuteMsg::RevokeOperator {
+            operator: "custodian".into(),
+        };
+        execute(
+            deps.as_mut(),
+            env.clone(),
+            mock_info("owner", &[]),
+            msg.clone(),
+        )
+        .unwrap();
+        let err = execute(deps.as_mut(), env.clone(), mock_info("owner", &[]), msg).unwrap_err();
+        assert_eq!(err, ContractError::OperatorNotFound {});
+        let operators = query_operators(deps.as_ref(), env, "owner".into()).unwrap();
+        assert_eq!(operators.operators.len(), 1);
+    }
+
+    #[test]
+    fn operator_manages_stake_for_owner() {
+        let mut deps = mock_dependencies();
+        do_instantiate(deps.as_mut());
+        let config = CONFIG.load(&deps.storage).unwrap();
+        let owner = Addr::unchecked("owner");
+
+        let stake_from = ExecuteMsg::StakeFrom {
+            owner: "owner".into(),
+            amount: 500u128.into(),
+            lock_duration: None,
+        };
+        let err = execute(
+            deps.as_mut(),
+            mock_env(),
+            mock_info("custodian", &[]),
+            stake_from.clone(),
+        )
+        .unwrap_err();
+        assert_eq!(err, ContractError::Unauthorized {});
+
+        approve(deps.as_mut(), "custodian", None);
+        let res = execute(
+            deps.as_mut(),
+            mock_env(),
+            mock_info("custodian", &[]),
+            stake_from,
+        )
+        .unwrap();
+        assert_eq!(
+            res.messages,
+            vec![SubMsg::new(
+                config
+                    .clone()
+                    .new_transfer_from_msg(&owner, &mock_env().contract.address, 500u128.into())
+                    .unwrap()
+            )]
+        );
+        let staked = query_staked(deps.as_ref(), "owner".into()).unwrap();
+        assert_eq!(staked.stake, Uint128::new(500));
+
+        // withdrawals always pay out to the owner
+        let msg = ExecuteMsg::WithdrawFrom {
+            owner: "owner".into(),
+            amount: 200u128.into(),
+        };
+        let res = execute(deps.as_mut(), mock_env(), mock_info("custodian", &[]), msg).unwrap();
+        assert_eq!(
+            res.messages,
+            vec![SubMsg::new(
+                config.new_transfer(&owner, 200u128.into()).unwrap()
+            )]
+        );
+        let staked = query_staked(deps.as_ref(), "owner".into()).unwrap();
+        assert_eq!(staked.stake, Uint128::new(300));
+
+        let msg = ExecuteMsg::ClaimRewardsFrom {
+            owner: "owner".into(),
+        };
+        let err = execute(deps.as_mut(), mock_env(), mock_info("custodian", &[]), msg).unwrap_err();
+        assert_eq!(err, ContractError::NothingToClaim {});
+    }
+}
@@ -0,0 +1,553 @@
+use cosmwasm_std::{
+    attr, Addr, Decimal, Deps, DepsMut, Env, MessageInfo, Order, Response, StdError, StdResult,
+    Storage, SubMsg, Timestamp, Uint128,
+};
+
+use crate::error::ContractError;
+use crate::msg::{SimulateWithdrawResponse, WithdrawPenaltyResponse};
+use crate::rewards::add_to_reward_pool;
+use crate::state::{
+    Deposit, PenaltyDestination, WithdrawPenalty, ADMIN, BALANCES, CONFIG, DEPOSITS,
+    DEPOSIT_BUCKET, REWARD_POOL, WITHDRAW_PENALTY,
+};
+
+type Deposits = Vec<(u64, Deposit)>;
+// what is left of each deposit touched by a withdrawal, and how much was taken from it
+type DepositUpdates = Vec<(u64, Deposit, Uint128)>;
+
+pub fn execute_update_withdraw_penalty(
+    deps: DepsMut,
+    info: MessageInfo,
+    penalty: Option<WithdrawPenalty>,
+) -> Result<Response, ContractError> {
+    ADMIN.assert_admin(deps.as_ref(), &info.sender)?;
+
+    match &penalty {
+        Some(penalty) => {
+            if penalty.min_hold == 0 || penalty.percentage > Decimal::one() {
+                return Err(ContractError::InvalidPenalty {});
+            }
+            WITHDRAW_PENALTY.save(deps.storage, penalty)?;
+        }
+        None => WITHDRAW_PENALTY.remove(deps.storage),
+    }
+
+    let res = Response::new().add_attributes(vec![
+        attr("action", "update_withdraw_penalty"),
+        attr("enabled", penalty.is_some().to_string()),
+        attr("sender", info.sender),
+    ]);
+    Ok(res)
+}
+
+/// Remembers when the staker deposited amount, as long as a penalty is configured.
+/// Stake without a deposit record is treated as held long enough.
+pub fn record_deposit(
+    storage: &mut dyn Storage,
+    time: &Timestamp,
+    staker: &Addr,
+    amount: Uint128,
+) -> StdResult<()> {
+    let penalty = match WITHDRAW_PENALTY.may_load(storage)? {
+        Some(penalty) => penalty,
+        None => return Ok(()),
+    };
+    prune_deposits(storage, &penalty, time, staker)?;
+    let deposit = Deposit {
+        amount,
+        time: *time,
+    };
+    add_deposit(storage, staker, deposit)
+}
+
+// matured deposits no longer change the penalty
+fn prune_deposits(
+    storage: &mut dyn Storage,
+    penalty: &WithdrawPenalty,
+    time: &Timestamp,
+    staker: &Addr,
+) -> StdResult<()> {
+    for (day, _) in young_deposits(storage, penalty, time, staker)?.1 {
+        DEPOSITS.remove(storage, (staker, day));
+    }
+    Ok(())
+}
+
+// merges the deposit into the one of the same day, keeping the later time so that merging
+// never shortens the holding time
+fn add_deposit(storage: &mut dyn Storage, staker: &Addr, deposit: Deposit) -> StdResult<()> {
+    let day = deposit.time.seconds() / DEPOSIT_BUCKET;
+    DEPOSITS.update(storage, (staker, day), |merged| -> StdResult<_> {
+        Ok(match merged {
+            Some(merged) => Deposit {
+                amount: merged.amount.checked_add(deposit.amount)?,
+                time: merged.time.max(deposit.time),
+            },
+            None => deposit,
+        })
+    })?;
+    Ok(())
+}
+
+// splits the deposits in the ones still inside the holding time and the matured ones
+fn young_deposits(
+    storage: &dyn Storage,
+    penalty: &WithdrawPenalty,
+    time: &Timestamp,
+    staker: &Addr,
+) -> StdResult<(Deposits, Deposits)> {
+    let deposits = DEPOSITS
+        .prefix(staker)
+        .range(storage, None, None, Order::Ascending)
+        .collect::<StdResult<Deposits>>()?;
+    Ok(deposits
+        .into_iter()
+        .partition(|(_, deposit)| deposit.time.plus_seconds(penalty.min_hold) > *time))
+}
+
+/// Penalty for withdrawing amount out of stake, and what is left of each deposit afterwards.
+/// Matured stake is withdrawn first, then the deposits from oldest to newest.
+fn plan_withdrawal(
+    storage: &dyn Storage,
+    time: &Timestamp,
+    staker: &Addr,
+    stake: Uint128,
+    amount: Uint128,
+) -> StdResult<(Uint128, DepositUpdates)> {
+    let penalty = match WITHDRAW_PENALTY.may_load(storage)? {
+        Some(penalty) => penalty,
+        None => return Ok((Uint128::zero(), vec![])),
+    };
+    let (young, matured) = young_deposits(storage, &penalty, time, staker)?;
+    let mut updates: Vec<_> = matured
+        .into_iter()
+        .map(|(id, deposit)| {
+            let left = Deposit {
+                amount: Uint128::zero(),
+                ..deposit
+            };
+            (id, left, Uint128::zero())
+        })
+        .collect();
+
+    let young_total = young
+        .iter()
+        .try_fold(Uint128::zero(), |sum, (_, d)| sum.checked_add(d.amount))?;
+    let mut remaining = amount.saturating_sub(stake.saturating_sub(young_total));
+    let mut penalized = Uint128::zero();
+    for (id, mut deposit) in young {
+        if remaining.is_zero() {
+            break;
+        }
+        let take = remaining.min(deposit.amount);
+        remaining -= take;
+        penalized += take;
+        deposit.amount -= take;
+        updates.push((id, deposit, take));
+    }
+    Ok((penalized * penalty.percentage, updates))
+}
+
+/// Consumes amount of the staker's deposits and returns the penalty to charge on it
+pub fn take_penalty(
+    storage: &mut dyn Storage,
+    time: &Timestamp,
+    staker: &Addr,
+    stake: Uint128,
+    amount: Uint128,
+) -> StdResult<Uint128> {
+    let (penalty, updates) = plan_withdrawal(storage, time, staker, stake, amount)?;
+    apply_updates(storage, staker, &updates)?;
+    Ok(penalty)
+}
+
+/// Sends a penalty kept from the staker's withdrawn stake to its destination. Returns the
+/// burn message if it is burned, penalties for the reward pool stay in this contract and
+/// go to the other stakers, so the stake left does not earn back its own penalty.
+pub fn collect_penalty(
+    storage: &mut dyn Storage,
+    time: &Timestamp,
+    staker: &Addr,
+    penalty: Uint128,
+) -> StdResult<Option<SubMsg>> {
+    if penalty.is_zero() {
+        return Ok(None);
+    }
+    match WITHDRAW_PENALTY.load(storage)?.destination {
+        PenaltyDestination::RewardPool => {
+            add_to_reward_pool(storage, time, penalty, staker)?;
+            Ok(None)
+        }
+        PenaltyDestination::Burn => {
+            let config = CONFIG.load(storage)?;
+            Ok(Some(SubMsg::new(config.new_burn(penalty)?)))
+        }
+    }
+}
+
+/// Moves the deposits which withdrawing amount out of stake would consume to the recipient,
+/// so they keep their deposit time
+pub fn transfer_deposits(
+    storage: &mut dyn Storage,
+    time: &Timestamp,
+    from: &Addr,
+    to: &Addr,
+    stake: Uint128,
+    amount: Uint128,
+) -> StdResult<()> {
+    let (_, updates) = plan_withdrawal(storage, time, from, stake, amount)?;
+    apply_updates(storage, from, &updates)?;
+    if let Some(penalty) = WITHDRAW_PENALTY.may_load(storage)? {
+        prune_deposits(storage, &penalty, time, to)?;
+    }
+    for (_, deposit, taken) in updates {
+        if taken.is_zero() {
+            continue;
+        }
+        let moved = Deposit {
+            amount: taken,
+            time: deposit.time,
+        };
+        add_deposit(storage, to, moved)?;
+    }
+    Ok(())
+}
+
+/// Takes percentage of every deposit, rounding up like the slashed positions
+pub fn slash_deposits(
+    storage: &mut dyn Storage,
+    staker: &Addr,
+    percentage: Decimal,
+) -> StdResult<()> {
+    let deposits = DEPOSITS
+        .prefix(staker)
+        .range(storage, None, None, Order::Ascending)
+        .collect::<StdResult<Deposits>>()?;
+    for (id, mut deposit) in deposits {
+        deposit.amount -= deposit.amount.mul_ceil(percentage);
+        if deposit.amount.is_zero() {
+            DEPOSITS.remove(storage, (staker, id));
+        } else {
+            DEPOSITS.save(storage, (staker, id), &deposit)?;
+        }
+    }
+    Ok(())
+}
+
+fn apply_updates(
+    storage: &mut dyn Storage,
+    staker: &Addr,
+    updates: &DepositUpdates,
+) -> StdResult<()> {
+    for (id, deposit, _) in updates {
+        if deposit.amount.is_zero() {
+            DEPOSITS.remove(storage, (staker, *id));
+        } else {
+            DEPOSITS.save(storage, (staker, *id), deposit)?;
+        }
+    }
+    Ok(())
+}
+
+pub fn query_withdraw_penalty(deps: Deps) -> StdResult<WithdrawPenaltyResponse> {
+    Ok(WithdrawPenaltyResponse {
+        penalty: WITHDRAW_PENALTY.may_load(deps.storage)?,
+        reward_pool: REWARD_POOL.may_load(deps.storage)?.unwrap_or_default(),
+    })
+}
+
+/// Does not check the lockup of the stake, only what withdrawing it would cost
+pub fn query_simulate_withdraw(
+    deps: Deps,
+    env: Env,
+    address: String,
+    amount: Uint128,
+) -> StdResult<SimulateWithdrawResponse> {
+    let address = deps.api.addr_validate(&address)?;
+    let stake = BALANCES
+        .may_load(deps.storage, &address)?
+        .unwrap_or_default();
+    if amount > stake {
+        return Err(StdError::generic_err("Cannot withdraw more than staked"));
+    }
+    let (penalty, _) = plan_withdrawal(deps.storage, &env.block.time, &address, stake, amount)?;
+    Ok(SimulateWithdrawResponse {
+        received: amount - penalty,
+        penalty,
+        destination: WITHDRAW_PENALTY
+            .may_load(deps.storage)?
+            .map(|penalty| penalty.destination),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use cosmwasm_std::testing::{mock_dependencies, mock_env, mock_info};
+    use cosmwasm_std::{to_json_binary, SubMsg, WasmMsg};
+    use cw20::Cw20ExecuteMsg;
+
+    use crate::contract::{execute, instantiate};
+    use crate::msg::{ExecuteMsg, InstantiateMsg};
+    use crate::state::PenaltyDestination;
+
+    const INIT_ADMIN: &str = "juan";
+    const CW20_ADDRESS: &str = "wasm1234567890";
+    const DAY: u64 = 24 * 60 * 60;
+
+    fn do_instantiate(mut deps: DepsMut, destination: PenaltyDestination) {
+        let msg = InstantiateMsg {
+            addr: Addr::unchecked(CW20_ADDRESS),
+            admin: Some(INIT_ADMIN.into()),
+        };
+        let info = mock_info("creator", &[]);
+        instantiate(deps.branch(), mock_env(), info, msg).unwrap();
+
+        let msg = ExecuteMsg::UpdateWithdrawPenalty {
+            penalty: Some(WithdrawPenalty {
+                min_hold: 10 * DAY,
+                percentage: Decimal::percent(10),
+                destination,
+            }),
+        };
+        execute(deps, mock_env(), mock_info(INIT_ADMIN, &[]), msg).unwrap();
+    }
+
+    fn stake(deps: DepsMut, env: &Env, amount: u128) {
+        let msg = ExecuteMsg::Stake {
+            amount: amount.into(),
+            lock_duration: None,
+        };
+        execute(deps, env.clone(), mock_info("mintu", &[]), msg).unwrap();
+    }
+
+    fn withdraw(deps: DepsMut, env: &Env, amount: u128) -> Response {
+        let msg = ExecuteMsg::Withdraw {
+            amount: amount.into(),
+        };
+        execute(deps, env.clone(), mock_info("mintu", &[]), msg).unwrap()
+    }
+
+    fn cw20_msg(msg: Cw20ExecuteMsg) -> SubMsg {
+        SubMsg::new(WasmMsg::Execute {
+            contract_addr: CW20_ADDRESS.into(),
+            msg: to_json_binary(&msg).unwrap(),
+            funds: vec![],
+        })
+    }
+
+    #[test]
+    fn only_admin_sets_valid_penalty() {
+        let mut deps = mock_dependencies();
+        do_instantiate(deps.as_mut(), PenaltyDestination::RewardPool);
+
+        let penalty = WithdrawPenalty {
+            min_hold: DAY,
+            percentage: Decimal::percent(101),
+            destination: PenaltyDestination::Burn,
+        };
+        let msg = ExecuteMsg::UpdateWithdrawPenalty {
+            penalty: Some(penalty.clone()),
+        };
+        let err = execute(
+            deps.as_mut(),
+            mock_env(),
+            mock_info("mintu", &[]),
+            msg.clone(),
+        )
+        .unwrap_err();
+        assert!(matches!(err, ContractError::Admin(_)));
+        let err = execute(deps.as_mut(), mock_env(), mock_info(INIT_ADMIN, &[]), msg).unwrap_err();
+        assert_eq!(err, ContractError::InvalidPenalty {});
+
+        let msg = ExecuteMsg::UpdateWithdrawPenalty { penalty: None };
+        execute(deps.as_mut(), mock_env(), mock_info(INIT_ADMIN, &[]), msg).unwrap();
+        assert_eq!(
+            query_withdraw_penalty(deps.as_ref()).unwrap(),
+            WithdrawPenaltyResponse {
+                penalty: None,
+                reward_pool: Uint128::zero(),
+            }
+        );
+    }
+
+    #[test]
+    fn early_withdrawal_feeds_reward_pool() {
+        let mut deps = mock_dependencies();
+        do_instantiate(deps.as_mut(), PenaltyDestination::RewardPool);
+        let mut env = mock_env();
+
+        stake(deps.as_mut(), &env, 1000);
+        let msg = ExecuteMsg::Stake {
+            amount: 500u128.into(),
+            lock_duration: None,
+        };
+        execute(deps.as_mut(), env.clone(), mock_info("lisa", &[]), msg).unwrap();
+        env.block.time = env.block.time.plus_seconds(5 * DAY);
+        stake(deps.as_mut(), &env, 500);
+
+        // first deposit matured, 300 of the second one are withdrawn early
+        env.block.time = env.block.time.plus_seconds(6 * DAY);
+        let simulated =
+            query_simulate_withdraw(deps.as_ref(), env.clone(), "mintu".into(), 1300u128.into())
+                .unwrap();
+        assert_eq!(
+            simulated,
+            SimulateWithdrawResponse {
+                received: 1270u128.into(),
+                penalty: 30u128.into(),
+                destination: Some(PenaltyDestination::RewardPool),
+            }
+        );
+        let res = withdraw(deps.as_mut(), &env, 1300);
+        assert_eq!(
+            res.messages,
+            vec![cw20_msg(Cw20ExecuteMsg::Transfer {
+                recipient: "mintu".into(),
+                amount: 1270u128.into(),
+            })]
+        );
+        assert!(res.attributes.contains(&attr("penalty", "30")));
+        assert_eq!(
+            query_withdraw_penalty(deps.as_ref()).unwrap().reward_pool,
+            Uint128::new(30)
+        );
+
+        // the penalty is paid out to the other stakers, the stake mintu kept earns none of it
+        let err = execute(
+            deps.as_mut(),
+            env.clone(),
+            mock_info("mintu", &[]),
+            ExecuteMsg::ClaimRewards {},
+        )
+        .unwrap_err();
+        assert_eq!(err, ContractError::NothingToClaim {});
+        let res = execute(
+            deps.as_mut(),
+            env.clone(),
+            mock_info("lisa", &[]),
+            ExecuteMsg::ClaimRewards {},
+        )
+        .unwrap();
+        assert_eq!(
+            res.messages,
+            vec![cw20_msg(Cw20ExecuteMsg::Transfer {
+                recipient: "lisa".into(),
+                amount: 30u128.into(),
+            })]
+        );
+        assert_eq!(
+            query_withdraw_penalty(deps.as_ref()).unwrap().reward_pool,
+            Uint128::zero()
+        );
+
+        // the rest becomes free once it was held long enough
+        env.block.time = env.block.time.plus_seconds(4 * DAY);
+        let simulated =
+            query_simulate_withdraw(deps.as_ref(), env, "mintu".into(), 200u128.into()).unwrap();
+        assert_eq!(simulated.penalty, Uint128::zero());
+        let err =
+            query_simulate_withdraw(deps.as_ref(), mock_env(), "mintu".into(), 201u128.into())
+                .unwrap_err();
+        assert!(matches!(err, StdError::GenericErr { .. }));
+    }
+
+    #[test]
+    fn early_withdrawal_burns_penalty() {
+        let mut deps = mock_dependencies();
+        do_instantiate(deps.as_mut(), PenaltyDestination::Burn);
+        let env = mock_env();
+
+        stake(deps.as_mut(), &env, 1000);
+        let res = withdraw(deps.as_mut(), &env, 999);
+        assert_eq!(
+            res.messages,
+            vec![
+                cw20_msg(Cw20ExecuteMsg::Transfer {
+                    recipient: "mintu".into(),
+                    amount: 900u128.into(),
+                }),
+                cw20_msg(Cw20ExecuteMsg::Burn {
+                    amount: 99u128.into()
+                }),
+            ]
+        );
+        assert_eq!(
+            query_withdraw_penalty(deps.as_ref()).unwrap().reward_pool,
+            Uint128::zero()
+        );
+        let remaining: Vec<_> = DEPOSITS
+            .prefix(&Addr::unchecked("mintu"))
+            .range(&deps.storage, None, None, Order::Ascending)
+            .collect::<StdResult<_>>()
+            .unwrap();
+        assert_eq!(remaining.len(), 1);
+        assert_eq!(remaining[0].1.amount, Uint128::one());
+    }
+
+    #[test]
+    fn full_penalty_pays_nothing_out() {
+        let mut deps = mock_dependencies();
+        do_instantiate(deps.as_mut(), PenaltyDestination::Burn);
+        let msg = ExecuteMsg::UpdateWithdrawPenalty {
+            penalty: Some(WithdrawPenalty {
+                min_hold: 10 * DAY,
+                percentage: Decimal::one(),
+                destination: PenaltyDestination::Burn,
+            }),
+        };
+        execute(deps.as_mut(), mock_env(), mock_info(INIT_ADMIN, &[]), msg).unwrap();
+        let env = mock_env();
+
+        stake(deps.as_mut(), &env, 100);
+        let res = withdraw(deps.as_mut(), &env, 100);
+        assert_eq!(
+            res.messages,
+            vec![cw20_msg(Cw20ExecuteMsg::Burn {
+                amount: 100u128.into()
+            })]
+        );
+    }
+
+    #[test]
+    fn deposits_of_a_day_merge() {
+        let mut deps = mock_dependencies();
+        do_instantiate(deps.as_mut(), PenaltyDestination::Burn);
+        let mut env = mock_env();
+        let deposits = |deps: Deps| -> Vec<(u64, Deposit)> {
+            DEPOSITS
+                .prefix(&Addr::unchecked("mintu"))
+                .range(deps.storage, None, None, Order::Ascending)
+                .collect::<StdResult<_>>()
+                .unwrap()
+        };
+
+        // dust staked for the staker over the day adds up to a single deposit
+        for _ in 0..10 {
+            let msg = ExecuteMsg::StakeFor {
+                beneficiary: "mintu".into(),
+                amount: Uint128::one(),
+            };
+            execute(deps.as_mut(), env.clone(), mock_info("guild", &[]), msg).unwrap();
+            env.block.time = env.block.time.plus_seconds(60);
+        }
+        let merged = deposits(deps.as_ref());
+        assert_eq!(merged.len(), 1);
+        assert_eq!(
+            merged[0].1,
+            Deposit {
+                amount: Uint128::new(10),
+                time: env.block.time.minus_seconds(60),
+            }
+        );
+
+        // the next day starts a new deposit, matured ones are dropped
+        env.block.time = env.block.time.plus_seconds(DAY);
+        stake(deps.as_mut(), &env, 5);
+        assert_eq!(deposits(deps.as_ref()).len(), 2);
+        env.block.time = env.block.time.plus_seconds(10 * DAY);
+        stake(deps.as_mut(), &env, 5);
+        assert_eq!(deposits(deps.as_ref()).len(), 1);
+    }
+}
@@ -0,0 +1,639 @@
+use cosmwasm_std::{
+    attr, Addr, Decimal, Deps, DepsMut, Empty, Env, MessageInfo, Order, Response, StdError,
+    StdResult, Storage, SubMsg, Timestamp, Uint128,
+};
+use cw4::MemberDiff;
+use cw_storage_plus::Bound;
+
+use crate::error::ContractError;
+use crate::limits::load_limits;
+use crate::lockup::{expired_boosts, member_hooks, staker_weight, update_weight};
+use crate::msg::RewardsResponse;
+use crate::state::{
+    RewardState, StakerRewards, AUTO_COMPOUND, BALANCES, CONFIG, EXPIRING_BOOST, EXPIRY_INDEX,
+    REWARD_POOL, REWARD_STATE, STAKER_REWARDS, TOTAL, TOTAL_WEIGHT,
+};
+
+// bounds the gas spent by a single compound crank
+pub const MAX_COMPOUND: u32 = 30;
+
+/// Changes the reward rate. Rewards up to now are still paid at the old rate.
+pub fn set_reward_rate(
+    storage: &mut dyn Storage,
+    time: &Timestamp,
+    rate: Uint128,
+) -> StdResult<()> {
+    let mut state = accrue(storage, time)?;
+    state.rate = rate;
+    REWARD_STATE.save(storage, &state)
+}
+
+/// Pays the pending rewards of the staker to the staker
+pub fn execute_claim_rewards(
+    deps: DepsMut,
+    env: Env,
+    staker: Addr,
+) -> Result<Response, ContractError> {
+    let diff = update_weight(deps.storage, &env, &staker)?;
+    let amount = take_pending(deps.storage, &staker, None)?;
+    if amount.is_zero() {
+        return Err(ContractError::NothingToClaim {});
+    }
+
+    let res = Response::new()
+        .add_submessages(reward_msgs(deps.storage, &staker, amount)?)
+        .add_submessages(member_hooks(deps.storage, diff.into_iter().collect())?)
+        .add_attribute("action", "claim_rewards")
+        .add_attribute("to", staker)
+        .add_attribute("amount", amount);
+    Ok(res)
+}
+
+pub fn execute_set_auto_compound(
+    deps: DepsMut,
+    info: MessageInfo,
+    enabled: bool,
+) -> Result<Response, ContractError> {
+    if enabled {
+        AUTO_COMPOUND.save(deps.storage, &info.sender, &Empty {})?;
+    } else {
+        AUTO_COMPOUND.remove(deps.storage, &info.sender);
+    }
+
+    let res = Response::new().add_attributes(vec![
+        attr("action", "set_auto_compound"),
+        attr("staker", info.sender),
+        attr("enabled", enabled.to_string()),
+    ]);
+    Ok(res)
+}
+
+/// Stakes the pending rewards of every given staker who opted in to auto-compounding.
+/// Anyone can call this, stakers who did not opt in are skipped.
+pub fn execute_compound(
+    deps: DepsMut,
+    env: Env,
+    addresses: Vec<String>,
+) -> Result<Response, ContractError> {
+    if addresses.len() > MAX_COMPOUND as usize {
+        return Err(ContractError::TooManyAddresses { max: MAX_COMPOUND });
+    }
+
+    let mut compounded = 0u32;
+    let mut total_rewards = Uint128::zero();
+    let mut diffs = vec![];
+    for address in addresses {
+        let staker = deps.api.addr_validate(&address)?;
+        if !AUTO_COMPOUND.has(deps.storage, &staker) {
+            continue;
+        }
+        let amount = compound_stake(deps.storage, &env, &staker, None, &mut diffs)?;
+        if amount.is_zero() {
+            continue;
+        }
+        compounded += 1;
+        total_rewards = total_rewards.checked_add(amount).map_err(StdError::from)?;
+    }
+
+    let mut res = Response::new()
+        .add_submessages(member_hooks(deps.storage, diffs)?)
+        .add_attribute("action", "compound")
+        .add_attribute("compounded", compounded.to_string())
+        .add_attribute("amount", total_rewards);
+    if !total_rewards.is_zero() {
+        // the rewards back the new stakes, so what the reward pool does not cover is minted
+        // to this contract
+        let minted = total_rewards - take_from_reward_pool(deps.storage, total_rewards)?;
+        if !minted.is_zero() {
+            let config = CONFIG.load(deps.storage)?;
+            res = res.add_submessage(SubMsg::new(config.new_mint(&env.contract.address, minted)?));
+        }
+    }
+    Ok(res)
+}
+
+/// Adds the pending rewards of the staker to its stake and returns them. Only as much as
+/// `max` and the stake limits leave room for is added, the rest stays pending. The liquid
+/// pool is not bound by the maximum per address. The caller must cover them with
+/// `take_from_reward_pool` or by minting them to this contract, and pass the weight changes
+/// to `member_hooks`.
+pub fn compound_stake(
+    storage: &mut dyn Storage,
+    env: &Env,
+    staker: &Addr,
+    max: Option<Uint128>,
+    diffs: &mut Vec<MemberDiff>,
+) -> StdResult<Uint128> {
+    diffs.extend(update_weight(storage, env, staker)?);
+    let limits = load_limits(storage)?;
+    let stake = BALANCES.may_load(storage, staker)?.unwrap_or_default();
+    let total = TOTAL.load(storage)?;
+    let mut room = max;
+    if let Some(cap) = limits.max_total_stake {
+        let left = cap.saturating_sub(total);
+        room = Some(room.map_or(left, |room| room.min(left)));
+    }
+    if let Some(max) = limits.max_stake_per_address {
+        if *staker != env.contract.address {
+            let left = max.saturating_sub(stake);
+            room = Some(room.map_or(left, |room| room.min(left)));
+        }
+    }
+
+    let amount = take_pending(storage, staker, room)?;
+    if !amount.is_zero() {
+        BALANCES.save(storage, staker, &stake.checked_add(amount)?)?;
+        TOTAL.save(storage, &total.checked_add(amount)?)?;
+        diffs.extend(update_weight(storage, env, staker)?);
+    }
+    Ok(amount)
+}
+
+struct Accrual {
+    state: RewardState,
+    total_weight: Uint128,
+    /// Index at every unlock time passed, in seconds
+    expiries: Vec<(u64, Decimal)>,
+}
+
+// reward index at the given time, without storing it. Boosts expiring on the way stop
+// counting in the total weight from their unlock time on.
+fn current_state(storage: &dyn Storage, time: &Timestamp) -> StdResult<Accrual> {
+    let mut state = REWARD_STATE.may_load(storage)?.unwrap_or(RewardState {
+        rate: Uint128::zero(),
+        index: Decimal::zero(),
+        last_update: *time,
+    });
+    let mut total_weight = TOTAL_WEIGHT.may_load(storage)?.unwrap_or_default();
+    let expiring = EXPIRING_BOOST
+        .range(
+            storage,
+            Some(Bound::exclusive(state.last_update.seconds())),
+            Some(Bound::inclusive(time.seconds())),
+            Order::Ascending,
+        )
+        .collect::<StdResult<Vec<_>>>()?;
+    let mut expiries = vec![];
+    for (expiry, boost) in expiring {
+        advance(&mut state, total_weight, expiry)?;
+        total_weight = total_weight.checked_sub(boost)?;
+        expiries.push((expiry, state.index));
+    }
+    advance(&mut state, total_weight, time.seconds())?;
+    state.last_update = *time;
+    Ok(Accrual {
+        state,
+        total_weight,
+        expiries,
+    })
+}
+
+// moves the index to the given time in seconds at the current rate and total weight
+fn advance(state: &mut RewardState, total_weight: Uint128, seconds: u64) -> StdResult<()> {
+    let elapsed = seconds.saturating_sub(state.last_update.seconds());
+    if !total_weight.is_zero() && elapsed > 0 {
+        let emitted = state.rate.checked_mul(elapsed.into())?;
+        let per_weight = Decimal::checked_from_ratio(emitted, total_weight)
+            .map_err(|err| StdError::generic_err(err.to_string()))?;
+        state.index = state.index.checked_add(per_weight)?;
+    }
+    state.last_update = Timestamp::from_seconds(seconds);
+    Ok(())
+}
+
+fn accrue(storage: &mut dyn Storage, time: &Timestamp) -> StdResult<RewardState> {
+    let accrual = current_state(storage, time)?;
+    for (expiry, index) in &accrual.expiries {
+        EXPIRING_BOOST.remove(storage, *expiry);
+        EXPIRY_INDEX.save(storage, *expiry, index)?;
+    }
+    TOTAL_WEIGHT.save(storage, &accrual.total_weight)?;
+    REWARD_STATE.save(storage, &accrual.state)?;
+    Ok(accrual.state)
+}
+
+// pairs the boost of every expired position with the index it expired at, taken from the
+// expiries passed by an accrual which was not stored yet, or from the stored ones
+fn expiry_indexes(
+    storage: &dyn Storage,
+    passed: &[(u64, Decimal)],
+    expired: &[(u64, Uint128)],
+    state: &RewardState,
+) -> StdResult<Vec<(Decimal, Uint128)>> {
+    expired
+        .iter()
+        .map(|(expiry, boost)| {
+            let index = match passed.iter().find(|(time, _)| time == expiry) {
+                Some((_, index)) => *index,
+                None => EXPIRY_INDEX
+                    .may_load(storage, *expiry)?
+                    .unwrap_or(state.index),
+            };
+            Ok((index, *boost))
+        })
+        .collect()
+}
+
+// credits the rewards earned at weight up to the state, where each expired boost only
+// counts up to the index it expired at. Returns the weight left without the expired boosts.
+fn settle(
+    rewards: &mut StakerRewards,
+    state: &RewardState,
+    mut weight: Uint128,
+    expiries: &[(Decimal, Uint128)],
+) -> StdResult<Uint128> {
+    for (index, boost) in expiries {
+        if *index > rewards.index {
+            let earned = weight * (*index - rewards.index);
+            rewards.pending = rewards.pending.checked_add(earned)?;
+            rewards.index = *index;
+        }
+        weight = weight.checked_sub(*boost)?;
+    }
+    let earned = weight * (state.index - rewards.index);
+    rewards.pending = rewards.pending.checked_add(earned)?;
+    rewards.index = state.index;
+    Ok(weight)
+}
+
+/// Credits the staker with the rewards earned at the old weight and moves the total weight
+/// to the new one. The boosts of the expired positions, given by unlock time, only earned
+/// rewards until then. Must be called before every change of a member weight.
+pub fn settle_rewards(
+    storage: &mut dyn Storage,
+    time: &Timestamp,
+    staker: &Addr,
+    old_weight: Uint128,
+    expired: &[(u64, Uint128)],
+    new_weight: Uint128,
+) -> StdResult<()> {
+    let state = accrue(storage, time)?;
+    let expiries = expiry_indexes(storage, &[], expired, &state)?;
+    let mut rewards = STAKER_REWARDS
+        .may_load(storage, staker)?
+        .unwrap_or_default();
+    // the expired boosts already left the total weight when they expired
+    let old_weight = settle(&mut rewards, &state, old_weight, &expiries)?;
+    STAKER_REWARDS.save(storage, staker, &rewards)?;
+
+    let total_weight = TOTAL_WEIGHT.may_load(storage)?.unwrap_or_default();
+    TOTAL_WEIGHT.save(
+        storage,
+        &total_weight
+            .checked_sub(old_weight)?
+            .checked_add(new_weight)?,
+    )
+}
+
+/// Moves the share of the pending rewards earned by amount out of stake to the recipient.
+/// Pending rewards of both must be settled before.
+pub fn transfer_pending(
+    storage: &mut dyn Storage,
+    from: &Addr,
+    to: &Addr,
+    stake: Uint128,
+    amount: Uint128,
+) -> StdResult<()> {
+    let mut rewards = STAKER_REWARDS.may_load(storage, from)?.unwrap_or_default();
+    if stake.is_zero() || rewards.pending.is_zero() {
+        return Ok(());
+    }
+    let moved = rewards.pending.multiply_ratio(amount, stake);
+    rewards.pending -= moved;
+    STAKER_REWARDS.save(storage, from, &rewards)?;
+    STAKER_REWARDS.update(storage, to, |rewards| -> StdResult<_> {
+        let mut rewards = rewards.unwrap_or_default();
+        rewards.pending = rewards.pending.checked_add(moved)?;
+        Ok(rewards)
+    })?;
+    Ok(())
+}
+
+/// Adds amount, which this contract already holds, to the rewards of the stakers by weight,
+/// leaving out the given staker, whose rewards must be settled before. Without any weight
+/// it stays in the pool and covers later rewards.
+pub fn add_to_reward_pool(
+    storage: &mut dyn Storage,
+    time: &Timestamp,
+    amount: Uint128,
+    excluded: &Addr,
+) -> StdResult<()> {
+    let mut state = accrue(storage, time)?;
+    let total_weight = TOTAL_WEIGHT.may_load(storage)?.unwrap_or_default();
+    let weight = total_weight.saturating_sub(staker_weight(storage, excluded)?);
+    if !weight.is_zero() {
+        let per_weight = Decimal::checked_from_ratio(amount, weight)
+            .map_err(|err| StdError::generic_err(err.to_string()))?;
+        state.index = state.index.checked_add(per_weight)?;
+        REWARD_STATE.save(storage, &state)?;
+        // moving the excluded staker along with the index skips just this share
+        let mut rewards = STAKER_REWARDS
+            .may_load(storage, excluded)?
+            .unwrap_or_default();
+        rewards.index = state.index;
+        STAKER_REWARDS.save(storage, excluded, &rewards)?;
+    }
+    let pool = REWARD_POOL.may_load(storage)?.unwrap_or_default();
+    REWARD_POOL.save(storage, &pool.checked_add(amount)?)
+}
+
+/// Takes up to amount out of the reward pool and returns what was taken. Rewards are paid
+/// out of the pool first, only the rest is minted.
+pub fn take_from_reward_pool(storage: &mut dyn Storage, amount: Uint128) -> StdResult<Uint128> {
+    let pool = REWARD_POOL.may_load(storage)?.unwrap_or_default();
+    let taken = pool.min(amount);
+    REWARD_POOL.save(storage, &(pool - taken))?;
+    Ok(taken)
+}
+
+// pays amount of rewards to the recipient, transferring what the reward pool covers
+fn reward_msgs(
+    storage: &mut dyn Storage,
+    recipient: &Addr,
+    amount: Uint128,
+) -> StdResult<Vec<SubMsg>> {
+    let from_pool = take_from_reward_pool(storage, amount)?;
+    let config = CONFIG.load(storage)?;
+    let mut msgs = vec![];
+    if !from_pool.is_zero() {
+        msgs.push(SubMsg::new(
+            config.clone().new_transfer(recipient, from_pool)?,
+        ));
+    }
+    if from_pool < amount {
+        msgs.push(SubMsg::new(config.new_mint(recipient, amount - from_pool)?));
+    }
+    Ok(msgs)
+}
+
+// takes the pending rewards up to max, which must be settled before
+fn take_pending(
+    storage: &mut dyn Storage,
+    staker: &Addr,
+    max: Option<Uint128>,
+) -> StdResult<Uint128> {
+    let mut rewards = STAKER_REWARDS
+        .may_load(storage, staker)?
+        .unwrap_or_default();
+    let amount = max.map_or(rewards.pending, |max| rewards.pending.min(max));
+    rewards.pending -= amount;
+    STAKER_REWARDS.save(storage, staker, &rewards)?;
+    Ok(amount)
+}
+
+pub fn query_rewards(deps: Deps, env: Env, address: String) -> StdResult<RewardsResponse> {
+    let address = deps.api.addr_validate(&address)?;
+    let accrual = current_state(deps.storage, &env.block.time)?;
+    let expired = expired_boosts(deps.storage, &address, &env.block.time)?;
+    let expiries = expiry_indexes(deps.storage, &accrual.expiries, &expired, &accrual.state)?;
+    let mut rewards = STAKER_REWARDS
+        .may_load(deps.storage, &address)?
+        .unwrap_or_default();
+    let weight = staker_weight(deps.storage, &address)?;
+    settle(&mut rewards, &accrual.state, weight, &expiries)?;
+    Ok(RewardsResponse {
+        pending: rewards.pending,
+        auto_compound: AUTO_COMPOUND.has(deps.storage, &address),
+        rate: accrual.state.rate,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use cosmwasm_std::testing::{mock_dependencies, mock_env, mock_info};
+    use cosmwasm_std::{to_json_binary, WasmMsg};
+    use cw20::Cw20ExecuteMsg;
+
+    use crate::contract::{execute, instantiate, query_staked};
+    use crate::msg::{ExecuteMsg, InstantiateMsg};
+    use crate::state::{LockTier, StakeLimits, MEMBERS};
+
+    const INIT_ADMIN: &str = "juan";
+    const CW20_ADDRESS: &str = "wasm1234567890";
+    const DAY: u64 = 24 * 60 * 60;
+
+    fn do_instantiate(mut deps: DepsMut) {
+        let msg = InstantiateMsg {
+            addr: Addr::unchecked(CW20_ADDRESS),
+            admin: Some(INIT_ADMIN.into()),
+        };
+        let info = mock_info("creator", &[]);
+        instantiate(deps.branch(), mock_env(), info, msg).unwrap();
+
+        let msg = ExecuteMsg::UpdateConfig {
+            reward_rate: Some(10u128.into()),
+            unbonding_period: None,
+            limits: None,
+        };
+        execute(deps, mock_env(), mock_info(INIT_ADMIN, &[]), msg).unwrap();
+    }
+
+    fn stake(deps: DepsMut, env: &Env, staker: &str, amount: u128, lock_duration: Option<u64>) {
+        let msg = ExecuteMsg::Stake {
+            amount: amount.into(),
+            lock_duration,
+        };
+        execute(deps, env.clone(), mock_info(staker, &[]), msg).unwrap();
+    }
+
+    fn pending_of(deps: Deps, env: &Env, staker: &str) -> Uint128 {
+        query_rewards(deps, env.clone(), staker.into())
+            .unwrap()
+            .pending
+    }
+
+    fn mint_msg(recipient: &str, amount: u128) -> SubMsg {
+        SubMsg::new(WasmMsg::Execute {
+            contract_addr: CW20_ADDRESS.into(),
+            msg: to_json_binary(&Cw20ExecuteMsg::Mint {
+                recipient: recipient.into(),
+                amount: amount.into(),
+            })
+            .unwrap(),
+            funds: vec![],
+        })
+    }
+
+    #[test]
+    fn only_admin_sets_reward_rate() {
+        let mut deps = mock_dependencies();
+        do_instantiate(deps.as_mut());
+
+        let msg = ExecuteMsg::UpdateConfig {
+            reward_rate: Some(5u128.into()),
+            unbonding_period: None,
+            limits: None,
+        };
+        let err = execute(deps.as_mut(), mock_env(), mock_info("mintu", &[]), msg).unwrap_err();
+        assert!(matches!(err, ContractError::Admin(_)));
+        let rewards = query_rewards(deps.as_ref(), mock_env(), "mintu".into()).unwrap();
+        assert_eq!(rewards.rate, Uint128::new(10));
+    }
+
+    #[test]
+    fn rewards_accrue_by_weight() {
+        let mut deps = mock_dependencies();
+        do_instantiate(deps.as_mut());
+        let msg = ExecuteMsg::UpdateLockTiers {
+            tiers: vec![LockTier {
+                duration: 30 * DAY,
+                multiplier: Decimal::percent(300),
+            }],
+        };
+        execute(deps.as_mut(), mock_env(), mock_info(INIT_ADMIN, &[]), msg).unwrap();
+
+        let mut env = mock_env();
+        stake(deps.as_mut(), &env, "mintu", 100, None);
+        env.block.time = env.block.time.plus_seconds(100);
+        assert_eq!(pending_of(deps.as_ref(), &env, "mintu"), Uint128::new(1000));
+
+        // a locked stake with three times the weight earns three times as much
+        stake(deps.as_mut(), &env, "lisa", 100, Some(30 * DAY));
+        env.block.time = env.block.time.plus_seconds(100);
+        assert_eq!(pending_of(deps.as_ref(), &env, "mintu"), Uint128::new(1250));
+        assert_eq!(pending_of(deps.as_ref(), &env, "lisa"), Uint128::new(750));
+
+        let res = execute(
+            deps.as_mut(),
+            env.clone(),
+            mock_info("lisa", &[]),
+            ExecuteMsg::ClaimRewards {},
+        )
+        .unwrap();
+        assert_eq!(res.messages, vec![mint_msg("lisa", 750)]);
+        assert_eq!(pending_of(deps.as_ref(), &env, "lisa"), Uint128::zero());
+        let err = execute(
+            deps.as_mut(),
+            env,
+            mock_info("lisa", &[]),
+            ExecuteMsg::ClaimRewards {},
+        )
+        .unwrap_err();
+        assert_eq!(err, ContractError::NothingToClaim {});
+    }
+
+    #[test]
+    fn boost_ends_at_unlock_time() {
+        let mut deps = mock_dependencies();
+        do_instantiate(deps.as_mut());
+        let msg = ExecuteMsg::UpdateLockTiers {
+            tiers: vec![LockTier {
+                duration: 30 * DAY,
+                multiplier: Decimal::percent(300),
+            }],
+        };
+        execute(deps.as_mut(), mock_env(), mock_info(INIT_ADMIN, &[]), msg).unwrap();
+
+        let mut env = mock_env();
+        env.block.time = Timestamp::from_seconds(env.block.time.seconds() / DAY * DAY);
+        stake(deps.as_mut(), &env, "mintu", 100, None);
+        stake(deps.as_mut(), &env, "lisa", 100, Some(30 * DAY));
+
+        // nobody touches the lock when it expires, still its boost stops earning right then
+        env.block.time = env.block.time.plus_seconds(30 * DAY + 1000);
+        // 30 days at 300 of 400 weight, then 1000 seconds at 100 of 200
+        let expected = 30 * DAY as u128 * 10 * 3 / 4 + 5000;
+        assert_eq!(
+            pending_of(deps.as_ref(), &env, "lisa"),
+            Uint128::new(expected)
+        );
+        let res = execute(
+            deps.as_mut(),
+            env.clone(),
+            mock_info("lisa", &[]),
+            ExecuteMsg::ClaimRewards {},
+        )
+        .unwrap();
+        assert_eq!(res.messages, vec![mint_msg("lisa", expected)]);
+        assert_eq!(
+            pending_of(deps.as_ref(), &env, "mintu"),
+            Uint128::new(30 * DAY as u128 * 10 / 4 + 5000)
+        );
+        assert_eq!(TOTAL_WEIGHT.load(&deps.storage).unwrap(), Uint128::new(200));
+        let member = MEMBERS
+            .load(&deps.storage, &Addr::unchecked("lisa"))
+            .unwrap();
+        assert_eq!(member, 100);
+    }
+
+    #[test]
+    fn compound_restakes_opted_in_rewards() {
+        let mut deps = mock_dependencies();
+        do_instantiate(deps.as_mut());
+
+        let mut env = mock_env();
+        stake(deps.as_mut(), &env, "mintu", 100, None);
+        stake(deps.as_mut(), &env, "lisa", 100, None);
+        let msg = ExecuteMsg::SetAutoCompound { enabled: true };
+        execute(deps.as_mut(), env.clone(), mock_info("mintu", &[]), msg).unwrap();
+        env.block.time = env.block.time.plus_seconds(100);
+
+        let msg = ExecuteMsg::Compound {
+            addresses: vec!["mintu".into(), "lisa".into()],
+        };
+        let res = execute(deps.as_mut(), env.clone(), mock_info("anyone", &[]), msg).unwrap();
+        assert_eq!(
+            res.messages,
+            vec![mint_msg(env.contract.address.as_str(), 500)]
+        );
+        assert!(res.attributes.contains(&attr("compounded", "1")));
+
+        // only the opted in staker compounded, the other one can still claim
+        let staked = query_staked(deps.as_ref(), "mintu".into()).unwrap();
+        assert_eq!(staked.stake, Uint128::new(600));
+        let staked = query_staked(deps.as_ref(), "lisa".into()).unwrap();
+        assert_eq!(staked.stake, Uint128::new(100));
+        assert_eq!(TOTAL.load(&deps.storage).unwrap(), Uint128::new(700));
+        assert_eq!(pending_of(deps.as_ref(), &env, "mintu"), Uint128::zero());
+        assert_eq!(pending_of(deps.as_ref(), &env, "lisa"), Uint128::new(500));
+        let rewards = query_rewards(deps.as_ref(), env, "mintu".into()).unwrap();
+        assert!(rewards.auto_compound);
+
+        let msg = ExecuteMsg::Compound {
+            addresses: vec!["mintu".into(); MAX_COMPOUND as usize + 1],
+        };
+        let err = execute(deps.as_mut(), mock_env(), mock_info("anyone", &[]), msg).unwrap_err();
+        assert_eq!(err, ContractError::TooManyAddresses { max: MAX_COMPOUND });
+    }
+
+    #[test]
+    fn compound_stays_within_stake_limits() {
+        let mut deps = mock_dependencies();
+        do_instantiate(deps.as_mut());
+        let msg = ExecuteMsg::UpdateStakeLimits {
+            limits: StakeLimits {
+                min_stake: Uint128::zero(),
+                max_stake_per_address: Some(Uint128::new(400)),
+                max_total_stake: Some(Uint128::new(700)),
+            },
+        };
+        execute(deps.as_mut(), mock_env(), mock_info(INIT_ADMIN, &[]), msg).unwrap();
+
+        let mut env = mock_env();
+        stake(deps.as_mut(), &env, "mintu", 100, None);
+        stake(deps.as_mut(), &env, "lisa", 100, None);
+        for staker in ["mintu", "lisa"] {
+            let msg = ExecuteMsg::SetAutoCompound { enabled: true };
+            execute(deps.as_mut(), env.clone(), mock_info(staker, &[]), msg).unwrap();
+        }
+        env.block.time = env.block.time.plus_seconds(100);
+
+        // mintu reaches the maximum per address, lisa the total cap with the rest
+        let msg = ExecuteMsg::Compound {
+            addresses: vec!["mintu".into(), "lisa".into()],
+        };
+        let res = execute(deps.as_mut(), env.clone(), mock_info("anyone", &[]), msg).unwrap();
+        assert!(res.attributes.contains(&attr("amount", "500")));
+        let staked = query_staked(deps.as_ref(), "mintu".into()).unwrap();
+        assert_eq!(staked.stake, Uint128::new(400));
+        let staked = query_staked(deps.as_ref(), "lisa".into()).unwrap();
+        assert_eq!(staked.stake, Uint128::new(300));
+        assert_eq!(TOTAL.load(&deps.storage).unwrap(), Uint128::new(700));
+
+        // what does not fit stays pending
+        assert_eq!(pending_of(deps.as_ref(), &env, "mintu"), Uint128::new(200));
+        assert_eq!(pending_of(deps.as_ref(), &env, "lisa"), Uint128::new(300));
+    }
+}
@@ -0,0 +1,328 @@
+use cosmwasm_std::{
+    attr, Addr, Decimal, Deps, DepsMut, Env, MessageInfo, Response, StdError, StdResult, Storage,
+    SubMsg, Uint128,
+};
+use cw_controllers::Claim;
+use cw_storage_plus::Map;
+
+use crate::error::ContractError;
+use crate::lockup::{member_hooks, slash_positions, update_weight};
+use crate::msg::SlashingResponse;
+use crate::penalty::slash_deposits;
+use crate::state::{
+    SlashingConfig, ADMIN, BALANCES, CLAIMS_KEY, CONFIG, SLASHING, TOTAL, UNBONDING,
+};
+use crate::transfer::slash_pending_transfer;
+
+// the claims stored by `CLAIMS`, as the controller cannot change a claim in place
+const CLAIMS_STORE: Map<&Addr, Vec<Claim>> = Map::new(CLAIMS_KEY);
+
+pub fn execute_update_slashing(
+    deps: DepsMut,
+    info: MessageInfo,
+    slasher: Option<String>,
+    treasury: String,
+) -> Result<Response, ContractError> {
+    ADMIN.assert_admin(deps.as_ref(), &info.sender)?;
+    let config = SlashingConfig {
+        slasher: slasher
+            .map(|slasher| deps.api.addr_validate(&slasher))
+            .transpose()?,
+        treasury: deps.api.addr_validate(&treasury)?,
+    };
+    SLASHING.save(deps.storage, &config)?;
+
+    let res = Response::new().add_attributes(vec![
+        attr("action", "update_slashing"),
+        attr("treasury", treasury),
+        attr("sender", info.sender),
+    ]);
+    Ok(res)
+}
+
+/// Takes percentage of the stake and pending claims of the address and sends it to the
+/// treasury. Only the admin or the slasher can do this.
+pub fn execute_slash(
+    deps: DepsMut,
+    env: Env,
+    info: MessageInfo,
+    address: String,
+    percentage: Decimal,
+) -> Result<Response, ContractError> {
+    let slashing = SLASHING
+        .may_load(deps.storage)?
+        .ok_or(ContractError::SlashingNotConfigured {})?;
+    if slashing.slasher.as_ref() != Some(&info.sender)
+        && !ADMIN.is_admin(deps.as_ref(), &info.sender)?
+    {
+        return Err(ContractError::Unauthorized {});
+    }
+    if percentage.is_zero() || percentage > Decimal::one() {
+        return Err(ContractError::InvalidSlashPercentage {});
+    }
+    let staker = deps.api.addr_validate(&address)?;
+    // receipts of the liquid pool are held by many, so its stake is not slashed
+    if staker == env.contract.address {
+        return Err(ContractError::LiquidPoolTarget {});
+    }
+
+    // settle rewards and expired locks at the weight before the slash
+    let mut diffs = vec![];
+    diffs.extend(update_weight(deps.storage, &env, &staker)?);
+
+    let stake = BALANCES
+        .may_load(deps.storage, &staker)?
+        .unwrap_or_default();
+    let slashed_stake = stake * percentage;
+    BALANCES.save(deps.storage, &staker, &(stake - slashed_stake))?;
+    slash_positions(deps.storage, &staker, percentage)?;
+    slash_deposits(deps.storage, &staker, percentage)?;
+    slash_pending_transfer(deps.storage, &staker, percentage)?;
+    let total = TOTAL.load(deps.storage)?;
+    TOTAL.save(
+        deps.storage,
+        &total
+            .checked_sub(slashed_stake)
+            .map_err(StdError::overflow)?,
+    )?;
+    diffs.extend(update_weight(deps.storage, &env, &staker)?);
+
+    let slashed_claims = slash_claims(deps.storage, &staker, percentage)?;
+    let slashed = slashed_stake
+        .checked_add(slashed_claims)
+        .map_err(StdError::overflow)?;
+
+    let mut msgs = member_hooks(deps.storage, diffs)?;
+    if !slashed.is_zero() {
+        let config = CONFIG.load(deps.storage)?;
+        msgs.push(SubMsg::new(
+            config.new_transfer(&slashing.treasury, slashed)?,
+        ));
+    }
+
+    let res = Response::new()
+        .add_submessages(msgs)
+        .add_attribute("action", "slash")
+        .add_attribute("address", staker)
+        .add_attribute("percentage", percentage.to_string())
+        .add_attribute("amount", slashed)
+        .add_attribute("treasury", slashing.treasury);
+    Ok(res)
+}
+
+/// Takes percentage of every claim, as unbonding stake is still held by this contract.
+/// Rounds up like the slashed positions and deposits.
+fn slash_claims(
+    storage: &mut dyn Storage,
+    staker: &Addr,
+    percentage: Decimal,
+) -> StdResult<Uint128> {
+    let mut claims = CLAIMS_STORE.may_load(storage, staker)?.unwrap_or_default();
+    if claims.is_empty() {
+        return Ok(Uint128::zero());
+    }
+
+    let mut slashed = Uint128::zero();
+    for claim in claims.iter_mut() {
+        let amount = claim.amount.mul_ceil(percentage);
+        claim.amount -= amount;
+        slashed = slashed.checked_add(amount)?;
+    }
+    claims.retain(|claim| !claim.amount.is_zero());
+    if claims.is_empty() {
+        CLAIMS_STORE.remove(storage, staker);
+    } else {
+        CLAIMS_STORE.save(storage, staker, &claims)?;
+    }
+    let unbonding = UNBONDING.load(storage)?;
+    UNBONDING.save(storage, &unbonding.checked_sub(slashed)?)?;
+    Ok(slashed)
+}
+
+pub fn query_slashing(deps: Deps) -> StdResult<SlashingResponse> {
+    let config = SLASHING.may_load(deps.storage)?;
+    Ok(SlashingResponse {
+        slasher: config.as_ref().and_then(|config| config.slasher.clone()),
+        treasury: config.map(|config| config.treasury),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use cosmwasm_std::testing::{mock_dependencies, mock_env, mock_info};
+    use cw4::{MemberChangedHookMsg, MemberDiff};
+    use cw_utils::Expiration;
+
+    use crate::contract::{execute, instantiate, query_staked};
+    use crate::lockup::query_positions;
+    use crate::msg::{ExecuteMsg, InstantiateMsg};
+    use crate::state::LockTier;
+    use crate::unbonding::query_claims;
+
+    const INIT_ADMIN: &str = "juan";
+    const CW20_ADDRESS: &str = "wasm1234567890";
+    const DAY: u64 = 24 * 60 * 60;
+
+    fn do_instantiate(mut deps: DepsMut) {
+        let msg = InstantiateMsg {
+            addr: Addr::unchecked(CW20_ADDRESS),
+            admin: Some(INIT_ADMIN.into()),
+        };
+        let info = mock_info("creator", &[]);
+        instantiate(deps.branch(), mock_env(), info, msg).unwrap();
+
+        let admin_msgs = vec![
+            ExecuteMsg::UpdateSlashing {
+                slasher: Some("moderation".into()),
+                treasury: "treasury".into(),
+            },
+            ExecuteMsg::AddHook {
+                addr: "guild".into(),
+            },
+            ExecuteMsg::UpdateLockTiers {
+                tiers: vec![LockTier {
+                    duration: 30 * DAY,
+                    multiplier: Decimal::percent(200),
+                }],
+            },
+        ];
+        for msg in admin_msgs {
+            execute(deps.branch(), mock_env(), mock_info(INIT_ADMIN, &[]), msg).unwrap();
+        }
+    }
+
+    fn slash(sender: &str, percentage: u64) -> (MessageInfo, ExecuteMsg) {
+        let msg = ExecuteMsg::Slash {
+            address: "moderator".into(),
+            percentage: Decimal::percent(percentage),
+        };
+        (mock_info(sender, &[]), msg)
+    }
+
+    #[test]
+    fn only_admin_or_slasher_can_slash() {
+        let mut deps = mock_dependencies();
+        let msg = InstantiateMsg {
+            addr: Addr::unchecked(CW20_ADDRESS),
+            admin: Some(INIT_ADMIN.into()),
+        };
+        instantiate(deps.as_mut(), mock_env(), mock_info("creator", &[]), msg).unwrap();
+        let (info, msg) = slash(INIT_ADMIN, 10);
+        let err = execute(deps.as_mut(), mock_env(), info, msg).unwrap_err();
+        assert_eq!(err, ContractError::SlashingNotConfigured {});
+
+        do_instantiate(deps.as_mut());
+        let (info, msg) = slash("moderator", 10);
+        let err = execute(deps.as_mut(), mock_env(), info, msg).unwrap_err();
+        assert_eq!(err, ContractError::Unauthorized {});
+        let (info, msg) = slash("moderation", 101);
+        let err = execute(deps.as_mut(), mock_env(), info, msg).unwrap_err();
+        assert_eq!(err, ContractError::InvalidSlashPercentage {});
+        for sender in ["moderation", INIT_ADMIN] {
+            let (info, msg) = slash(sender, 10);
+            execute(deps.as_mut(), mock_env(), info, msg).unwrap();
+        }
+        assert_eq!(
+            query_slashing(deps.as_ref()).unwrap(),
+            SlashingResponse {
+                slasher: Some(Addr::unchecked("moderation")),
+                treasury: Some(Addr::unchecked("treasury")),
+            }
+        );
+    }
+
+    #[test]
+    fn slash_stake_and_locks() {
+        let mut deps = mock_dependencies();
+        do_instantiate(deps.as_mut());
+        let env = mock_env();
+
+        let msg = ExecuteMsg::Stake {
+            amount: 400u128.into(),
+            lock_duration: None,
+        };
+        execute(deps.as_mut(), env.clone(), mock_info("moderator", &[]), msg).unwrap();
+        let msg = ExecuteMsg::Stake {
+            amount: 600u128.into(),
+            lock_duration: Some(30 * DAY),
+        };
+        execute(deps.as_mut(), env.clone(), mock_info("moderator", &[]), msg).unwrap();
+
+        let (info, msg) = slash("moderation", 25);
+        let res = execute(deps.as_mut(), env.clone(), info, msg).unwrap();
+        let diff = MemberDiff::new("moderator", Some(1600), Some(1200));
+        let config = CONFIG.load(&deps.storage).unwrap();
+        assert_eq!(
+            res.messages,
+            vec![
+                SubMsg::new(
+                    MemberChangedHookMsg::one(diff)
+                        .into_cosmos_msg("guild")
+                        .unwrap()
+                ),
+                SubMsg::new(
+                    config
+                        .new_transfer(&Addr::unchecked("treasury"), 250u128.into())
+                        .unwrap()
+                ),
+            ]
+        );
+
+        let staked = query_staked(deps.as_ref(), "moderator".into()).unwrap();
+        assert_eq!(staked.stake, Uint128::new(750));
+        assert_eq!(TOTAL.load(&deps.storage).unwrap(), Uint128::new(750));
+        let positions = query_positions(deps.as_ref(), env, "moderator".into()).unwrap();
+        assert_eq!(positions.positions[0].amount, Uint128::new(450));
+    }
+
+    #[test]
+    fn slash_unbonding_claims() {
+        let mut deps = mock_dependencies();
+        do_instantiate(deps.as_mut());
+        let msg = ExecuteMsg::UpdateConfig {
+            reward_rate: None,
+            unbonding_period: Some(DAY),
+            limits: None,
+        };
+        execute(deps.as_mut(), mock_env(), mock_info(INIT_ADMIN, &[]), msg).unwrap();
+        let mut env = mock_env();
+        let msgs = vec![
+            ExecuteMsg::Stake {
+                amount: 100u128.into(),
+                lock_duration: None,
+            },
+            ExecuteMsg::Withdraw {
+                amount: 41u128.into(),
+            },
+        ];
+        for msg in msgs {
+            execute(deps.as_mut(), env.clone(), mock_info("moderator", &[]), msg).unwrap();
+        }
+        env.block.time = env.block.time.plus_seconds(10);
+        let msg = ExecuteMsg::Withdraw {
+            amount: 21u128.into(),
+        };
+        execute(deps.as_mut(), env.clone(), mock_info("moderator", &[]), msg).unwrap();
+
+        // 19 of the stake left and 21 and 11 of the claims, which round up
+        let (info, msg) = slash("moderation", 50);
+        let res = execute(deps.as_mut(), env.clone(), info, msg).unwrap();
+        assert!(res.attributes.contains(&attr("amount", "51")));
+        let claims = query_claims(deps.as_ref(), "moderator".into())
+            .unwrap()
+            .claims;
+        assert_eq!(
+            claims,
+            vec![
+                Claim::new(
+                    20,
+                    Expiration::AtTime(mock_env().block.time.plus_seconds(DAY))
+                ),
+                Claim::new(10, Expiration::AtTime(env.block.time.plus_seconds(DAY))),
+            ]
+        );
+        assert_eq!(UNBONDING.load(&deps.storage).unwrap(), Uint128::new(30));
+    }
+}
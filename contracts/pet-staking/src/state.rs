@@ -1,11 +1,22 @@
 use cosmwasm_schema::cw_serde;
-use cosmwasm_std::{to_json_binary, Addr, CosmosMsg, StdResult, Uint128, WasmMsg};
+use cosmwasm_std::{
+    to_json_binary, Addr, CosmosMsg, Decimal, Empty, QuerierWrapper, StdResult, Timestamp, Uint128,
+    WasmMsg,
+};
 use cw20::Cw20ExecuteMsg;
-use cw_controllers::{Admin, Claims};
+use cw_controllers::{Admin, Claims, Hooks};
 use cw_storage_plus::{Item, Map, SnapshotMap, Strategy};
-use cw_utils::Duration;
+use cw_utils::{Duration, Expiration};
 
-pub const CLAIMS: Claims = Claims::new("claims");
+use crate::msg::{TokenQueryMsg, TokenVestingResponse};
+
+pub const CLAIMS_KEY: &str = "claims";
+/// Unbonded stake waiting to be released
+pub const CLAIMS: Claims = Claims::new(CLAIMS_KEY);
+/// Seconds withdrawn stake waits in `CLAIMS` before it can be claimed, paid out at once if 0
+pub const UNBONDING_PERIOD: Item<u64> = Item::new("unbonding_period");
+/// Sum of all claims, held by this contract on top of the stakes
+pub const UNBONDING: Item<Uint128> = Item::new("unbonding");
 
 #[cw_serde]
 pub struct Config {
@@ -13,7 +24,19 @@ pub struct Config {
     pub addr: Addr,
 }
 
+#[cw_serde]
+#[derive(Default)]
+pub struct StakeLimits {
+    /// Smallest amount a single stake may add
+    pub min_stake: Uint128,
+    pub max_stake_per_address: Option<Uint128>,
+    pub max_total_stake: Option<Uint128>,
+}
+
+pub const STAKE_LIMITS: Item<StakeLimits> = Item::new("stake_limits");
+
 pub const ADMIN: Admin = Admin::new("admin");
+pub const HOOKS: Hooks = Hooks::new("cw4-hooks");
 pub const CONFIG: Item<Config> = Item::new("config");
 pub const BALANCES: Map<&Addr, Uint128> = Map::new("balance");
 pub const TOTAL: Item<Uint128> = Item::new("total_coins");
@@ -27,7 +50,150 @@ pub const MEMBERS: SnapshotMap<&Addr, u64> = SnapshotMap::new(
 
 pub const STAKE: Map<&Addr, Uint128> = Map::new("stake");
 
+#[cw_serde]
+pub struct LockTier {
+    /// How long stakes in this tier are locked, in seconds
+    pub duration: u64,
+    /// Weight of a locked token relative to an unlocked one
+    pub multiplier: Decimal,
+}
+
+#[cw_serde]
+pub struct Position {
+    pub amount: Uint128,
+    /// Weight the position adds on top of its amount until it unlocks
+    pub boost: Uint128,
+    pub unlock_at: Timestamp,
+}
+
+/// Locks unlock at the end of a day of this many seconds, and the locks of an address which
+/// unlock on the same day are merged into one position
+pub const LOCK_BUCKET: u64 = 24 * 60 * 60;
+
+pub const LOCK_TIERS: Item<Vec<LockTier>> = Item::new("lock_tiers");
+/// Keyed by (staker, unlock time in seconds)
+pub const POSITIONS: Map<(&Addr, u64), Position> = Map::new("positions");
+/// Boost of all positions unlocking at the given time in seconds, taken out of
+/// `TOTAL_WEIGHT` once the rewards are accrued past that time
+pub const EXPIRING_BOOST: Map<u64, Uint128> = Map::new("expiring_boost");
+
+#[cw_serde]
+pub enum PenaltyDestination {
+    /// Penalties stay in this contract and are paid out to the stakers as rewards, split by
+    /// their weight when the penalty is taken
+    RewardPool,
+    /// Penalties are burned
+    Burn,
+}
+
+#[cw_serde]
+pub struct WithdrawPenalty {
+    /// Seconds a deposit must be staked before it can be withdrawn without a penalty
+    pub min_hold: u64,
+    /// Share of an early withdrawal which is taken as penalty
+    pub percentage: Decimal,
+    pub destination: PenaltyDestination,
+}
+
+#[cw_serde]
+pub struct Deposit {
+    pub amount: Uint128,
+    /// Time of the latest deposit merged into this one
+    pub time: Timestamp,
+}
+
+/// Deposits of an address are merged per day of this many seconds, so one address holds
+/// at most one deposit per day of the holding time
+pub const DEPOSIT_BUCKET: u64 = 24 * 60 * 60;
+
+pub const WITHDRAW_PENALTY: Item<WithdrawPenalty> = Item::new("withdraw_penalty");
+/// Keyed by (staker, day of the deposit)
+pub const DEPOSITS: Map<(&Addr, u64), Deposit> = Map::new("deposits");
+/// Penalties collected for stakers, held by this contract on top of the stakes. Rewards are
+/// paid out of it before any are minted.
+pub const REWARD_POOL: Item<Uint128> = Item::new("reward_pool");
+
+#[cw_serde]
+pub struct RewardState {
+    /// Tokens minted to stakers per second
+    pub rate: Uint128,
+    /// Rewards earned per unit of weight since the start
+    pub index: Decimal,
+    pub last_update: Timestamp,
+}
+
+#[cw_serde]
+#[derive(Default)]
+pub struct StakerRewards {
+    /// Reward index when the rewards of this staker were last settled
+    pub index: Decimal,
+    pub pending: Uint128,
+}
+
+pub const REWARD_STATE: Item<RewardState> = Item::new("reward_state");
+pub const STAKER_REWARDS: Map<&Addr, StakerRewards> = Map::new("staker_rewards");
+/// Sum of all member weights without the boosts which expired, rewards are split by it
+pub const TOTAL_WEIGHT: Item<Uint128> = Item::new("total_weight");
+/// Weight of each staker, which rewards are split by. `MEMBERS` holds it as cw4 points,
+/// capped at `u64::MAX`. Stakers weighed before this was kept weigh their points.
+pub const WEIGHTS: Map<&Addr, Uint128> = Map::new("weights");
+/// Reward index at the given time in seconds, for every time positions unlocked at.
+/// Boosts earn rewards up to this index, even if their position is dropped later.
+pub const EXPIRY_INDEX: Map<u64, Decimal> = Map::new("expiry_index");
+pub const AUTO_COMPOUND: Map<&Addr, Empty> = Map::new("auto_compound");
+
+#[cw_serde]
+pub struct LiquidPool {
+    /// cw20 minted to liquid stakers, this contract must be its minter
+    pub receipt: Addr,
+    /// Receipts outstanding
+    pub shares: Uint128,
+}
+
+/// Liquid stakes are staked as this contract's own address in `BALANCES`, which also makes
+/// it a member with the pool's weight. The pool is not bound by the maximum stake per
+/// address and takes no locks. Its deposits are shared by all liquid stakers, so redeeming
+/// pays the withdraw penalty once it reaches into stake the pool received too recently.
+pub const LIQUID_POOL: Item<LiquidPool> = Item::new("liquid_pool");
+
+/// Operators allowed to stake, withdraw and claim for an owner, keyed by (owner, operator)
+pub const OPERATORS: Map<(&Addr, &Addr), Expiration> = Map::new("operators");
+
+#[cw_serde]
+pub struct SlashingConfig {
+    /// Can slash stakers besides the admin
+    pub slasher: Option<Addr>,
+    /// Receives the slashed tokens
+    pub treasury: Addr,
+}
+
+pub const SLASHING: Item<SlashingConfig> = Item::new("slashing");
+
+#[cw_serde]
+pub struct PendingTransfer {
+    pub recipient: Addr,
+    /// Stake of the sender reserved for the recipient, which it can no longer withdraw
+    pub amount: Uint128,
+    pub release_at: Timestamp,
+}
+
+/// Stake transfers waiting out the unbonding period, at most one per sender. The stake stays
+/// with the sender until the transfer completes, so slashes of the sender still reach it.
+pub const PENDING_TRANSFERS: Map<&Addr, PendingTransfer> = Map::new("pending_transfers");
+
 impl Config {
+    /// Vesting tokens of the address which cannot be transferred yet. Tokens without vesting
+    /// do not answer the query, so nothing is locked with them.
+    pub fn query_locked(&self, querier: &QuerierWrapper, address: &Addr) -> Uint128 {
+        let msg = TokenQueryMsg::Vesting {
+            address: address.into(),
+        };
+        querier
+            .query_wasm_smart::<TokenVestingResponse>(&self.addr, &msg)
+            .map(|vesting| vesting.locked)
+            .unwrap_or_default()
+    }
+
     pub fn new_transfer_from_msg(
         self,
         sender: &Addr,
@@ -60,6 +226,16 @@ impl Config {
         Ok(execute.into())
     }
 
+    pub fn new_burn(self, amount: Uint128) -> StdResult<CosmosMsg> {
+        let msg = to_json_binary(&Cw20ExecuteMsg::Burn { amount })?;
+        let execute = WasmMsg::Execute {
+            contract_addr: self.addr.into(),
+            msg,
+            funds: vec![],
+        };
+        Ok(execute.into())
+    }
+
     pub fn new_mint(self, recipient: &Addr, amount: Uint128) -> StdResult<CosmosMsg> {
         let msg = to_json_binary(&Cw20ExecuteMsg::Mint {
             recipient: recipient.into(),
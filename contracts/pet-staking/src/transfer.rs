@@ -0,0 +1,477 @@
+use cosmwasm_std::{
+    Addr, Decimal, Deps, DepsMut, Env, MessageInfo, Response, StdError, StdResult, Storage, SubMsg,
+    Uint128,
+};
+
+use crate::error::ContractError;
+use crate::limits::{assert_max_stake, assert_min_stake, load_limits};
+use crate::lockup::{member_hooks, transfer_positions, update_weight};
+use crate::msg::PendingTransferResponse;
+use crate::penalty::transfer_deposits;
+use crate::rewards::transfer_pending;
+use crate::state::{PendingTransfer, BALANCES, CONFIG, PENDING_TRANSFERS, UNBONDING_PERIOD};
+
+/// Moves amount of the sender's stake to the recipient, together with the locks, deposit
+/// times and share of pending rewards backing it. The staked tokens stay in this contract.
+/// Like a stake, the amount moved must reach the minimum stake. If an unbonding period is
+/// set, the stake is only reserved for the recipient and moves with `CompleteTransferStake`
+/// once the period passed, so slashes of the sender still reach it until then.
+pub fn execute_transfer_stake(
+    deps: DepsMut,
+    env: Env,
+    info: MessageInfo,
+    recipient: String,
+    amount: Uint128,
+) -> Result<Response, ContractError> {
+    let recipient = deps.api.addr_validate(&recipient)?;
+    if recipient == info.sender {
+        return Err(ContractError::CannotTransferToSelf {});
+    }
+    if recipient == env.contract.address {
+        return Err(ContractError::LiquidPoolTarget {});
+    }
+    // vesting tokens may be staked, but only by their holder, who cannot sell them this way
+    let config = CONFIG.load(deps.storage)?;
+    if !config.query_locked(&deps.querier, &info.sender).is_zero() {
+        return Err(ContractError::VestingLocked {});
+    }
+    let limits = load_limits(deps.storage)?;
+    assert_min_stake(&limits, amount)?;
+
+    let period = UNBONDING_PERIOD.may_load(deps.storage)?.unwrap_or_default();
+    if period == 0 {
+        let reserved = reserved_stake(deps.storage, &info.sender)?;
+        let msgs = move_stake(deps, &env, &info.sender, &recipient, amount, reserved)?;
+        let res = Response::new()
+            .add_submessages(msgs)
+            .add_attribute("action", "transfer_stake")
+            .add_attribute("from", info.sender)
+            .add_attribute("to", recipient)
+            .add_attribute("amount", amount);
+        return Ok(res);
+    }
+
+    // a sender reserves stake for one recipient at a time, so slashes stay cheap
+    let reserved = match PENDING_TRANSFERS.may_load(deps.storage, &info.sender)? {
+        Some(pending) if pending.recipient != recipient => {
+            return Err(ContractError::TransferPending {})
+        }
+        Some(pending) => pending.amount,
+        None => Uint128::zero(),
+    }
+    .checked_add(amount)
+    .map_err(StdError::overflow)?;
+    let stake = BALANCES
+        .may_load(deps.storage, &info.sender)?
+        .unwrap_or_default();
+    stake.checked_sub(reserved).map_err(StdError::overflow)?;
+    let received = BALANCES
+        .may_load(deps.storage, &recipient)?
+        .unwrap_or_default()
+        .checked_add(reserved)
+        .map_err(StdError::overflow)?;
+    assert_max_stake(&limits, received)?;
+
+    // adding to a pending transfer waits the whole period again
+    let release_at = env.block.time.plus_seconds(period);
+    let pending = PendingTransfer {
+        recipient: recipient.clone(),
+        amount: reserved,
+        release_at,
+    };
+    PENDING_TRANSFERS.save(deps.storage, &info.sender, &pending)?;
+
+    let res = Response::new()
+        .add_attribute("action", "transfer_stake")
+        .add_attribute("from", info.sender)
+        .add_attribute("to", recipient)
+        .add_attribute("amount", amount)
+        .add_attribute("release_at", release_at.to_string());
+    Ok(res)
+}
+
+/// Moves the stake reserved by the pending transfer of the sender once the unbonding period
+/// passed. Anyone can do this.
+pub fn execute_complete_transfer_stake(
+    deps: DepsMut,
+    env: Env,
+    sender: String,
+) -> Result<Response, ContractError> {
+    let sender = deps.api.addr_validate(&sender)?;
+    let pending = PENDING_TRANSFERS
+        .may_load(deps.storage, &sender)?
+        .ok_or(ContractError::NoPendingTransfer {})?;
+    if env.block.time < pending.release_at {
+        return Err(ContractError::TransferNotReleased {});
+    }
+    PENDING_TRANSFERS.remove(deps.storage, &sender);
+
+    // slashes may have taken the whole reservation
+    let msgs = if pending.amount.is_zero() {
+        vec![]
+    } else {
+        move_stake(
+            deps,
+            &env,
+            &sender,
+            &pending.recipient,
+            pending.amount,
+            Uint128::zero(),
+        )?
+    };
+
+    let res = Response::new()
+        .add_submessages(msgs)
+        .add_attribute("action", "complete_transfer_stake")
+        .add_attribute("from", sender)
+        .add_attribute("to", pending.recipient)
+        .add_attribute("amount", pending.amount);
+    Ok(res)
+}
+
+/// Drops the pending transfer of the sender, releasing the stake it reserved.
+pub fn execute_cancel_transfer_stake(
+    deps: DepsMut,
+    info: MessageInfo,
+) -> Result<Response, ContractError> {
+    let pending = PENDING_TRANSFERS
+        .may_load(deps.storage, &info.sender)?
+        .ok_or(ContractError::NoPendingTransfer {})?;
+    PENDING_TRANSFERS.remove(deps.storage, &info.sender);
+
+    let res = Response::new()
+        .add_attribute("action", "cancel_transfer_stake")
+        .add_attribute("from", info.sender)
+        .add_attribute("to", pending.recipient)
+        .add_attribute("amount", pending.amount);
+    Ok(res)
+}
+
+/// Stake of the staker reserved by its pending transfer, which it cannot withdraw.
+pub fn reserved_stake(storage: &dyn Storage, staker: &Addr) -> StdResult<Uint128> {
+    Ok(PENDING_TRANSFERS
+        .may_load(storage, staker)?
+        .map(|pending| pending.amount)
+        .unwrap_or_default())
+}
+
+/// Takes percentage of the stake reserved by the pending transfer of the staker. Rounds up,
+/// so the reservation never exceeds the slashed stake.
+pub fn slash_pending_transfer(
+    storage: &mut dyn Storage,
+    staker: &Addr,
+    percentage: Decimal,
+) -> StdResult<()> {
+    if let Some(mut pending) = PENDING_TRANSFERS.may_load(storage, staker)? {
+        pending.amount -= pending.amount.mul_ceil(percentage);
+        PENDING_TRANSFERS.save(storage, staker, &pending)?;
+    }
+    Ok(())
+}
+
+// moves stake with its locks, deposits and pending rewards, keeping `reserved` with the
+// sender, and returns the hook messages for the weight changes
+fn move_stake(
+    deps: DepsMut,
+    env: &Env,
+    sender: &Addr,
+    recipient: &Addr,
+    amount: Uint128,
+    reserved: Uint128,
+) -> Result<Vec<SubMsg>, ContractError> {
+    let limits = load_limits(deps.storage)?;
+    let time = &env.block.time;
+
+    // settle rewards at the current weights and drop expired locks first
+    let mut diffs = vec![];
+    diffs.extend(update_weight(deps.storage, env, sender)?);
+    diffs.extend(update_weight(deps.storage, env, recipient)?);
+
+    let stake = BALANCES.may_load(deps.storage, sender)?.unwrap_or_default();
+    let remaining = stake.checked_sub(amount).map_err(StdError::overflow)?;
+    if remaining < reserved {
+        return Err(ContractError::StakeReserved {});
+    }
+    let received = BALANCES
+        .may_load(deps.storage, recipient)?
+        .unwrap_or_default()
+        .checked_add(amount)
+        .map_err(StdError::overflow)?;
+    assert_max_stake(&limits, received)?;
+    transfer_positions(deps.storage, time, sender, recipient, stake, amount)?;
+    transfer_deposits(deps.storage, time, sender, recipient, stake, amount)?;
+    transfer_pending(deps.storage, sender, recipient, stake, amount)?;
+
+    BALANCES.save(deps.storage, sender, &remaining)?;
+    BALANCES.save(deps.storage, recipient, &received)?;
+    diffs.extend(update_weight(deps.storage, env, sender)?);
+    diffs.extend(update_weight(deps.storage, env, recipient)?);
+    Ok(member_hooks(deps.storage, diffs)?)
+}
+
+pub fn query_pending_transfer(deps: Deps, address: String) -> StdResult<PendingTransferResponse> {
+    let address = deps.api.addr_validate(&address)?;
+    let transfer = PENDING_TRANSFERS.may_load(deps.storage, &address)?;
+    Ok(PendingTransferResponse { transfer })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use cosmwasm_std::testing::{mock_dependencies, mock_env, mock_info};
+    use cosmwasm_std::{Binary, ContractResult, Order, SystemResult, WasmQuery};
+
+    use crate::contract::{execute, instantiate, query_staked};
+    use crate::lockup::{query_positions, unlock_time};
+    use crate::msg::{ExecuteMsg, InstantiateMsg};
+    use crate::penalty::query_simulate_withdraw;
+    use crate::rewards::query_rewards;
+    use crate::state::{LockTier, PenaltyDestination, WithdrawPenalty, DEPOSITS, MEMBERS, TOTAL};
+
+    const INIT_ADMIN: &str = "juan";
+    const CW20_ADDRESS: &str = "wasm1234567890";
+    const DAY: u64 = 24 * 60 * 60;
+
+    fn do_instantiate(mut deps: DepsMut) {
+        let msg = InstantiateMsg {
+            addr: Addr::unchecked(CW20_ADDRESS),
+            admin: Some(INIT_ADMIN.into()),
+        };
+        let info = mock_info("creator", &[]);
+        instantiate(deps.branch(), mock_env(), info, msg).unwrap();
+
+        let admin_msgs = vec![
+            ExecuteMsg::UpdateLockTiers {
+                tiers: vec![LockTier {
+                    duration: 30 * DAY,
+                    multiplier: Decimal::percent(200),
+                }],
+            },
+            ExecuteMsg::UpdateWithdrawPenalty {
+                penalty: Some(WithdrawPenalty {
+                    min_hold: 10 * DAY,
+                    percentage: Decimal::percent(10),
+                    destination: PenaltyDestination::RewardPool,
+                }),
+            },
+            ExecuteMsg::UpdateConfig {
+                reward_rate: Some(3u128.into()),
+                unbonding_period: None,
+                limits: None,
+            },
+        ];
+        for msg in admin_msgs {
+            execute(deps.branch(), mock_env(), mock_info(INIT_ADMIN, &[]), msg).unwrap();
+        }
+    }
+
+    fn transfer(
+        deps: DepsMut,
+        env: &Env,
+        recipient: &str,
+        amount: u128,
+    ) -> Result<Response, ContractError> {
+        let msg = ExecuteMsg::TransferStake {
+            recipient: recipient.into(),
+            amount: amount.into(),
+        };
+        execute(deps, env.clone(), mock_info("old_wallet", &[]), msg)
+    }
+
+    #[test]
+    fn stake_moves_with_locks_and_rewards() {
+        let mut deps = mock_dependencies();
+        do_instantiate(deps.as_mut());
+        let mut env = mock_env();
+
+        let msg = ExecuteMsg::Stake {
+            amount: 100u128.into(),
+            lock_duration: None,
+        };
+        execute(
+            deps.as_mut(),
+            env.clone(),
+            mock_info("old_wallet", &[]),
+            msg,
+        )
+        .unwrap();
+        let msg = ExecuteMsg::Stake {
+            amount: 200u128.into(),
+            lock_duration: Some(30 * DAY),
+        };
+        execute(
+            deps.as_mut(),
+            env.clone(),
+            mock_info("old_wallet", &[]),
+            msg,
+        )
+        .unwrap();
+        env.block.time = env.block.time.plus_seconds(100);
+
+        let err = transfer(deps.as_mut(), &env, "old_wallet", 1).unwrap_err();
+        assert_eq!(err, ContractError::CannotTransferToSelf {});
+        let err = transfer(deps.as_mut(), &env, "new_wallet", 301).unwrap_err();
+        assert!(matches!(err, ContractError::Std(StdError::Overflow { .. })));
+
+        // the unlocked stake and half of the locked position move
+        transfer(deps.as_mut(), &env, "new_wallet", 200).unwrap();
+        for (wallet, stake, weight) in [("old_wallet", 100, 200), ("new_wallet", 200, 300)] {
+            let staked = query_staked(deps.as_ref(), wallet.into()).unwrap();
+            assert_eq!(staked.stake, Uint128::new(stake));
+            let positions = query_positions(deps.as_ref(), env.clone(), wallet.into()).unwrap();
+            assert_eq!(positions.weight, Uint128::new(weight));
+            assert_eq!(
+                positions.positions[0].unlock_at,
+                unlock_time(&mock_env().block.time, 30 * DAY)
+            );
+            let member = MEMBERS
+                .load(&deps.storage, &Addr::unchecked(wallet))
+                .unwrap();
+            assert_eq!(Uint128::from(member), Uint128::new(weight));
+        }
+        assert_eq!(TOTAL.load(&deps.storage).unwrap(), Uint128::new(300));
+
+        // rewards earned before are split by the stake moved
+        for (wallet, pending) in [("old_wallet", 100), ("new_wallet", 200)] {
+            let rewards = query_rewards(deps.as_ref(), env.clone(), wallet.into()).unwrap();
+            assert_eq!(rewards.pending, Uint128::new(pending));
+        }
+
+        // the deposit times move along, so the penalty still applies
+        let simulated = query_simulate_withdraw(
+            deps.as_ref(),
+            env.clone(),
+            "new_wallet".into(),
+            100u128.into(),
+        )
+        .unwrap();
+        assert_eq!(simulated.penalty, Uint128::new(10));
+        let deposits: Vec<_> = DEPOSITS
+            .prefix(&Addr::unchecked("new_wallet"))
+            .range(&deps.storage, None, None, Order::Ascending)
+            .collect::<StdResult<_>>()
+            .unwrap();
+        // both stakes were made on the same day, so they are a single deposit
+        assert_eq!(deposits.len(), 1);
+        assert_eq!(deposits[0].1.amount, Uint128::new(200));
+
+        // the rest of the position joins the part moved before
+        transfer(deps.as_mut(), &env, "new_wallet", 100).unwrap();
+        let positions = query_positions(deps.as_ref(), env, "new_wallet".into()).unwrap();
+        assert_eq!(positions.positions.len(), 1);
+        assert_eq!(positions.positions[0].amount, Uint128::new(200));
+        assert_eq!(positions.weight, Uint128::new(500));
+    }
+
+    fn stake(deps: DepsMut, env: &Env, staker: &str, amount: u128) {
+        let msg = ExecuteMsg::Stake {
+            amount: amount.into(),
+            lock_duration: None,
+        };
+        execute(deps, env.clone(), mock_info(staker, &[]), msg).unwrap();
+    }
+
+    #[test]
+    fn vesting_holders_cannot_transfer_stake() {
+        let mut deps = mock_dependencies();
+        do_instantiate(deps.as_mut());
+        deps.querier.update_wasm(|query| match query {
+            WasmQuery::Smart { contract_addr, .. } if contract_addr == CW20_ADDRESS => {
+                let res = Binary::from(br#"{"locked":"1000"}"#);
+                SystemResult::Ok(ContractResult::Ok(res))
+            }
+            _ => panic!("unexpected query"),
+        });
+        let env = mock_env();
+
+        stake(deps.as_mut(), &env, "old_wallet", 100);
+        let err = transfer(deps.as_mut(), &env, "new_wallet", 100).unwrap_err();
+        assert_eq!(err, ContractError::VestingLocked {});
+        let staked = query_staked(deps.as_ref(), "new_wallet".into()).unwrap();
+        assert_eq!(staked.stake, Uint128::zero());
+    }
+
+    #[test]
+    fn transfers_wait_out_unbonding_and_stay_slashable() {
+        let mut deps = mock_dependencies();
+        do_instantiate(deps.as_mut());
+        let admin_msgs = vec![
+            ExecuteMsg::UpdateConfig {
+                reward_rate: None,
+                unbonding_period: Some(DAY),
+                limits: None,
+            },
+            ExecuteMsg::UpdateSlashing {
+                slasher: None,
+                treasury: "treasury".into(),
+            },
+        ];
+        for msg in admin_msgs {
+            execute(deps.as_mut(), mock_env(), mock_info(INIT_ADMIN, &[]), msg).unwrap();
+        }
+        let mut env = mock_env();
+
+        stake(deps.as_mut(), &env, "old_wallet", 200);
+        transfer(deps.as_mut(), &env, "new_wallet", 100).unwrap();
+
+        // the stake stays with the sender, reserved for the recipient
+        let staked = query_staked(deps.as_ref(), "old_wallet".into()).unwrap();
+        assert_eq!(staked.stake, Uint128::new(200));
+        let pending = query_pending_transfer(deps.as_ref(), "old_wallet".into())
+            .unwrap()
+            .transfer
+            .unwrap();
+        assert_eq!(pending.amount, Uint128::new(100));
+        assert_eq!(pending.release_at, env.block.time.plus_seconds(DAY));
+
+        let msg = ExecuteMsg::Withdraw {
+            amount: 150u128.into(),
+        };
+        let err = execute(
+            deps.as_mut(),
+            env.clone(),
+            mock_info("old_wallet", &[]),
+            msg,
+        )
+        .unwrap_err();
+        assert_eq!(err, ContractError::StakeReserved {});
+        let err = transfer(deps.as_mut(), &env, "other_wallet", 50).unwrap_err();
+        assert_eq!(err, ContractError::TransferPending {});
+
+        let complete = ExecuteMsg::CompleteTransferStake {
+            sender: "old_wallet".into(),
+        };
+        let err = execute(
+            deps.as_mut(),
+            env.clone(),
+            mock_info("anyone", &[]),
+            complete.clone(),
+        )
+        .unwrap_err();
+        assert_eq!(err, ContractError::TransferNotReleased {});
+
+        // a slash still reaches the reserved stake
+        let msg = ExecuteMsg::Slash {
+            address: "old_wallet".into(),
+            percentage: Decimal::percent(50),
+        };
+        execute(deps.as_mut(), env.clone(), mock_info(INIT_ADMIN, &[]), msg).unwrap();
+        let pending = query_pending_transfer(deps.as_ref(), "old_wallet".into())
+            .unwrap()
+            .transfer
+            .unwrap();
+        assert_eq!(pending.amount, Uint128::new(50));
+
+        env.block.time = env.block.time.plus_seconds(DAY);
+        execute(deps.as_mut(), env, mock_info("anyone", &[]), complete).unwrap();
+        for (wallet, stake) in [("old_wallet", 50), ("new_wallet", 50)] {
+            let staked = query_staked(deps.as_ref(), wallet.into()).unwrap();
+            assert_eq!(staked.stake, Uint128::new(stake));
+        }
+        assert!(query_pending_transfer(deps.as_ref(), "old_wallet".into())
+            .unwrap()
+            .transfer
+            .is_none());
+    }
+}
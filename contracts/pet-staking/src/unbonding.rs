@@ -0,0 +1,237 @@
+use cosmwasm_std::{
+    attr, Addr, Deps, DepsMut, Env, Response, StdError, StdResult, Storage, SubMsg, Uint128,
+};
+use cw_controllers::ClaimsResponse;
+use cw_utils::Expiration;
+
+use crate::error::ContractError;
+use crate::state::{CLAIMS, CONFIG, UNBONDING, UNBONDING_PERIOD};
+
+/// The longest unbonding period the admin can set, one year
+pub const MAX_UNBONDING_PERIOD: u64 = 365 * 24 * 60 * 60;
+
+pub fn validate_unbonding_period(period: u64) -> Result<(), ContractError> {
+    if period > MAX_UNBONDING_PERIOD {
+        return Err(ContractError::InvalidUnbondingPeriod {
+            max: MAX_UNBONDING_PERIOD,
+        });
+    }
+    Ok(())
+}
+
+/// Returns the transfer of withdrawn stake to the staker, or holds it as a claim
+/// until the unbonding period passed
+pub fn pay_out(
+    storage: &mut dyn Storage,
+    env: &Env,
+    staker: &Addr,
+    amount: Uint128,
+) -> StdResult<Option<SubMsg>> {
+    // cw20 rejects empty transfers, as when the penalty took all of the withdrawal
+    if amount.is_zero() {
+        return Ok(None);
+    }
+    let period = UNBONDING_PERIOD.may_load(storage)?.unwrap_or_default();
+    if period == 0 {
+        let config = CONFIG.load(storage)?;
+        return Ok(Some(SubMsg::new(config.new_transfer(staker, amount)?)));
+    }
+
+    let release_at = Expiration::AtTime(env.block.time.plus_seconds(period));
+    CLAIMS.create_claim(storage, staker, amount, release_at)?;
+    let unbonding = UNBONDING.may_load(storage)?.unwrap_or_default();
+    UNBONDING.save(storage, &unbonding.checked_add(amount)?)?;
+    Ok(None)
+}
+
+/// Pays out every claim of the staker which finished unbonding
+pub fn execute_claim(deps: DepsMut, env: Env, staker: Addr) -> Result<Response, ContractError> {
+    let amount = CLAIMS.claim_tokens(deps.storage, &staker, &env.block, None)?;
+    if amount.is_zero() {
+        return Err(ContractError::NothingToClaim {});
+    }
+
+    let unbonding = UNBONDING.load(deps.storage)?;
+    UNBONDING.save(
+        deps.storage,
+        &unbonding.checked_sub(amount).map_err(StdError::overflow)?,
+    )?;
+
+    let config = CONFIG.load(deps.storage)?;
+    let res = Response::new()
+        .add_submessage(SubMsg::new(config.new_transfer(&staker, amount)?))
+        .add_attributes(vec![
+            attr("action", "claim"),
+            attr("to", staker),
+            attr("amount", amount),
+        ]);
+    Ok(res)
+}
+
+pub fn query_claims(deps: Deps, address: String) -> StdResult<ClaimsResponse> {
+    let address = deps.api.addr_validate(&address)?;
+    CLAIMS.query_claims(deps, &address)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use cosmwasm_std::testing::{mock_dependencies, mock_env, mock_info};
+
+    use crate::contract::{execute, instantiate, query_config};
+    use crate::msg::{ExecuteMsg, InstantiateMsg};
+
+    const INIT_ADMIN: &str = "juan";
+    const CW20_ADDRESS: &str = "wasm1234567890";
+    const PERIOD: u64 = 7 * 24 * 60 * 60;
+
+    fn do_instantiate(mut deps: DepsMut) {
+        let msg = InstantiateMsg {
+            addr: Addr::unchecked(CW20_ADDRESS),
+            admin: Some(INIT_ADMIN.into()),
+        };
+        let info = mock_info("creator", &[]);
+        instantiate(deps.branch(), mock_env(), info, msg).unwrap();
+        let msg = ExecuteMsg::UpdateConfig {
+            reward_rate: None,
+            unbonding_period: Some(PERIOD),
+            limits: None,
+        };
+        execute(deps, mock_env(), mock_info(INIT_ADMIN, &[]), msg).unwrap();
+    }
+
+    fn stake(deps: DepsMut, staker: &str, amount: u128) {
+        let msg = ExecuteMsg::Stake {
+            amount: amount.into(),
+            lock_duration: None,
+        };
+        execute(deps, mock_env(), mock_info(staker, &[]), msg).unwrap();
+    }
+
+    fn withdraw(deps: DepsMut, env: Env, staker: &str, amount: u128) -> Response {
+        let msg = ExecuteMsg::Withdraw {
+            amount: amount.into(),
+        };
+        execute(deps, env, mock_info(staker, &[]), msg).unwrap()
+    }
+
+    #[test]
+    fn withdrawals_wait_for_unbonding() {
+        let mut deps = mock_dependencies();
+        do_instantiate(deps.as_mut());
+        let config = CONFIG.load(&deps.storage).unwrap();
+        stake(deps.as_mut(), "staker", 100);
+
+        let res = withdraw(deps.as_mut(), mock_env(), "staker", 40);
+        assert!(res.messages.is_empty());
+        let mut env = mock_env();
+        env.block.time = env.block.time.plus_seconds(10);
+        withdraw(deps.as_mut(), env.clone(), "staker", 10);
+        let claims = query_claims(deps.as_ref(), "staker".into()).unwrap().claims;
+        assert_eq!(claims.len(), 2);
+        assert_eq!(
+            claims[0].release_at,
+            Expiration::AtTime(mock_env().block.time.plus_seconds(PERIOD))
+        );
+        assert_eq!(UNBONDING.load(&deps.storage).unwrap(), Uint128::new(50));
+
+        let err = execute(
+            deps.as_mut(),
+            mock_env(),
+            mock_info("staker", &[]),
+            ExecuteMsg::Claim {},
+        )
+        .unwrap_err();
+        assert_eq!(err, ContractError::NothingToClaim {});
+
+        // only the first claim has matured
+        env.block.time = mock_env().block.time.plus_seconds(PERIOD);
+        let res = execute(
+            deps.as_mut(),
+            env,
+            mock_info("staker", &[]),
+            ExecuteMsg::Claim {},
+        )
+        .unwrap();
+        assert_eq!(
+            res.messages,
+            vec![SubMsg::new(
+                config
+                    .new_transfer(&Addr::unchecked("staker"), Uint128::new(40))
+                    .unwrap()
+            )]
+        );
+        let claims = query_claims(deps.as_ref(), "staker".into()).unwrap().claims;
+        assert_eq!(claims.len(), 1);
+        assert_eq!(claims[0].amount, Uint128::new(10));
+        assert_eq!(UNBONDING.load(&deps.storage).unwrap(), Uint128::new(10));
+    }
+
+    #[test]
+    fn operators_claim_for_owner() {
+        let mut deps = mock_dependencies();
+        do_instantiate(deps.as_mut());
+        stake(deps.as_mut(), "owner", 100);
+        withdraw(deps.as_mut(), mock_env(), "owner", 100);
+
+        let mut env = mock_env();
+        env.block.time = env.block.time.plus_seconds(PERIOD);
+        let msg = ExecuteMsg::ClaimFrom {
+            owner: "owner".into(),
+        };
+        let err = execute(
+            deps.as_mut(),
+            env.clone(),
+            mock_info("custodian", &[]),
+            msg.clone(),
+        )
+        .unwrap_err();
+        assert_eq!(err, ContractError::Unauthorized {});
+
+        let approve = ExecuteMsg::ApproveOperator {
+            operator: "custodian".into(),
+            expires: None,
+        };
+        execute(deps.as_mut(), env.clone(), mock_info("owner", &[]), approve).unwrap();
+        let res = execute(deps.as_mut(), env, mock_info("custodian", &[]), msg).unwrap();
+        assert_eq!(res.attributes[1], attr("to", "owner"));
+        assert_eq!(res.attributes[2], attr("amount", "100"));
+    }
+
+    #[test]
+    fn unbonding_period_is_bounded() {
+        let mut deps = mock_dependencies();
+        do_instantiate(deps.as_mut());
+        assert_eq!(
+            query_config(deps.as_ref()).unwrap().unbonding_period,
+            PERIOD
+        );
+
+        let msg = ExecuteMsg::UpdateConfig {
+            reward_rate: None,
+            unbonding_period: Some(MAX_UNBONDING_PERIOD + 1),
+            limits: None,
+        };
+        let err = execute(deps.as_mut(), mock_env(), mock_info(INIT_ADMIN, &[]), msg).unwrap_err();
+        assert_eq!(
+            err,
+            ContractError::InvalidUnbondingPeriod {
+                max: MAX_UNBONDING_PERIOD
+            }
+        );
+
+        // without a period withdrawals are paid out at once again
+        let msg = ExecuteMsg::UpdateConfig {
+            reward_rate: None,
+            unbonding_period: Some(0),
+            limits: None,
+        };
+        execute(deps.as_mut(), mock_env(), mock_info(INIT_ADMIN, &[]), msg).unwrap();
+        stake(deps.as_mut(), "staker", 100);
+        let res = withdraw(deps.as_mut(), mock_env(), "staker", 100);
+        assert_eq!(res.messages.len(), 1);
+        let claims = query_claims(deps.as_ref(), "staker".into()).unwrap().claims;
+        assert!(claims.is_empty());
+    }
+}